@@ -0,0 +1,94 @@
+//! Compares a fresh `reqwest::Client` per request (what every peer request used to do) with the shared, pooled client of `HttpTransport`.
+//! Requests go to a local server that answers like `GET /peer/successor/` does, so what's measured is the per-request overhead of the client: the TCP handshake and connection setup that pooling saves on every hop.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crust::http_client;
+use gotham::hyper::service::{make_service_fn, service_fn};
+use gotham::hyper::{Body, Response, Server};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use tokio::runtime::Runtime;
+
+const HOPS: usize = 5; // requests in one lookup, roughly log2 of the ring size.
+const CONCURRENT: usize = 64; // requests in flight at once for the throughput benchmark.
+
+/// Starts a server answering every request with an IP address, like `GET /peer/successor/` does, and returns its address.
+fn start_peer(rt: &Runtime) -> SocketAddr {
+    rt.block_on(async {
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|_| async {
+                Ok::<_, Infallible>(Response::new(Body::from("10.0.0.1")))
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    })
+}
+
+async fn get(client: &reqwest::Client, url: &str) {
+    let body = client.get(url).send().await.unwrap().text().await.unwrap();
+    assert_eq!(body, "10.0.0.1");
+}
+
+fn lookup_latency(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let url = format!("http://{}/peer/successor/", start_peer(&rt));
+    let shared = http_client(None);
+
+    let mut group = c.benchmark_group("lookup_latency");
+    // building a client takes tens of milliseconds, so the default 100 samples would take minutes.
+    group.sample_size(10);
+    group.bench_function(BenchmarkId::new("client_per_request", HOPS), |b| {
+        b.to_async(&rt).iter(|| async {
+            for _ in 0..HOPS {
+                get(&reqwest::Client::new(), &url).await;
+            }
+        })
+    });
+    group.bench_function(BenchmarkId::new("shared_client", HOPS), |b| {
+        b.to_async(&rt).iter(|| async {
+            for _ in 0..HOPS {
+                get(&shared, &url).await;
+            }
+        })
+    });
+    group.finish();
+}
+
+fn throughput(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let url = format!("http://{}/peer/successor/", start_peer(&rt));
+    let shared = http_client(None);
+
+    let mut group = c.benchmark_group("throughput");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(CONCURRENT as u64));
+    group.bench_function(BenchmarkId::new("client_per_request", CONCURRENT), |b| {
+        b.to_async(&rt).iter(|| async {
+            let requests = (0..CONCURRENT).map(|_| {
+                let url = url.clone();
+                tokio::spawn(async move { get(&reqwest::Client::new(), &url).await })
+            });
+            for request in requests.collect::<Vec<_>>() {
+                request.await.unwrap();
+            }
+        })
+    });
+    group.bench_function(BenchmarkId::new("shared_client", CONCURRENT), |b| {
+        b.to_async(&rt).iter(|| async {
+            let requests = (0..CONCURRENT).map(|_| {
+                let (client, url) = (shared.clone(), url.clone());
+                tokio::spawn(async move { get(&client, &url).await })
+            });
+            for request in requests.collect::<Vec<_>>() {
+                request.await.unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookup_latency, throughput);
+criterion_main!(benches);
@@ -0,0 +1,5 @@
+// Generates the gRPC peer service (see `src/grpc.rs`) from `proto/chord.proto`.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tonic_build::compile_protos("proto/chord.proto")?;
+    Ok(())
+}
@@ -0,0 +1,323 @@
+use crate::ring::Owners;
+use crate::{identifier, ChordError, Hello, Location, VisInfo};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Certificate, Method, RequestBuilder, Url};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(16); // upper bound for the exponential backoff between two retries.
+
+/// How a `Client` talks to the ring.
+/// token - the client token of the ring (`CRUST_CLIENT_TOKEN` on the nodes), sent as a bearer token.
+/// ca - the cluster CA, for rings that serve their client API over HTTPS. Node certificates aren't issued for an address, so host names aren't checked.
+/// request_timeout - how long one request can take, the hops through the ring included.
+/// retries - how many times a request that failed with a retryable error (see `ChordError::is_retryable`) is retried.
+/// backoff - the wait before the first retry, doubled at every retry up to `MAX_BACKOFF`. A `Retry-After` from the node takes precedence.
+/// cache_ttl - how long the client trusts the nodes it saw in the ring to own the keys between their IDs (see `Client::ring`).
+#[derive(Clone)]
+pub struct ClientConfig {
+    pub token: Option<String>,
+    pub ca: Option<Certificate>,
+    pub request_timeout: Duration,
+    pub retries: u32,
+    pub backoff: Duration,
+    pub cache_ttl: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            token: None,
+            ca: None,
+            request_timeout: Duration::from_secs(10),
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            cache_ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A client of the client API of a ring.
+/// Requests about a key go straight to the node that owns it, as far as the client knows from the last `/v1/ring/` it fetched. When it doesn't know, they go to a seed, which routes them through the ring. Every node of the ring is assumed to serve its client API on the port of the seed.
+/// Requests that fail with a retryable error are retried with exponential backoff. If a node didn't answer, the client forgets what it knew about the ring, and moves on to the next seed if that node was the seed.
+/// It's cheap to clone, and clones share their connections and what they know about the ring.
+#[derive(Clone)]
+pub struct Client {
+    inner: Arc<Inner>,
+}
+
+/// seed - index in `seeds` of the node requests go to when the owner of a key isn't known.
+struct Inner {
+    http: reqwest::Client,
+    scheme: &'static str,
+    seeds: Vec<SocketAddr>,
+    seed: AtomicUsize,
+    hello: Hello,
+    config: ClientConfig,
+    owners: RwLock<Option<Owners>>,
+}
+
+/// Who a request goes to.
+enum Target {
+    Owner(u64),
+    Seed,
+    Node(SocketAddr),
+}
+
+impl Client {
+    /// Connects to the ring through the first of `seeds` that answers its `/hello/` with a hello, and checks that this client can talk to it (see `Hello::check_client_compatible`). Seeds that can't be reached or don't answer with a hello are skipped. If no seed answers, returns the error of the last one.
+    pub async fn connect(seeds: &[SocketAddr], config: ClientConfig) -> Result<Self, ChordError> {
+        if seeds.is_empty() {
+            return Err(ChordError::BadRequest("No seed to connect to".to_string()));
+        }
+        let builder = reqwest::Client::builder().timeout(config.request_timeout);
+        let (builder, scheme) = match &config.ca {
+            Some(ca) => {
+                let builder = builder
+                    .use_native_tls()
+                    .tls_built_in_root_certs(false)
+                    .add_root_certificate(ca.clone())
+                    .danger_accept_invalid_hostnames(true);
+                (builder, "https")
+            }
+            None => (builder, "http"),
+        };
+        let http = builder.build().map_err(ChordError::bad_request)?;
+        let mut last_error = None;
+        for (i, seed) in seeds.iter().enumerate() {
+            let request = http.get(url(scheme, *seed, &["hello"]));
+            let hello = match send(request, seed.ip()).await {
+                Ok(body) => serde_json::from_str::<Hello>(&body).map_err(ChordError::from),
+                Err((e, _)) => Err(e),
+            };
+            let hello = match hello {
+                Ok(hello) => hello,
+                Err(e) => {
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+            hello.check_client_compatible()?;
+            let inner = Inner {
+                http,
+                scheme,
+                seeds: seeds.to_vec(),
+                seed: AtomicUsize::new(i),
+                hello,
+                config,
+                owners: RwLock::new(None),
+            };
+            return Ok(Client {
+                inner: Arc::new(inner),
+            });
+        }
+        Err(last_error.expect("There's at least one seed"))
+    }
+
+    /// returns what the seed the client connected through said about the ring.
+    pub fn hello(&self) -> &Hello {
+        &self.inner.hello
+    }
+
+    /// returns the ID of `key` on the ring.
+    pub fn key_id(&self, key: &str) -> u64 {
+        identifier(key, self.inner.hello.ring_bits)
+    }
+
+    /// Inserts `key` into the ring, and returns the ID of the node it was stored at.
+    pub async fn put(&self, key: &str) -> Result<u64, ChordError> {
+        let target = Target::Owner(self.key_id(key));
+        let form = [("key", key)];
+        let body = self
+            .call(target, Method::POST, &["v1", "key"], Some(&form))
+            .await?;
+        Ok(body.trim().parse()?)
+    }
+
+    /// returns whether `key` is in the ring.
+    pub async fn contains(&self, key: &str) -> Result<bool, ChordError> {
+        let target = Target::Owner(self.key_id(key));
+        let body = self
+            .call(target, Method::GET, &["v1", "key", key], None)
+            .await?;
+        Ok(body.trim().parse()?)
+    }
+
+    /// returns where `key` is, or `None` if it isn't in the ring. The ring only stores keys, so this is `contains` followed by `lookup`.
+    pub async fn get(&self, key: &str) -> Result<Option<Location>, ChordError> {
+        if self.contains(key).await? {
+            Ok(Some(self.lookup(key).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Deletes `key` from the ring, and from its replicas. Returns whether it was there: a delete that is retried after the first attempt went through returns `false`.
+    pub async fn delete(&self, key: &str) -> Result<bool, ChordError> {
+        let target = Target::Owner(self.key_id(key));
+        let body = self
+            .call(target, Method::DELETE, &["v1", "key", key], None)
+            .await?;
+        Ok(body.trim().parse()?)
+    }
+
+    /// Asks the ring which node is responsible for `key`.
+    pub async fn lookup(&self, key: &str) -> Result<Location, ChordError> {
+        let id = self.key_id(key);
+        let path = ["v1", "lookup", &id.to_string()];
+        let body = self
+            .call(Target::Owner(id), Method::GET, &path, None)
+            .await?;
+        let node: IpAddr = body.trim().parse()?;
+        Ok(Location {
+            id,
+            node,
+            node_id: identifier(&node.to_string(), self.inner.hello.ring_bits),
+        })
+    }
+
+    /// returns the nodes of the ring, walking it from a seed, and remembers which node owns which keys.
+    pub async fn ring(&self) -> Result<Vec<VisInfo>, ChordError> {
+        let body = self
+            .call(Target::Seed, Method::GET, &["v1", "ring"], None)
+            .await?;
+        let ring: Vec<VisInfo> = serde_json::from_str(&body)?;
+        *self.inner.owners.write().unwrap() = Some(Owners::new(&ring));
+        Ok(ring)
+    }
+
+    /// returns the `/info` of the node at `node`.
+    pub async fn info(&self, node: SocketAddr) -> Result<serde_json::Value, ChordError> {
+        let body = self
+            .call(Target::Node(node), Method::GET, &["v1", "info"], None)
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Sends a request to `target`, with `form` as its body if there's one, and returns the body of the response. Retryable errors are retried (see `ClientConfig`).
+    async fn call(
+        &self,
+        target: Target,
+        method: Method,
+        segments: &[&str],
+        form: Option<&[(&str, &str)]>,
+    ) -> Result<String, ChordError> {
+        let config = &self.inner.config;
+        let mut backoff = config.backoff;
+        let mut attempt = 0;
+        loop {
+            let node = match target {
+                Target::Owner(id) => self.owner(id).await,
+                Target::Seed => self.seed(),
+                Target::Node(node) => node,
+            };
+            let mut request = self.request(method.clone(), node, segments);
+            if let Some(form) = form {
+                request = request.form(form);
+            }
+            match send(request, node.ip()).await {
+                Ok(body) => return Ok(body),
+                Err((e, retry_after)) if e.is_retryable() && attempt < config.retries => {
+                    self.forget(node, &e);
+                    tokio::time::sleep(retry_after.unwrap_or(backoff)).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
+                }
+                Err((e, _)) => return Err(e),
+            }
+        }
+    }
+
+    fn request(&self, method: Method, node: SocketAddr, segments: &[&str]) -> RequestBuilder {
+        let request = (self.inner.http).request(method, url(self.inner.scheme, node, segments));
+        match &self.inner.config.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    fn seed(&self) -> SocketAddr {
+        let seeds = &self.inner.seeds;
+        seeds[self.inner.seed.load(Ordering::Relaxed) % seeds.len()]
+    }
+
+    /// returns the node that owns the ID `id` as far as the client knows, fetching the ring from the seed if what it knew is too old. If the ring can't be fetched, the seed is used, and the ring isn't fetched again before `cache_ttl`.
+    async fn owner(&self, id: u64) -> SocketAddr {
+        let port = self.seed().port();
+        let ttl = self.inner.config.cache_ttl;
+        let fresh = |owners: &Option<Owners>| {
+            owners
+                .as_ref()
+                .filter(|owners| owners.is_fresh(ttl))
+                .is_some()
+        };
+        let stale = !fresh(&self.inner.owners.read().unwrap());
+        if stale {
+            // a single attempt: if the ring is being repaired, the request is better off at the seed.
+            let request = self.request(Method::GET, self.seed(), &["v1", "ring"]);
+            let ring = match send(request, self.seed().ip()).await {
+                Ok(body) => serde_json::from_str(&body).unwrap_or_default(),
+                Err(_) => Vec::new(),
+            };
+            *self.inner.owners.write().unwrap() = Some(Owners::new(&ring));
+        }
+        let owners = self.inner.owners.read().unwrap();
+        match owners.as_ref().and_then(|owners| owners.owner(id)) {
+            Some((_, ip)) => SocketAddr::new(ip, port),
+            None => self.seed(),
+        }
+    }
+
+    /// Forgets what the client knew about the ring after `node` failed with `e`: if a node didn't answer, the ring is changing. If the seed itself didn't answer, the next one is used.
+    fn forget(&self, node: SocketAddr, e: &ChordError) {
+        let suspect = match e.suspect() {
+            Some(suspect) => suspect,
+            None => return,
+        };
+        *self.inner.owners.write().unwrap() = None;
+        if suspect == node.ip() && node == self.seed() {
+            self.inner.seed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// builds the URL of the path made of `segments` on `node`. Segments are percent-encoded, so keys can contain anything.
+fn url(scheme: &str, node: SocketAddr, segments: &[&str]) -> Url {
+    let mut url = Url::parse(&format!("{}://{}/", scheme, node)).expect("Invalid node URL");
+    url.path_segments_mut()
+        .expect("The URL has a path")
+        .pop_if_empty()
+        .extend(segments)
+        .push("");
+    url
+}
+
+/// Sends `request` to `node` and returns the body of the response. Error responses are turned back into the `ChordError` the node sent, along with how long the node asked to wait before retrying, if it did.
+async fn send(
+    request: RequestBuilder,
+    node: IpAddr,
+) -> Result<String, (ChordError, Option<Duration>)> {
+    let unreachable = |e: reqwest::Error| match e.is_timeout() {
+        true => ChordError::Timeout { node },
+        false => ChordError::Unreachable {
+            node,
+            reason: e.to_string(),
+        },
+    };
+    let resp = request.send().await.map_err(|e| (unreachable(e), None))?;
+    let status = resp.status();
+    let retry_after = resp
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs);
+    let body = resp.text().await.map_err(|e| (unreachable(e), None))?;
+    if status.is_success() {
+        Ok(body)
+    } else {
+        Err((ChordError::from_response(status, &body), retry_after))
+    }
+}
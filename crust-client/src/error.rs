@@ -0,0 +1,192 @@
+use reqwest::StatusCode;
+use serde_derive::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::net::{AddrParseError, IpAddr};
+use std::num::ParseIntError;
+use std::str::ParseBoolError;
+
+/// Everything that can go wrong in a Chord operation, on this node or on a peer it asked.
+/// Timeout - `node` didn't answer in time.
+/// Unreachable - `node` couldn't be reached at all (connection refused, reset, dropped, ...).
+/// NotOwner - the node asked isn't responsible for what it was asked about (for example a rejoin sent to a node that isn't the rejoining node's successor).
+/// BadRequest - the request itself was invalid.
+/// Conflict - the request is valid but clashes with what the node already knows (for example a rejoin from a stale incarnation).
+/// Unauthorized - the request lacks valid credentials: a peer signature (see `ClusterSecret`) or a client token.
+/// Forbidden - the caller isn't allowed to make this request, for example a client without a peer certificate calling a peer endpoint.
+/// RateLimited - the client sent more requests than it is allowed to, and should slow down.
+/// Incompatible - the other node runs with ring parameters (or a protocol version) this node can't work with, see `Hello::check_compatible`.
+/// Internal - anything else, including a reply that couldn't be understood.
+/// Errors cross the wire as an `ErrorBody` with a stable `code`, so the calling side gets the same `ChordError` back (see `from_response`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChordError {
+    Timeout { node: IpAddr },
+    Unreachable { node: IpAddr, reason: String },
+    NotOwner(String),
+    BadRequest(String),
+    Conflict(String),
+    Unauthorized(String),
+    Forbidden(String),
+    RateLimited(String),
+    Incompatible(String),
+    Internal(String),
+}
+
+/// The JSON body of an error response.
+/// code - one of the stable codes returned by `ChordError::code()`.
+/// node - the node that didn't answer, for `timeout` and `unreachable`.
+#[derive(Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<IpAddr>,
+}
+
+impl ChordError {
+    pub fn bad_request(error: impl fmt::Display) -> Self {
+        ChordError::BadRequest(error.to_string())
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ChordError::Timeout { .. } => "timeout",
+            ChordError::Unreachable { .. } => "unreachable",
+            ChordError::NotOwner(_) => "not_owner",
+            ChordError::BadRequest(_) => "bad_request",
+            ChordError::Conflict(_) => "conflict",
+            ChordError::Unauthorized(_) => "unauthorized",
+            ChordError::Forbidden(_) => "forbidden",
+            ChordError::RateLimited(_) => "rate_limited",
+            ChordError::Incompatible(_) => "incompatible",
+            ChordError::Internal(_) => "internal",
+        }
+    }
+
+    /// the HTTP status this error is returned with. A node that didn't answer is a `503 Service Unavailable`, since retrying once maintenance has routed around it should succeed.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ChordError::Timeout { .. } | ChordError::Unreachable { .. } => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            ChordError::NotOwner(_) => StatusCode::MISDIRECTED_REQUEST,
+            ChordError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ChordError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ChordError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ChordError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ChordError::Conflict(_) | ChordError::Incompatible(_) => StatusCode::CONFLICT,
+            ChordError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// returns the node suspected to be down, if this error is about a node that didn't answer. These errors are retryable.
+    pub fn suspect(&self) -> Option<IpAddr> {
+        match self {
+            ChordError::Timeout { node } | ChordError::Unreachable { node, .. } => Some(*node),
+            _ => None,
+        }
+    }
+
+    /// whether the same request may succeed if it's retried a bit later: a node didn't answer (maintenance will route around it), or the client was rate limited.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ChordError::Timeout { .. }
+                | ChordError::Unreachable { .. }
+                | ChordError::RateLimited(_)
+        )
+    }
+
+    pub fn to_body(&self) -> ErrorBody {
+        ErrorBody {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            node: self.suspect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.to_body()).expect("Can't serialize error")
+    }
+
+    /// The inverse of `to_body`. Unknown codes (from a newer node) become `Internal`.
+    pub fn from_body(body: ErrorBody) -> Self {
+        match (body.code.as_str(), body.node) {
+            ("timeout", Some(node)) => ChordError::Timeout { node },
+            ("unreachable", Some(node)) => ChordError::Unreachable {
+                node,
+                reason: body.message,
+            },
+            ("not_owner", _) => ChordError::NotOwner(body.message),
+            ("bad_request", _) => ChordError::BadRequest(body.message),
+            ("conflict", _) => ChordError::Conflict(body.message),
+            ("unauthorized", _) => ChordError::Unauthorized(body.message),
+            ("forbidden", _) => ChordError::Forbidden(body.message),
+            ("rate_limited", _) => ChordError::RateLimited(body.message),
+            ("incompatible", _) => ChordError::Incompatible(body.message),
+            _ => ChordError::Internal(body.message),
+        }
+    }
+
+    /// Maps an error response from a peer back to a `ChordError`. Peers that don't send an `ErrorBody` (older versions) are mapped by status code.
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        if let Ok(body) = serde_json::from_str::<ErrorBody>(body) {
+            return Self::from_body(body);
+        }
+        let message = format!("{} ({})", body, status);
+        match status {
+            StatusCode::BAD_REQUEST => ChordError::BadRequest(message),
+            StatusCode::CONFLICT => ChordError::Conflict(message),
+            StatusCode::UNAUTHORIZED => ChordError::Unauthorized(message),
+            StatusCode::FORBIDDEN => ChordError::Forbidden(message),
+            StatusCode::TOO_MANY_REQUESTS => ChordError::RateLimited(message),
+            StatusCode::MISDIRECTED_REQUEST => ChordError::NotOwner(message),
+            _ => ChordError::Internal(message),
+        }
+    }
+}
+
+impl fmt::Display for ChordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChordError::Timeout { node } => write!(f, "node {} didn't answer in time", node),
+            ChordError::Unreachable { node, reason } => {
+                write!(f, "node {} is unreachable ({})", node, reason)
+            }
+            ChordError::NotOwner(message)
+            | ChordError::BadRequest(message)
+            | ChordError::Conflict(message)
+            | ChordError::Unauthorized(message)
+            | ChordError::Forbidden(message)
+            | ChordError::RateLimited(message)
+            | ChordError::Incompatible(message)
+            | ChordError::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ChordError {}
+
+impl From<AddrParseError> for ChordError {
+    fn from(e: AddrParseError) -> Self {
+        ChordError::Internal(format!("invalid IP address: {}", e))
+    }
+}
+
+impl From<ParseIntError> for ChordError {
+    fn from(e: ParseIntError) -> Self {
+        ChordError::Internal(format!("invalid number: {}", e))
+    }
+}
+
+impl From<ParseBoolError> for ChordError {
+    fn from(e: ParseBoolError) -> Self {
+        ChordError::Internal(format!("invalid boolean: {}", e))
+    }
+}
+
+impl From<serde_json::Error> for ChordError {
+    fn from(e: serde_json::Error) -> Self {
+        ChordError::Internal(format!("invalid JSON: {}", e))
+    }
+}
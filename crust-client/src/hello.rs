@@ -0,0 +1,81 @@
+use crate::ChordError;
+use serde_derive::{Deserialize, Serialize};
+
+/// Version of the peer protocol, as `major.minor`. Nodes only talk to nodes of the same major version; minor versions only add to the protocol, so they can be mixed during a rolling upgrade.
+/// Nodes that predate the `/hello/` handshake speak version 1.0.
+pub const PROTOCOL_VERSION: &str = "1.4";
+
+/// Name of the hash function keys and IP addresses are mapped onto the ring with (see `identifier`).
+pub const HASH_ALGORITHM: &str = "std-default-hasher";
+
+/// What a node tells the nodes that talk to it about itself (GET /hello/). A node only joins a ring through a seed that uses the same ring parameters (see `check_compatible`).
+/// protocol - `PROTOCOL_VERSION`.
+/// ring_bits, hash, replication_factor - the parameters every node of a ring has to agree on.
+/// version - the version of crust the node runs. Informational only.
+/// transports - the peer transports the node supports, most preferred first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hello {
+    pub protocol: String,
+    pub ring_bits: u32,
+    pub hash: String,
+    pub replication_factor: u32,
+    pub version: String,
+    pub transports: Vec<String>,
+}
+
+impl Hello {
+    /// Checks that a node introducing itself with `self` can join the ring that `seed` belongs to, and returns a `ChordError::Incompatible` listing every mismatch if it can't. A different minor protocol version (or crust version) is fine.
+    pub fn check_compatible(&self, seed: &Hello) -> Result<(), ChordError> {
+        let mut mismatches = Vec::new();
+        if major(&self.protocol) != major(&seed.protocol) {
+            mismatches.push(format!(
+                "protocol version {} (mine is {})",
+                seed.protocol, self.protocol
+            ));
+        }
+        if self.ring_bits != seed.ring_bits {
+            mismatches.push(format!(
+                "{}-bit IDs (mine are {}-bit)",
+                seed.ring_bits, self.ring_bits
+            ));
+        }
+        if self.hash != seed.hash {
+            mismatches.push(format!("hash {} (mine is {})", seed.hash, self.hash));
+        }
+        if self.replication_factor != seed.replication_factor {
+            mismatches.push(format!(
+                "replication factor {} (mine is {})",
+                seed.replication_factor, self.replication_factor
+            ));
+        }
+        if !mismatches.is_empty() {
+            return Err(ChordError::Incompatible(format!(
+                "The seed uses {}",
+                mismatches.join(", ")
+            )));
+        }
+        if self.protocol != seed.protocol || self.version != seed.version {
+            tracing::info!(
+                "The seed runs crust {} (protocol {}), I run crust {} (protocol {}). Continuing, the versions are compatible.",
+                seed.version, seed.protocol, self.version, self.protocol
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that a client of this library can talk to a node introducing itself with `self`: it has to speak the same major protocol version and hash keys the same way, or the client would send keys to the wrong nodes.
+    pub fn check_client_compatible(&self) -> Result<(), ChordError> {
+        if major(&self.protocol) != major(PROTOCOL_VERSION) || self.hash != HASH_ALGORITHM {
+            return Err(ChordError::Incompatible(format!(
+                "The node speaks protocol {} with hash {}, this client protocol {} with hash {}",
+                self.protocol, self.hash, PROTOCOL_VERSION, HASH_ALGORITHM
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// returns the major part of a `major.minor` version.
+fn major(version: &str) -> &str {
+    version.split('.').next().unwrap_or(version)
+}
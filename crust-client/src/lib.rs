@@ -0,0 +1,23 @@
+//! An async client for crust rings, and the types crust nodes send over the wire.
+//! Nodes use the same types (`crust` re-exports them), so a client built on this crate can't drift from the nodes it talks to.
+//! ```no_run
+//! # async fn example() -> Result<(), crust_client::ChordError> {
+//! use crust_client::{Client, ClientConfig};
+//! let seeds = ["172.17.0.2:8000".parse().unwrap(), "172.17.0.3:8000".parse().unwrap()];
+//! let client = Client::connect(&seeds, ClientConfig::default()).await?;
+//! client.put("apple").await?;
+//! assert!(client.contains("apple").await?);
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+pub use client::{Client, ClientConfig};
+mod error;
+pub use error::{ChordError, ErrorBody};
+mod hello;
+pub use hello::{Hello, HASH_ALGORITHM, PROTOCOL_VERSION};
+mod ring;
+pub use ring::{identifier, Location, VisInfo};
+
+pub use reqwest::Certificate;
@@ -0,0 +1,64 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Hash a key (or the IP address of a node) and return hash(key)%2^ring_bits, its ID on a ring of `ring_bits`-bit IDs (see `HASH_ALGORITHM`).
+pub fn identifier(key: &str, ring_bits: u32) -> u64 {
+    let mut s = DefaultHasher::new();
+    key.hash(&mut s);
+    s.finish() % (1 << ring_bits)
+}
+
+/// Used for constructing a JSON of successor pointers (GET /v1/ring/): `node`, with ID `from`, has the node with ID `to` as its successor. This is used by the Javascript in `index.html` to render the Chord ring, and by `Client::ring`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VisInfo {
+    pub node: IpAddr,
+    pub from: u64,
+    pub to: u64,
+}
+
+impl VisInfo {
+    pub fn new(node: IpAddr, from: u64, to: u64) -> Self {
+        Self { node, from, to }
+    }
+}
+
+/// Where a key lives: its ID `id`, and the node responsible for it, `node`, whose ID is `node_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub id: u64,
+    pub node: IpAddr,
+    pub node_id: u64,
+}
+
+/// The nodes of the ring as a client last saw them, sorted by ID, so that the owner of a key (the successor of its ID) can be found without asking the ring.
+/// It's a hint: the ring changes under it, so it's refreshed after `ttl`, and dropped when one of its nodes doesn't answer (see `Client`). It's empty if the ring couldn't be fetched, and requests go to a seed until it's refreshed.
+pub(crate) struct Owners {
+    nodes: Vec<(u64, IpAddr)>,
+    fetched: Instant,
+}
+
+impl Owners {
+    pub(crate) fn new(ring: &[VisInfo]) -> Self {
+        let mut nodes: Vec<(u64, IpAddr)> =
+            ring.iter().map(|edge| (edge.from, edge.node)).collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        Owners {
+            nodes,
+            fetched: Instant::now(),
+        }
+    }
+
+    pub(crate) fn is_fresh(&self, ttl: Duration) -> bool {
+        self.fetched.elapsed() < ttl
+    }
+
+    /// returns the node responsible for `id`: the first one at or after it on the ring.
+    pub(crate) fn owner(&self, id: u64) -> Option<(u64, IpAddr)> {
+        let next = self.nodes.iter().find(|(node_id, _)| *node_id >= id);
+        next.or_else(|| self.nodes.first()).copied()
+    }
+}
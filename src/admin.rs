@@ -0,0 +1,109 @@
+use crate::{ring_bits, ChordError};
+use serde_derive::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A maintenance task that an admin can run on demand (see `ChordNode::run_now`), instead of waiting for its next round.
+/// Finger(i) - refreshes the `i`th entry of the finger table. `Fingers` refreshes every entry, in order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Maintenance {
+    Stabilize,
+    Finger(usize),
+    Fingers,
+    SuccessorList,
+    ReplicaSync,
+}
+
+impl Maintenance {
+    /// The task that refreshes finger `index`, failing with `ChordError::BadRequest` if the finger table has no such entry.
+    pub fn finger(index: usize) -> Result<Self, ChordError> {
+        if index >= ring_bits() as usize {
+            return Err(ChordError::BadRequest(format!(
+                "Invalid finger {}, the finger table has {} entries",
+                index,
+                ring_bits()
+            )));
+        }
+        Ok(Maintenance::Finger(index))
+    }
+
+    /// returns the name of the task in `Metrics` and in `maintenance` spans.
+    pub fn task(&self) -> &'static str {
+        match self {
+            Maintenance::Stabilize => "stabilize",
+            Maintenance::Finger(_) | Maintenance::Fingers => "fix_fingers",
+            Maintenance::SuccessorList => "successor_list",
+            Maintenance::ReplicaSync => "replica_sync",
+        }
+    }
+}
+
+impl fmt::Display for Maintenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Maintenance::Finger(index) => write!(f, "fix_finger {}", index),
+            Maintenance::Fingers => write!(f, "fix_every_finger"),
+            task => write!(f, "{}", task.task()),
+        }
+    }
+}
+
+/// What an admin action changed, as returned by the `/v1/admin` API.
+/// action - what was done, like `stabilize` or `fix_finger 3`.
+/// error - why the action failed, if it did. A failed maintenance round repairs the pointers of the node, so it can still change things.
+/// changes - every field of the state that changed, by name, with its value before and after. Entries of lists that keep their length (like the finger table) are compared one by one, as `finger_table[3]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StateDiff {
+    pub action: String,
+    pub error: Option<String>,
+    pub changes: BTreeMap<String, Change>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub before: Value,
+    pub after: Value,
+}
+
+impl StateDiff {
+    /// Compares two snapshots of a state, which are JSON objects.
+    pub fn new(
+        action: String,
+        before: &Value,
+        after: &Value,
+        result: Result<(), ChordError>,
+    ) -> Self {
+        let mut changes = BTreeMap::new();
+        let empty = serde_json::Map::new();
+        let before_fields = before.as_object().unwrap_or(&empty);
+        let after_fields = after.as_object().unwrap_or(&empty);
+        for (name, after) in after_fields {
+            let before = before_fields.get(name).unwrap_or(&Value::Null);
+            match (before, after) {
+                (Value::Array(before), Value::Array(after)) if before.len() == after.len() => {
+                    let entries = before.iter().zip(after).enumerate();
+                    for (i, (before, after)) in entries {
+                        add_change(&mut changes, format!("{}[{}]", name, i), before, after);
+                    }
+                }
+                _ => add_change(&mut changes, name.clone(), before, after),
+            }
+        }
+        StateDiff {
+            action,
+            error: result.err().map(|e| e.to_string()),
+            changes,
+        }
+    }
+}
+
+fn add_change(changes: &mut BTreeMap<String, Change>, name: String, before: &Value, after: &Value) {
+    if before != after {
+        let change = Change {
+            before: before.clone(),
+            after: after.clone(),
+        };
+        changes.insert(name, change);
+    }
+}
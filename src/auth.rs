@@ -0,0 +1,228 @@
+use crate::{ChordError, TlsConfig};
+use gotham_derive::StateData;
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use rand::Rng;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable with the secret shared by every node of the cluster. When it is set, nodes sign the requests they send each other, and only accept signed requests on their peer endpoints.
+pub const SECRET_ENV: &str = "CRUST_CLUSTER_SECRET";
+/// Environment variable with the token clients have to send (`Authorization: Bearer <token>`) to use the `/key` API. When it isn't set, the `/key` API is open.
+pub const CLIENT_TOKEN_ENV: &str = "CRUST_CLIENT_TOKEN";
+/// Environment variable with the token admins have to send (`Authorization: Bearer <token>`) to use the `/v1/admin` API. When it isn't set, the `/v1/admin` API is disabled.
+pub const ADMIN_TOKEN_ENV: &str = "CRUST_ADMIN_TOKEN";
+
+// headers (and gRPC metadata keys) a signed request carries.
+pub const TIMESTAMP_HEADER: &str = "x-crust-timestamp";
+pub const NONCE_HEADER: &str = "x-crust-nonce";
+pub const SIGNATURE_HEADER: &str = "x-crust-signature";
+
+const MAX_CLOCK_SKEW: u64 = 30; // seconds a signed request stays valid for, in either direction, so clocks of the nodes have to be this close.
+
+/// How this node authenticates itself to its peers, and its peers, clients and admins to itself: TLS (see `TlsConfig`), the cluster secret, the token of the `/key` API and the token of the `/admin` API. Everything is off by default.
+#[derive(StateData, Clone, Default)]
+pub struct Security {
+    pub tls: Option<TlsConfig>,
+    pub secret: Option<ClusterSecret>,
+    pub client_token: Option<String>,
+    pub admin_token: Option<String>,
+}
+
+impl Security {
+    /// Reads the configuration from `TlsConfig::from_env`, `SECRET_ENV`, `CLIENT_TOKEN_ENV` and `ADMIN_TOKEN_ENV`.
+    pub fn from_env() -> Result<Self, ChordError> {
+        let non_empty = |name| env::var(name).ok().filter(|value| !value.is_empty());
+        Ok(Security {
+            tls: TlsConfig::from_env()?,
+            secret: non_empty(SECRET_ENV).map(|secret| ClusterSecret::new(secret.as_bytes())),
+            client_token: non_empty(CLIENT_TOKEN_ENV),
+            admin_token: non_empty(ADMIN_TOKEN_ENV),
+        })
+    }
+
+    /// returns the token of `audience`, if there is one.
+    fn token(&self, audience: Audience) -> Option<&str> {
+        match audience {
+            Audience::Client => self.client_token.as_deref(),
+            Audience::Admin => self.admin_token.as_deref(),
+        }
+    }
+
+    /// Checks the `Authorization` header of a request from `audience` against its token. Without a token, the request is let through if the API of `audience` is open, and fails with `ChordError::Forbidden` if it is disabled (see `Audience::required`).
+    pub fn authorize(
+        &self,
+        audience: Audience,
+        authorization: Option<&str>,
+    ) -> Result<(), ChordError> {
+        match self.token(audience) {
+            Some(token) => check_bearer(token, authorization, audience.name()),
+            None if audience.required() => Err(ChordError::Forbidden(format!(
+                "The {} API is disabled, set {} to enable it",
+                audience.name(),
+                audience.env()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Who an API that takes a bearer token is for: clients of the `/key` API, or admins of the `/admin` API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Audience {
+    Client,
+    Admin,
+}
+
+impl Audience {
+    /// the name of the token, in errors.
+    fn name(self) -> &'static str {
+        match self {
+            Audience::Client => "client",
+            Audience::Admin => "admin",
+        }
+    }
+
+    /// the environment variable the token is read from.
+    fn env(self) -> &'static str {
+        match self {
+            Audience::Client => CLIENT_TOKEN_ENV,
+            Audience::Admin => ADMIN_TOKEN_ENV,
+        }
+    }
+
+    /// returns true if the API is disabled without a token, false if it is open.
+    fn required(self) -> bool {
+        self == Audience::Admin
+    }
+}
+
+/// Checks that `authorization` is `Bearer <token>`. `kind` names the token in errors.
+fn check_bearer(token: &str, authorization: Option<&str>, kind: &str) -> Result<(), ChordError> {
+    match authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+        Some(presented) if equal(presented.as_bytes(), token.as_bytes()) => Ok(()),
+        Some(_) => Err(unauthorized(&format!("Invalid {} token", kind))),
+        None => Err(unauthorized(&format!("A {} token is required", kind))),
+    }
+}
+
+/// The headers of a signed request. `mac` is the hex encoded HMAC-SHA256 of the method, path, `timestamp`, `nonce` and body of the request (see `ClusterSecret::mac`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub timestamp: u64,
+    pub nonce: String,
+    pub mac: String,
+}
+
+impl Signature {
+    /// returns the signature headers of a request, as (name, value) pairs.
+    pub fn headers(&self) -> [(&'static str, String); 3] {
+        [
+            (TIMESTAMP_HEADER, self.timestamp.to_string()),
+            (NONCE_HEADER, self.nonce.clone()),
+            (SIGNATURE_HEADER, self.mac.clone()),
+        ]
+    }
+
+    /// Reads a signature from the headers of a request, `None` if any of them is missing or malformed.
+    pub fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Option<Self> {
+        Some(Signature {
+            timestamp: header(TIMESTAMP_HEADER)?.parse().ok()?,
+            nonce: header(NONCE_HEADER)?.to_string(),
+            mac: header(SIGNATURE_HEADER)?.to_string(),
+        })
+    }
+}
+
+/// The secret shared by the nodes of a cluster, used to sign and verify the requests they send each other.
+/// A request is only accepted once: the nonces of the requests verified in the last `MAX_CLOCK_SKEW` seconds are remembered, and older requests are rejected by their timestamp.
+#[derive(Clone)]
+pub struct ClusterSecret {
+    key: Vec<u8>,
+    seen: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl ClusterSecret {
+    pub fn new(secret: &[u8]) -> Self {
+        ClusterSecret {
+            key: secret.to_vec(),
+            seen: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Signs a request with a fresh nonce.
+    pub fn sign(&self, method: &str, path: &str, body: &[u8]) -> Signature {
+        let nonce: [u8; 16] = rand::thread_rng().gen();
+        let nonce = hex(&nonce);
+        let timestamp = now();
+        let mac = hex(&self.mac(method, path, timestamp, &nonce, body));
+        Signature {
+            timestamp,
+            nonce,
+            mac,
+        }
+    }
+
+    /// Fails with `ChordError::Unauthorized` unless `signature` is a valid signature of this request, made by a node with the same secret in the last `MAX_CLOCK_SKEW` seconds, and wasn't seen before.
+    pub fn verify(
+        &self,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        signature: Option<Signature>,
+    ) -> Result<(), ChordError> {
+        let signature = signature.ok_or_else(|| unauthorized("The request isn't signed"))?;
+        let now = now();
+        if signature.timestamp + MAX_CLOCK_SKEW < now || signature.timestamp > now + MAX_CLOCK_SKEW
+        {
+            return Err(unauthorized("The signature has expired"));
+        }
+        let expected = hex(&self.mac(method, path, signature.timestamp, &signature.nonce, body));
+        if !equal(expected.as_bytes(), signature.mac.as_bytes()) {
+            return Err(unauthorized("Invalid signature"));
+        }
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, timestamp| *timestamp + MAX_CLOCK_SKEW >= now);
+        if seen.insert(signature.nonce, signature.timestamp).is_some() {
+            return Err(unauthorized("The request was already received"));
+        }
+        Ok(())
+    }
+
+    /// HMAC-SHA256 of `method`, `path`, `timestamp` and `nonce` (one per line), followed by `body`.
+    fn mac(&self, method: &str, path: &str, timestamp: u64, nonce: &str, body: &[u8]) -> Vec<u8> {
+        let key = PKey::hmac(&self.key).expect("Can't create an HMAC key");
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).expect("Can't create an HMAC");
+        let head = format!("{}\n{}\n{}\n{}\n", method, path, timestamp, nonce);
+        signer
+            .update(head.as_bytes())
+            .expect("Can't compute an HMAC");
+        signer.update(body).expect("Can't compute an HMAC");
+        signer.sign_to_vec().expect("Can't compute an HMAC")
+    }
+}
+
+fn unauthorized(reason: &str) -> ChordError {
+    ChordError::Unauthorized(reason.to_string())
+}
+
+/// Compares two secrets in constant time.
+fn equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && memcmp::eq(a, b)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("The clock is before 1970")
+        .as_secs()
+}
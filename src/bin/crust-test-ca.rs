@@ -0,0 +1,19 @@
+//! Generates a throwaway cluster CA and a node certificate for trying TLS out locally, e.g. `cargo run --bin crust-test-ca -- certs`.
+//! Then start every node with `CRUST_TLS_CERT=certs/node.pem CRUST_TLS_KEY=certs/node-key.pem CRUST_TLS_CA=certs/ca.pem`.
+
+use crust::TestCa;
+use std::path::Path;
+
+fn main() {
+    let dir = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "certs".to_string());
+    let result = TestCa::generate().and_then(|ca| ca.write_to(Path::new(&dir)));
+    match result {
+        Ok(()) => println!("Wrote a test CA and a node certificate to {}", dir),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
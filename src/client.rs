@@ -0,0 +1,261 @@
+use crust::{ChordError, ClientOptions, Command, InfoCommand, KeyCommand, VisInfo};
+use crust_client::{Certificate, Client, ClientConfig};
+use serde_json::{json, Value};
+
+// exit status of the client subcommands. 2 is taken by clap, for invalid arguments.
+const EXIT_NOT_FOUND: i32 = 1; // the key of a get or delete wasn't in the ring.
+const EXIT_INVALID: i32 = 2;
+const EXIT_UNAVAILABLE: i32 = 3; // the node, or a node of the ring it asked, didn't answer (or rate limited the client), even after retrying. Retrying later may work.
+const EXIT_REFUSED: i32 = 4; // the node answered with any other error.
+
+/// Runs a client subcommand against the ring `--node` belongs to (see `crust_client::Client`), prints its result as a table (or as JSON with `--json`), and returns the exit status of the process.
+pub async fn run(command: Command) -> i32 {
+    let result = match command {
+        Command::Put(command) => put(command).await,
+        Command::Get(command) => get(command).await,
+        Command::Delete(command) => delete(command).await,
+        Command::Lookup(command) => lookup(command).await,
+        Command::Ring(options) => ring(options).await,
+        Command::Info(command) => info(command).await,
+        Command::Serve(_) => unreachable!("serve isn't a client subcommand"),
+    };
+    match result {
+        Ok(status) => status,
+        Err(Failure::Invalid(e)) => {
+            eprintln!("{}", e);
+            EXIT_INVALID
+        }
+        Err(Failure::Chord(e)) => {
+            eprintln!("Error ({}): {}", e.code(), e);
+            if e.is_retryable() {
+                EXIT_UNAVAILABLE
+            } else {
+                EXIT_REFUSED
+            }
+        }
+    }
+}
+
+/// Why a client subcommand failed: its arguments couldn't be used, or the node answered with an error.
+enum Failure {
+    Invalid(String),
+    Chord(ChordError),
+}
+
+impl From<ChordError> for Failure {
+    fn from(e: ChordError) -> Self {
+        Failure::Chord(e)
+    }
+}
+
+/// connects to the ring through `--node`.
+async fn connect(options: &ClientOptions) -> Result<Client, Failure> {
+    let ca = match &options.ca {
+        Some(path) => {
+            let pem = std::fs::read(path)
+                .map_err(|e| Failure::Invalid(format!("Can't read {}: {}", path.display(), e)))?;
+            let ca = Certificate::from_pem(&pem)
+                .map_err(|e| Failure::Invalid(format!("Invalid CA {}: {}", path.display(), e)))?;
+            Some(ca)
+        }
+        None => None,
+    };
+    let config = ClientConfig {
+        token: options.token.clone(),
+        ca,
+        ..ClientConfig::default()
+    };
+    Ok(Client::connect(&[options.node], config).await?)
+}
+
+/// crust put <key>: prints the ID of the node the key was stored at.
+async fn put(command: KeyCommand) -> Result<i32, Failure> {
+    let client = connect(&command.client).await?;
+    let node_id = client.put(&command.key).await?;
+    let output = json!({"key": command.key, "node_id": node_id});
+    print(
+        &command.client,
+        &output,
+        &["KEY", "STORED AT ID"],
+        vec![vec![command.key.clone(), node_id.to_string()]],
+    );
+    Ok(0)
+}
+
+/// crust get <key>: exits with `EXIT_NOT_FOUND` if the key isn't in the ring.
+async fn get(command: KeyCommand) -> Result<i32, Failure> {
+    let client = connect(&command.client).await?;
+    let found = client.contains(&command.key).await?;
+    let output = json!({"key": command.key, "found": found});
+    print(
+        &command.client,
+        &output,
+        &["KEY", "FOUND"],
+        vec![vec![command.key.clone(), yes_no(found)]],
+    );
+    Ok(if found { 0 } else { EXIT_NOT_FOUND })
+}
+
+/// crust delete <key>: exits with `EXIT_NOT_FOUND` if the key wasn't in the ring.
+async fn delete(command: KeyCommand) -> Result<i32, Failure> {
+    let client = connect(&command.client).await?;
+    let deleted = client.delete(&command.key).await?;
+    let output = json!({"key": command.key, "deleted": deleted});
+    print(
+        &command.client,
+        &output,
+        &["KEY", "DELETED"],
+        vec![vec![command.key.clone(), yes_no(deleted)]],
+    );
+    Ok(if deleted { 0 } else { EXIT_NOT_FOUND })
+}
+
+/// crust lookup <key>: the ID of the key and the node responsible for it.
+async fn lookup(command: KeyCommand) -> Result<i32, Failure> {
+    let client = connect(&command.client).await?;
+    let location = client.lookup(&command.key).await?;
+    let mut output = json!(location);
+    output["key"] = json!(command.key);
+    let row = vec![
+        command.key.clone(),
+        location.id.to_string(),
+        location.node.to_string(),
+        location.node_id.to_string(),
+    ];
+    print(
+        &command.client,
+        &output,
+        &["KEY", "ID", "NODE", "NODE ID"],
+        vec![row],
+    );
+    Ok(0)
+}
+
+/// crust ring: walks the ring from the node (see `ChordNode::ring_info`).
+async fn ring(options: ClientOptions) -> Result<i32, Failure> {
+    let client = connect(&options).await?;
+    let mut ring: Vec<VisInfo> = client.ring().await?;
+    ring.sort_by_key(|edge| edge.from);
+    let output = serde_json::to_value(&ring).expect("Can't serialize the ring");
+    let rows = ring
+        .iter()
+        .map(|edge| {
+            let successor = ring.iter().find(|next| next.from == edge.to);
+            let successor = successor.map_or_else(|| "?".to_string(), |next| next.node.to_string());
+            vec![
+                edge.node.to_string(),
+                edge.from.to_string(),
+                successor,
+                edge.to.to_string(),
+            ]
+        })
+        .collect();
+    print(
+        &options,
+        &output,
+        &["NODE", "ID", "SUCCESSOR", "SUCCESSOR ID"],
+        rows,
+    );
+    Ok(0)
+}
+
+/// crust info [node]: the `/info` of the node, summed up.
+async fn info(command: InfoCommand) -> Result<i32, Failure> {
+    let node = command.target.unwrap_or(command.client.node);
+    let client = connect(&ClientOptions {
+        node,
+        ..command.client.clone()
+    })
+    .await?;
+    let info = client.info(node).await?;
+    let text = |value: &Value| match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".to_string(),
+        other => other.to_string(),
+    };
+    let count = |value: &Value| {
+        value
+            .as_array()
+            .map_or(0, |values| values.len())
+            .to_string()
+    };
+    let successors: Vec<String> = info["successor_list"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|entry| format!("{} ({})", text(&entry[0]), text(&entry[1])))
+        .collect();
+    let alive = info["members"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|member| member["status"] == "alive")
+        .count();
+    let rows = vec![
+        vec!["node".to_string(), text(&info["self_ip"])],
+        vec!["id".to_string(), text(&info["self_id"])],
+        vec!["incarnation".to_string(), text(&info["incarnation"])],
+        vec!["peer transport".to_string(), text(&info["peer_transport"])],
+        vec![
+            "predecessor".to_string(),
+            format!(
+                "{} ({})",
+                text(&info["predecessor"]),
+                text(&info["predecessor_id"])
+            ),
+        ],
+        vec!["successors".to_string(), successors.join(", ")],
+        vec!["keys".to_string(), count(&info["hash_set"])],
+        vec!["suspects".to_string(), count(&info["suspects"])],
+        vec![
+            "members".to_string(),
+            format!("{} alive of {}", alive, count(&info["members"])),
+        ],
+        vec!["ring bits".to_string(), text(&info["config"]["ring_bits"])],
+        vec![
+            "replication factor".to_string(),
+            text(&info["config"]["replication_factor"]),
+        ],
+    ];
+    print(&command.client, &info, &[], rows);
+    Ok(0)
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+/// Prints `output` as JSON with `--json`, or else `rows` as a table under `headers` (no header line if there are none).
+fn print(options: &ClientOptions, output: &Value, headers: &[&str], rows: Vec<Vec<String>>) {
+    if options.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(output).expect("Can't serialize the output")
+        );
+        return;
+    }
+    let mut lines: Vec<Vec<String>> = Vec::new();
+    if !headers.is_empty() {
+        lines.push(headers.iter().map(|header| header.to_string()).collect());
+    }
+    lines.extend(rows);
+    let columns = lines.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            lines
+                .iter()
+                .filter_map(|line| line.get(i))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for line in lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
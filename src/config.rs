@@ -0,0 +1,191 @@
+use crate::logging::{LogFormat, LOG_FILTER};
+use crate::{
+    ChordError, DATA_DIR, LIVENESS_TIMEOUT, PORT, REPLICATION_FACTOR, REQ_TIMEOUT, RING_BITS,
+};
+use crate::{
+    FIX_FINGERS_INTERVAL, REPLICA_SYNC_INTERVAL, STABILIZE_INTERVAL, SUCCESSOR_LIST_INTERVAL,
+};
+use serde_derive::{Deserialize, Serialize};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use tracing_subscriber::EnvFilter;
+
+/// The largest `ring_bits`: IDs are `u64`s, and the ring needs room for `ID + 2^(ring_bits-1)` without overflowing.
+pub const MAX_RING_BITS: u32 = 63;
+
+// public resolvers that `detect_ip` pretends to send a packet to, so that the OS picks the address of the interface with the default route. Nothing is actually sent.
+const PROBE_V4: Ipv4Addr = Ipv4Addr::new(8, 8, 8, 8);
+const PROBE_V6: Ipv6Addr = Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888);
+
+/// The configuration a node runs with, shown as `config` in `/info`.
+/// It's built in layers, each overriding the one before (see `Options::config`): these defaults, then a TOML file, then `CRUST_*` environment variables, then command line flags. The result is checked by `validate` before the node starts.
+/// bind, advertise - the addresses the node listens on and is reached at (see `advertised_ip`).
+/// port - the port of the client API. The peer ports can't be configured: peers only know each other's IP addresses, so they are the same on every node (see `PEER_PORT`).
+/// data_dir - where the keys and the incarnation number are persisted (see `Storage`).
+/// ring_bits, replication_factor - the ring parameters, which every node of a ring has to agree on (see `Hello`).
+/// liveness_timeout - seconds a node has to answer a liveness probe in before it's considered dead.
+/// request_timeout - seconds any other request to a peer can take before it fails.
+/// intervals - how often each maintenance task runs.
+/// log - which events the node logs, and how (see `init_logging`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: IpAddr,
+    pub advertise: Option<IpAddr>,
+    pub port: u16,
+    pub data_dir: PathBuf,
+    pub ring_bits: u32,
+    pub replication_factor: u32,
+    pub liveness_timeout: u64,
+    pub request_timeout: u64,
+    pub intervals: Intervals,
+    pub log: Logging,
+}
+
+/// How often, in seconds, each maintenance task runs (see `start_maintenance`). It's the `[intervals]` table of the configuration file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Intervals {
+    pub stabilize: u64,
+    pub fix_fingers: u64,
+    pub successor_list: u64,
+    pub replica_sync: u64,
+}
+
+/// What a node logs, to stdout. It's the `[log]` table of the configuration file.
+/// filter - the events that are logged, as a `tracing_subscriber::EnvFilter` directive like `info` or `crust=debug,gotham=warn`.
+/// format - `text` for people, or `json` (one object per line) for log pipelines.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Logging {
+    pub filter: String,
+    pub format: LogFormat,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            advertise: None,
+            port: PORT,
+            data_dir: PathBuf::from(DATA_DIR),
+            ring_bits: RING_BITS,
+            replication_factor: REPLICATION_FACTOR,
+            liveness_timeout: LIVENESS_TIMEOUT,
+            request_timeout: REQ_TIMEOUT,
+            intervals: Intervals::default(),
+            log: Logging::default(),
+        }
+    }
+}
+
+impl Default for Intervals {
+    fn default() -> Self {
+        Intervals {
+            stabilize: STABILIZE_INTERVAL,
+            fix_fingers: FIX_FINGERS_INTERVAL,
+            successor_list: SUCCESSOR_LIST_INTERVAL,
+            replica_sync: REPLICA_SYNC_INTERVAL,
+        }
+    }
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Logging {
+            filter: LOG_FILTER.to_string(),
+            format: LogFormat::Text,
+        }
+    }
+}
+
+impl Config {
+    /// Reads a configuration file. Keys that are missing keep their defaults, and unknown keys are an error, so that a typo doesn't go unnoticed.
+    pub fn from_file(path: &Path) -> Result<Self, ChordError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| ChordError::BadRequest(format!("Can't read {}: {}", path.display(), e)))?;
+        Self::from_toml(&contents)
+            .map_err(|e| ChordError::BadRequest(format!("In {}: {}", path.display(), e)))
+    }
+
+    /// Parses a configuration in TOML, like `from_file`.
+    pub fn from_toml(contents: &str) -> Result<Self, ChordError> {
+        toml::from_str(contents).map_err(ChordError::bad_request)
+    }
+
+    /// Checks that the values make sense together, and returns a `ChordError::BadRequest` listing every one that doesn't.
+    pub fn validate(&self) -> Result<(), ChordError> {
+        let mut problems = Vec::new();
+        if self.port == 0 {
+            problems.push("port must be at least 1".to_string());
+        }
+        if !(1..=MAX_RING_BITS).contains(&self.ring_bits) {
+            problems.push(format!("ring_bits must be between 1 and {}", MAX_RING_BITS));
+        } else if self.replication_factor == 0
+            || u64::from(self.replication_factor) >= 1 << self.ring_bits
+        {
+            problems.push(format!(
+                "replication_factor must be between 1 and {} with {}-bit IDs",
+                (1u64 << self.ring_bits) - 1,
+                self.ring_bits
+            ));
+        }
+        let seconds = [
+            ("liveness_timeout", self.liveness_timeout),
+            ("request_timeout", self.request_timeout),
+            ("intervals.stabilize", self.intervals.stabilize),
+            ("intervals.fix_fingers", self.intervals.fix_fingers),
+            ("intervals.successor_list", self.intervals.successor_list),
+            ("intervals.replica_sync", self.intervals.replica_sync),
+        ];
+        for (name, value) in seconds.iter() {
+            if *value == 0 {
+                problems.push(format!("{} must be at least 1 second", name));
+            }
+        }
+        if let Err(e) = EnvFilter::try_new(&self.log.filter) {
+            problems.push(format!(
+                "log.filter {:?} is invalid ({})",
+                self.log.filter, e
+            ));
+        }
+        if self.liveness_timeout > self.request_timeout {
+            problems.push("liveness_timeout can't be longer than request_timeout".to_string());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ChordError::BadRequest(format!(
+                "Invalid configuration: {}",
+                problems.join(", ")
+            )))
+        }
+    }
+
+    /// returns the address the other nodes reach this node at: `advertise` if it was given, `bind` if it is a specific address, or else the address of the interface with the default route, of the same IP version as `bind`.
+    pub fn advertised_ip(&self) -> Result<IpAddr, ChordError> {
+        match self.advertise {
+            Some(ip) => Ok(ip),
+            None if !self.bind.is_unspecified() => Ok(self.bind),
+            None => detect_ip(self.bind.is_ipv6()).map_err(|e| {
+                ChordError::Internal(format!(
+                    "Can't detect the address of this node ({}), pass it with --advertise",
+                    e
+                ))
+            }),
+        }
+    }
+}
+
+/// returns the local address the OS would send packets to the internet from.
+fn detect_ip(v6: bool) -> io::Result<IpAddr> {
+    let (local, probe) = if v6 {
+        (IpAddr::V6(Ipv6Addr::UNSPECIFIED), IpAddr::V6(PROBE_V6))
+    } else {
+        (IpAddr::V4(Ipv4Addr::UNSPECIFIED), IpAddr::V4(PROBE_V4))
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(local, 0))?;
+    socket.connect(SocketAddr::new(probe, 80))?;
+    Ok(socket.local_addr()?.ip())
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Seconds a client should wait before retrying an operation that failed because a node didn't answer (see `ChordError::suspect`). This is roughly one stabilize round, which is usually enough for maintenance to route around a dead node.
+pub const RETRY_AFTER: u64 = 2;
+
+/// Response header carrying the IP address of the node suspected to be dead, set on 503 responses so that the caller (a client or another node) knows which node failed.
+pub const SUSPECT_HEADER: &str = "x-crust-suspect";
+
+/// A suspicion about a node that failed to respond.
+/// first_seen - when the node was first reported.
+/// reports - how many times the node has been reported since it last responded.
+#[derive(Clone, Copy)]
+struct Suspicion {
+    first_seen: Instant,
+    reports: u32,
+}
+
+/// Keeps track of nodes that failed to respond to a request. Both client operations and maintenance report failures here; only maintenance (`stabilize()` and `handle_failure()`) acts on them.
+/// Like `ChordNode`, this is cloned a lot, so the suspicion table is wrapped in an `Arc`.
+#[derive(Clone, Default)]
+pub struct FailureDetector {
+    suspects: Arc<Mutex<HashMap<IpAddr, Suspicion>>>,
+}
+
+impl FailureDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report that `node` didn't respond to a request.
+    pub fn report(&self, node: IpAddr) {
+        let mut suspects = self.suspects.lock().unwrap();
+        let suspicion = suspects.entry(node).or_insert(Suspicion {
+            first_seen: Instant::now(),
+            reports: 0,
+        });
+        suspicion.reports += 1;
+    }
+
+    /// Forget any suspicion about `node`, usually because it responded again or because it was removed from the ring.
+    pub fn clear(&self, node: IpAddr) {
+        self.suspects.lock().unwrap().remove(&node);
+    }
+
+    /// Forget every suspect that `keep` returns false for.
+    pub fn retain(&self, keep: impl Fn(IpAddr) -> bool) {
+        self.suspects.lock().unwrap().retain(|node, _| keep(*node));
+    }
+
+    pub fn is_suspected(&self, node: IpAddr) -> bool {
+        self.suspects.lock().unwrap().contains_key(&node)
+    }
+
+    /// returns all suspected nodes, oldest suspicion first.
+    pub fn suspects(&self) -> Vec<IpAddr> {
+        let suspects = self.suspects.lock().unwrap();
+        let mut list: Vec<(IpAddr, Suspicion)> = suspects.iter().map(|(k, v)| (*k, *v)).collect();
+        list.sort_by_key(|(_, s)| s.first_seen);
+        list.into_iter().map(|(ip, _)| ip).collect()
+    }
+
+    /// returns each suspected node along with the number of times it was reported since it last responded.
+    pub fn snapshot(&self) -> Vec<(IpAddr, u32)> {
+        let suspects = self.suspects.lock().unwrap();
+        suspects.iter().map(|(ip, s)| (*ip, s.reports)).collect()
+    }
+}
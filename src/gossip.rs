@@ -0,0 +1,308 @@
+use crate::membership::{MemberStatus, Update};
+use crate::{ChordError, ChordNode, ClusterSecret, Security, Signature, Supervisor, GOSSIP_PORT};
+use rand::seq::SliceRandom;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+use tracing::{debug, info, info_span, Instrument};
+
+const SIGNED_METHOD: &str = "swim"; // the "method" and "path" gossip packets are signed with (see `ClusterSecret::sign`).
+const SIGNED_PATH: &str = "/gossip";
+const MAX_DATAGRAM: usize = 65_507; // the largest payload of a UDP datagram.
+const MAX_PIGGYBACK: usize = 8; // at most this many updates are piggybacked on one message, which keeps packets far below the MTU.
+
+/// How often and how patiently `Gossip` probes the other members.
+/// period - one member is probed per period.
+/// ping_timeout - how long to wait for the ack of a direct ping before asking other members to ping it (the rest of the period is left for their answers).
+/// suspicion_timeout - how long a suspected member has to refute the suspicion before it is declared dead.
+/// dead_timeout - how long a dead member is kept in the view before it's forgotten. By then the news of its death has been gossiped to everyone, so it isn't brought back by a late update.
+/// indirect_probes - how many other members are asked to ping a member that didn't answer directly.
+#[derive(Debug, Clone)]
+pub struct GossipConfig {
+    pub period: Duration,
+    pub ping_timeout: Duration,
+    pub suspicion_timeout: Duration,
+    pub dead_timeout: Duration,
+    pub indirect_probes: usize,
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        GossipConfig {
+            period: Duration::from_secs(1),
+            ping_timeout: Duration::from_millis(300),
+            suspicion_timeout: Duration::from_secs(5),
+            dead_timeout: Duration::from_secs(60),
+            indirect_probes: 3,
+        }
+    }
+}
+
+/// The messages of the protocol. A member answers a `Ping` with an `Ack` of the same `seq`, and a `PingReq` by pinging `target` and relaying its ack back.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    Ping { seq: u64 },
+    PingReq { seq: u64, target: IpAddr },
+    Ack { seq: u64 },
+}
+
+/// A message, with the address and incarnation of its sender and the updates piggybacked on it.
+#[derive(Serialize, Deserialize)]
+struct Packet {
+    from: IpAddr,
+    incarnation: u64,
+    message: Message,
+    updates: Vec<Update>,
+}
+
+/// What goes over the wire: a serialized `Packet`, signed with the cluster secret if there is one.
+#[derive(Serialize, Deserialize)]
+struct Datagram {
+    packet: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<Signature>,
+}
+
+/// Someone waiting for an ack: this node, probing a member, or another member that asked this node to ping one.
+enum Waiting {
+    Probe(oneshot::Sender<()>),
+    Relay {
+        requester: IpAddr,
+        seq: u64,
+        asked: Instant,
+    },
+}
+
+/// A SWIM-style gossip protocol over UDP, on `GOSSIP_PORT`, that keeps the `Membership` of a node up to date.
+/// Every period, the node pings one member (going round-robin through a shuffled list). If there's no ack within `ping_timeout`, it asks `indirect_probes` other members to ping it too, and if none of them relays an ack before the end of the period, the member becomes suspect. Suspects that don't refute in time are declared dead.
+/// Updates to the view are piggybacked on every message, and the nodes that Chord knows about (fingers, successor list and predecessor) are added to the view as they show up, so every member eventually learns about the whole ring.
+/// With a cluster secret, packets are signed like peer requests, and unsigned ones are dropped.
+#[derive(Clone)]
+pub struct Gossip {
+    socket: Arc<UdpSocket>,
+    node: ChordNode,
+    secret: Option<ClusterSecret>,
+    config: GossipConfig,
+    seq: Arc<AtomicU64>,
+    waiting: Arc<Mutex<HashMap<u64, Waiting>>>,
+    order: Arc<Mutex<Vec<IpAddr>>>,
+}
+
+impl Gossip {
+    /// Binds the gossip socket of `node` on `bind`.
+    pub async fn bind(
+        node: &ChordNode,
+        bind: IpAddr,
+        security: &Security,
+        config: GossipConfig,
+    ) -> io::Result<Self> {
+        let socket = UdpSocket::bind(SocketAddr::new(bind, GOSSIP_PORT)).await?;
+        info!("Gossiping with peers at udp://{}", socket.local_addr()?);
+        Ok(Gossip {
+            socket: Arc::new(socket),
+            node: node.clone(),
+            secret: security.secret.clone(),
+            config,
+            seq: Arc::new(AtomicU64::new(0)),
+            waiting: Arc::new(Mutex::new(HashMap::new())),
+            order: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Answers the packets of the other members, forever.
+    pub async fn serve(&self) -> io::Result<()> {
+        let mut buf = vec![0; MAX_DATAGRAM];
+        loop {
+            let (len, addr) = self.socket.recv_from(&mut buf).await?;
+            if let Err(e) = self.handle(&buf[..len]).await {
+                debug!(peer = %addr.ip(), "Dropped a gossip packet: {}", e);
+            }
+        }
+    }
+
+    /// Runs one protocol period: probes the next member, directly and then through other members, and suspects it if nobody got an ack.
+    pub async fn probe_round(&self) -> Result<(), ChordError> {
+        let membership = self.node.membership();
+        for node in self.node.known_nodes() {
+            membership.learn(node);
+        }
+        membership.expire_suspects(self.config.suspicion_timeout);
+        membership.prune_dead(self.config.dead_timeout);
+        self.forget_stale_relays();
+        let target = match self.next_target() {
+            Some(target) => target,
+            None => return Ok(()),
+        };
+
+        let (seq, ack) = self.expect_ack();
+        self.send(target, Message::Ping { seq }).await?;
+        if tokio::time::timeout(self.config.ping_timeout, ack)
+            .await
+            .is_ok()
+        {
+            return Ok(());
+        }
+        self.waiting.lock().unwrap().remove(&seq);
+
+        let (seq, ack) = self.expect_ack();
+        let mut helpers: Vec<IpAddr> = membership
+            .live_members()
+            .into_iter()
+            .filter(|node| *node != target)
+            .collect();
+        helpers.shuffle(&mut rand::thread_rng());
+        helpers.truncate(self.config.indirect_probes);
+        for helper in helpers {
+            self.send(helper, Message::PingReq { seq, target }).await?;
+        }
+        let rest = self.config.period.saturating_sub(self.config.ping_timeout);
+        if tokio::time::timeout(rest, ack).await.is_err() {
+            self.waiting.lock().unwrap().remove(&seq);
+            if membership.suspect(target) {
+                info!(peer = %target, "Gossip: the peer didn't answer, suspecting it");
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles one datagram from another member.
+    async fn handle(&self, bytes: &[u8]) -> Result<(), ChordError> {
+        let datagram: Datagram = serde_json::from_slice(bytes)?;
+        if let Some(secret) = &self.secret {
+            let body = datagram.packet.as_bytes();
+            secret.verify(SIGNED_METHOD, SIGNED_PATH, body, datagram.signature)?;
+        }
+        let packet: Packet = serde_json::from_str(&datagram.packet)?;
+        if packet.from == self.node.self_ip() {
+            return Ok(());
+        }
+        let membership = self.node.membership();
+        membership.apply(Update {
+            node: packet.from,
+            status: MemberStatus::Alive,
+            incarnation: packet.incarnation,
+        });
+        for update in packet.updates {
+            membership.apply(update);
+        }
+
+        match packet.message {
+            Message::Ping { seq } => self.send(packet.from, Message::Ack { seq }).await,
+            Message::PingReq { seq, target } => {
+                let relay = Waiting::Relay {
+                    requester: packet.from,
+                    seq,
+                    asked: Instant::now(),
+                };
+                let own_seq = self.next_seq();
+                self.waiting.lock().unwrap().insert(own_seq, relay);
+                self.send(target, Message::Ping { seq: own_seq }).await
+            }
+            Message::Ack { seq } => {
+                let waiting = self.waiting.lock().unwrap().remove(&seq);
+                match waiting {
+                    Some(Waiting::Probe(acked)) => {
+                        let _ = acked.send(());
+                        Ok(())
+                    }
+                    Some(Waiting::Relay { requester, seq, .. }) => {
+                        self.send(requester, Message::Ack { seq }).await
+                    }
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Sends `message` to `to`, with as many updates as fit piggybacked on it. Whatever this node holds against `to` goes first, so that it can refute it.
+    async fn send(&self, to: IpAddr, message: Message) -> Result<(), ChordError> {
+        let membership = self.node.membership();
+        let mut updates: Vec<Update> = membership.rumor_about(to).into_iter().collect();
+        updates.extend(membership.piggyback(MAX_PIGGYBACK - updates.len()));
+        let packet = Packet {
+            from: self.node.self_ip(),
+            incarnation: membership.incarnation(),
+            message,
+            updates,
+        };
+        let packet = serde_json::to_string(&packet)?;
+        let signature = self
+            .secret
+            .as_ref()
+            .map(|secret| secret.sign(SIGNED_METHOD, SIGNED_PATH, packet.as_bytes()));
+        let datagram = serde_json::to_vec(&Datagram { packet, signature })?;
+        self.socket
+            .send_to(&datagram, SocketAddr::new(to, GOSSIP_PORT))
+            .await
+            .map_err(|e| ChordError::Unreachable {
+                node: to,
+                reason: e.to_string(),
+            })?;
+        Ok(())
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Registers a new probe. returns its sequence number and a receiver that completes when its ack arrives.
+    fn expect_ack(&self) -> (u64, oneshot::Receiver<()>) {
+        let seq = self.next_seq();
+        let (tx, rx) = oneshot::channel();
+        self.waiting.lock().unwrap().insert(seq, Waiting::Probe(tx));
+        (seq, rx)
+    }
+
+    /// Drops the pings this node relayed for other members that never got an ack.
+    fn forget_stale_relays(&self) {
+        let period = self.config.period;
+        self.waiting
+            .lock()
+            .unwrap()
+            .retain(|_, waiting| match waiting {
+                Waiting::Relay { asked, .. } => asked.elapsed() < period,
+                Waiting::Probe(_) => true,
+            });
+    }
+
+    /// returns the next member to probe, reshuffling the members once every one of them was probed.
+    fn next_target(&self) -> Option<IpAddr> {
+        let membership = self.node.membership();
+        let mut order = self.order.lock().unwrap();
+        loop {
+            if order.is_empty() {
+                *order = membership.live_members();
+                if order.is_empty() {
+                    return None;
+                }
+                order.shuffle(&mut rand::thread_rng());
+            }
+            let target = order.pop().unwrap();
+            if membership.status(target) != Some(MemberStatus::Dead) {
+                return Some(target);
+            }
+        }
+    }
+}
+
+/// Runs `Gossip::probe_round` every period on the `Supervisor`, as the "gossip" task. The packets of the other members are answered by `Gossip::serve`.
+pub fn start_gossip(gossip: &Gossip, supervisor: &Supervisor) {
+    let gossip = gossip.clone();
+    let period = gossip.config.period;
+    supervisor.spawn("gossip", period, move || {
+        let gossip = gossip.clone();
+        let span = info_span!(
+            "maintenance",
+            task = "gossip",
+            node_id = gossip.node.self_id()
+        );
+        async move { gossip.probe_round().await }.instrument(span)
+    });
+}
@@ -0,0 +1,686 @@
+// `tonic::Status` is large, but it is what every RPC has to return anyway.
+#![allow(clippy::result_large_err)]
+
+use crate::peer::{timed_out, unreachable, Transport, MAX_REQUESTS_PER_PEER, TCP_KEEPALIVE};
+use crate::{check_id, GRPC_PORT};
+use crate::{
+    ChordError, ChordNode, ClusterSecret, ErrorBody, Finger, Hello, Load, NodeView, Security,
+    Signature, TlsConfig,
+};
+use async_trait::async_trait;
+use prost::Message;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::Duration;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::transport::{Channel, Endpoint, Server};
+use tonic::{Code, Request, Response, Status};
+use tracing::{debug, field, info, info_span, warn, Span};
+
+/// Metadata key set on every error status a node returns, carrying the `ChordError` code.
+const ERROR_METADATA: &str = "x-crust-error";
+
+/// Types generated from `proto/chord.proto`.
+pub mod proto {
+    tonic::include_proto!("chord");
+}
+
+use proto::chord_peer_client::ChordPeerClient;
+use proto::chord_peer_server::{ChordPeer, ChordPeerServer};
+use proto::{ContainsReply, Empty, FingerUpdate, HelloReply, Id, InsertReply, Key, Keys, Node};
+use proto::{DeleteReply, LoadReply, RejoinRequest, ViewReply};
+
+/// Method every gRPC request is signed with: the path of the RPC (see `rpc_path`) is what tells RPCs apart.
+const SIGNED_METHOD: &str = "grpc";
+
+/// Serves the gRPC peer protocol on `GRPC_PORT` of `bind` until the server fails. With TLS, only peers with a certificate signed by the cluster CA can connect. With a cluster secret, every RPC but `Hello` has to be signed with it.
+/// Every RPC runs in a `request` span, like the requests of the HTTP listeners, with the path of the RPC once it is known (see `PeerService::verify`).
+pub async fn serve(
+    node: ChordNode,
+    bind: IpAddr,
+    security: &Security,
+) -> Result<(), tonic::transport::Error> {
+    let addr = SocketAddr::new(bind, GRPC_PORT);
+    let node_id = node.self_id();
+    let mut server = Server::builder().trace_fn(move |_| {
+        info_span!(
+            "request",
+            listener = "grpc",
+            node_id,
+            path = field::Empty,
+            key_id = field::Empty,
+        )
+    });
+    match &security.tls {
+        Some(tls) => {
+            server = server.tls_config(tls.grpc_server_config())?;
+            info!("Listening for peers at grpcs://{}", addr);
+        }
+        None => info!("Listening for peers at grpc://{}", addr),
+    }
+    server
+        .add_service(ChordPeerServer::new(PeerService {
+            node,
+            secret: security.secret.clone(),
+        }))
+        .serve(addr)
+        .await
+}
+
+/// The server side of the gRPC peer protocol. Every RPC is a thin wrapper around the `ChordNode` method the HTTP handler of the same operation calls.
+struct PeerService {
+    node: ChordNode,
+    secret: Option<ClusterSecret>,
+}
+
+impl PeerService {
+    /// Fails with `Unauthenticated` unless `req` is signed with the cluster secret, if there is one. Every RPC but `Hello` starts here, so this is also where the path of the RPC is added to its span.
+    fn verify<T: Message>(&self, rpc: &str, req: &Request<T>) -> Result<(), Status> {
+        Span::current().record("path", rpc_path(rpc).as_str());
+        let secret = match &self.secret {
+            Some(secret) => secret,
+            None => return Ok(()),
+        };
+        let metadata = req.metadata();
+        let signature = Signature::from_headers(|name| {
+            metadata.get(name).and_then(|value| value.to_str().ok())
+        });
+        secret
+            .verify(
+                SIGNED_METHOD,
+                &rpc_path(rpc),
+                &encode(req.get_ref()),
+                signature,
+            )
+            .map_err(to_status)
+    }
+}
+
+/// The HTTP/2 path of an RPC of the `ChordPeer` service.
+fn rpc_path(rpc: &str) -> String {
+    format!("/chord.ChordPeer/{}", rpc)
+}
+
+#[async_trait]
+impl ChordPeer for PeerService {
+    async fn hello(&self, _: Request<Empty>) -> Result<Response<HelloReply>, Status> {
+        let hello = self.node.hello();
+        Ok(Response::new(HelloReply {
+            protocol: hello.protocol,
+            ring_bits: hello.ring_bits,
+            hash: hello.hash,
+            replication_factor: hello.replication_factor,
+            version: hello.version,
+            transports: hello.transports,
+        }))
+    }
+
+    async fn get_successor(&self, req: Request<Empty>) -> Result<Response<Node>, Status> {
+        self.verify("GetSuccessor", &req)?;
+        Ok(node_response(self.node.get_successor()))
+    }
+
+    async fn update_successor(&self, req: Request<Node>) -> Result<Response<Empty>, Status> {
+        self.verify("UpdateSuccessor", &req)?;
+        let ip = parse_ip(&req.get_ref().ip)?;
+        debug!(peer = %ip, "Will update my successor");
+        self.node.update_successor(ip);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn closest_preceding_finger(&self, req: Request<Id>) -> Result<Response<Node>, Status> {
+        self.verify("ClosestPrecedingFinger", &req)?;
+        let id = parse_id(req.get_ref().id)?;
+        Ok(node_response(self.node.closest_preceding_finger(id)))
+    }
+
+    async fn get_predecessor(&self, req: Request<Empty>) -> Result<Response<Node>, Status> {
+        self.verify("GetPredecessor", &req)?;
+        Ok(node_response(self.node.get_predecessor()))
+    }
+
+    async fn update_predecessor(&self, req: Request<Node>) -> Result<Response<Empty>, Status> {
+        self.verify("UpdatePredecessor", &req)?;
+        let ip = parse_ip(&req.get_ref().ip)?;
+        debug!(peer = %ip, "Will update my predecessor");
+        self.node.update_predecessor(ip);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn update_finger_table(
+        &self,
+        req: Request<FingerUpdate>,
+    ) -> Result<Response<Empty>, Status> {
+        self.verify("UpdateFingerTable", &req)?;
+        let update = req.into_inner();
+        let s = parse_ip(&update.node)?;
+        let mut node = self.node.clone();
+        node.update_finger_table(s, update.i)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn notify(&self, req: Request<Node>) -> Result<Response<Empty>, Status> {
+        self.verify("Notify", &req)?;
+        let n = parse_ip(&req.get_ref().ip)?;
+        self.node.notify(n).await;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn insert_replica(&self, req: Request<Keys>) -> Result<Response<Empty>, Status> {
+        self.verify("InsertReplica", &req)?;
+        self.node.insert_replica(req.into_inner().keys);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn rejoin(&self, req: Request<RejoinRequest>) -> Result<Response<Keys>, Status> {
+        self.verify("Rejoin", &req)?;
+        let rejoin = req.into_inner();
+        let n = parse_ip(&rejoin.node)?;
+        let keys = self
+            .node
+            .handle_rejoin(n, rejoin.incarnation)
+            .map_err(to_status)?;
+        Ok(Response::new(Keys { keys }))
+    }
+
+    async fn insert(&self, req: Request<Key>) -> Result<Response<InsertReply>, Status> {
+        self.verify("Insert", &req)?;
+        let inserted_at = self
+            .node
+            .insert(req.into_inner().key)
+            .await
+            .map_err(to_status)?;
+        let node_id = inserted_at.parse().map_err(|_| {
+            to_status(ChordError::Internal(format!(
+                "Invalid node id {}",
+                inserted_at
+            )))
+        })?;
+        Ok(Response::new(InsertReply { node_id }))
+    }
+
+    async fn contains(&self, req: Request<Key>) -> Result<Response<ContainsReply>, Status> {
+        self.verify("Contains", &req)?;
+        let found = self
+            .node
+            .contains(&req.get_ref().key)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(ContainsReply { found }))
+    }
+
+    async fn delete(&self, req: Request<Key>) -> Result<Response<DeleteReply>, Status> {
+        self.verify("Delete", &req)?;
+        let found = self
+            .node
+            .delete(&req.get_ref().key)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(DeleteReply { found }))
+    }
+
+    async fn delete_replica(&self, req: Request<Keys>) -> Result<Response<Empty>, Status> {
+        self.verify("DeleteReplica", &req)?;
+        self.node.delete_replica(req.into_inner().keys);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn load(&self, req: Request<Empty>) -> Result<Response<LoadReply>, Status> {
+        self.verify("Load", &req)?;
+        let load = self.node.load();
+        Ok(Response::new(LoadReply {
+            keys: load.keys,
+            replicas: load.replicas,
+            requests_per_sec: load.requests_per_sec,
+            key_ops_per_sec: load.key_ops_per_sec,
+        }))
+    }
+
+    async fn view(&self, req: Request<Empty>) -> Result<Response<ViewReply>, Status> {
+        self.verify("View", &req)?;
+        let view = self.node.view();
+        Ok(Response::new(ViewReply {
+            successor: view.successor.to_string(),
+            predecessor: view.predecessor.to_string(),
+            successor_list: view.successor_list.iter().map(IpAddr::to_string).collect(),
+            fingers: view
+                .fingers
+                .into_iter()
+                .map(|finger| proto::Finger {
+                    start: finger.start,
+                    node: finger.node.to_string(),
+                })
+                .collect(),
+            keys: view.keys,
+            replicas: view.replicas,
+        }))
+    }
+}
+
+fn node_response(ip: IpAddr) -> Response<Node> {
+    Response::new(Node { ip: ip.to_string() })
+}
+
+fn parse_ip(ip: &str) -> Result<IpAddr, Status> {
+    ip.parse()
+        .map_err(|_| to_status(ChordError::BadRequest(format!("Invalid IP address {}", ip))))
+}
+
+fn parse_id(id: u64) -> Result<u64, Status> {
+    check_id(id).map_err(to_status)
+}
+
+/// Turns the error of a `ChordNode` method into a gRPC status. The message is the same JSON `ErrorBody` the HTTP API returns, and `ERROR_METADATA` carries its code, which tells the caller that the status came from a node rather than from the connection.
+fn to_status(error: ChordError) -> Status {
+    warn!(code = error.code(), "Request failed: {}", error);
+    let code = match error {
+        ChordError::Timeout { .. } | ChordError::Unreachable { .. } => Code::Unavailable,
+        ChordError::NotOwner(_) => Code::FailedPrecondition,
+        ChordError::BadRequest(_) => Code::InvalidArgument,
+        ChordError::Conflict(_) => Code::AlreadyExists,
+        ChordError::Unauthorized(_) => Code::Unauthenticated,
+        ChordError::Forbidden(_) => Code::PermissionDenied,
+        ChordError::RateLimited(_) => Code::ResourceExhausted,
+        ChordError::Incompatible(_) => Code::FailedPrecondition,
+        ChordError::Internal(_) => Code::Internal,
+    };
+    let mut status = Status::new(code, error.to_json());
+    status
+        .metadata_mut()
+        .insert(ERROR_METADATA, MetadataValue::from_static(error.code()));
+    status
+}
+
+/// The client side of `to_status`: maps a status returned by `ip` back to the `ChordError` the node sent. Statuses without `ERROR_METADATA` were produced by the connection, so `ip` is reported as timed out or unreachable.
+fn from_status(node: &ChordNode, ip: IpAddr, status: Status) -> ChordError {
+    if status.metadata().get(ERROR_METADATA).is_some() {
+        node.failures.clear(ip);
+        return match serde_json::from_str::<ErrorBody>(status.message()) {
+            Ok(body) => ChordError::from_body(body),
+            Err(_) => ChordError::Internal(status.message().to_string()),
+        };
+    }
+    match status.code() {
+        Code::DeadlineExceeded | Code::Cancelled => timed_out(ip, node),
+        Code::Unimplemented => {
+            ChordError::Internal(format!("{} doesn't support this RPC: {}", ip, status))
+        }
+        _ => unreachable(ip, status.to_string(), node),
+    }
+}
+
+/// The bytes of `message` on the wire, which is what a signature covers.
+fn encode<T: Message>(message: &T) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(message.encoded_len());
+    message.encode(&mut bytes).expect("A Vec grows as needed");
+    bytes
+}
+
+fn insert_signature(metadata: &mut MetadataMap, signature: &Signature) {
+    for (name, value) in signature.headers().iter() {
+        let value = value.parse().expect("Signatures are ASCII");
+        metadata.insert(*name, value);
+    }
+}
+
+/// Opens a channel to `ip`, waiting at most `timeout` for the connection. RPCs on the channel time out after `request_timeout`.
+async fn connect_with_timeout(
+    ip: IpAddr,
+    timeout: Duration,
+    request_timeout: Duration,
+    tls: Option<&TlsConfig>,
+) -> Result<Channel, String> {
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let mut endpoint =
+        Endpoint::from_shared(format!("{}://{}", scheme, SocketAddr::new(ip, GRPC_PORT)))
+            .map_err(|e| e.to_string())?
+            .timeout(request_timeout)
+            .tcp_keepalive(Some(Duration::from_secs(TCP_KEEPALIVE)))
+            .tcp_nodelay(true)
+            .concurrency_limit(MAX_REQUESTS_PER_PEER);
+    if let Some(tls) = tls {
+        endpoint = endpoint
+            .tls_config(tls.grpc_client_config())
+            .map_err(|e| e.to_string())?;
+    }
+    match tokio::time::timeout(timeout, endpoint.connect()).await {
+        Ok(Ok(channel)) => Ok(channel),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("timed out connecting".to_string()),
+    }
+}
+
+/// The typed peer protocol in `proto/chord.proto`, served on `GRPC_PORT` (see `serve`).
+/// One channel is kept open to each peer and shared by every RPC sent to it (HTTP/2 multiplexes them over a single connection), with at most `MAX_REQUESTS_PER_PEER` of them in flight. A channel is dropped as soon as its peer stops answering, and reopened on the next request.
+#[derive(Default)]
+pub struct GrpcTransport {
+    channels: Mutex<HashMap<IpAddr, Channel>>,
+    tls: Option<TlsConfig>,
+    secret: Option<ClusterSecret>,
+}
+
+impl GrpcTransport {
+    pub const NAME: &'static str = "grpc";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// With TLS, channels are opened over TLS and present this node's certificate (see `TlsConfig::grpc_client_config`). With a cluster secret, every RPC is signed with it.
+    pub fn with_security(security: &Security) -> Self {
+        GrpcTransport {
+            tls: security.tls.clone(),
+            secret: security.secret.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Wraps `message` in a request for `rpc`, signed with the cluster secret if there is one.
+    fn request<T: Message>(&self, rpc: &str, message: T) -> Request<T> {
+        let signature = self
+            .secret
+            .as_ref()
+            .map(|secret| secret.sign(SIGNED_METHOD, &rpc_path(rpc), &encode(&message)));
+        let mut request = Request::new(message);
+        if let Some(signature) = signature {
+            insert_signature(request.metadata_mut(), &signature);
+        }
+        request
+    }
+
+    /// returns the channel to `ip`, opening it if needed. Failing to connect reports `ip` to the failure detector.
+    async fn connect(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+    ) -> Result<ChordPeerClient<Channel>, ChordError> {
+        let timeout = Duration::from_secs(node.config().request_timeout);
+        self.channel(node, ip, timeout)
+            .await
+            .map(ChordPeerClient::new)
+            .map_err(|reason| unreachable(ip, reason, node))
+    }
+
+    /// returns the channel from `node` to `ip`, opening it within `timeout` if needed.
+    async fn channel(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        timeout: Duration,
+    ) -> Result<Channel, String> {
+        if let Some(channel) = self.channels.lock().unwrap().get(&ip) {
+            return Ok(channel.clone());
+        }
+        let request_timeout = Duration::from_secs(node.config().request_timeout);
+        let channel = connect_with_timeout(ip, timeout, request_timeout, self.tls.as_ref()).await?;
+        self.channels.lock().unwrap().insert(ip, channel.clone());
+        Ok(channel)
+    }
+
+    /// Unwraps the response of an RPC sent to `ip`, clearing any suspicion about `ip` if it answered. If `ip` itself didn't answer, its channel is closed.
+    fn reply<T>(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        resp: Result<Response<T>, Status>,
+    ) -> Result<T, ChordError> {
+        match resp {
+            Ok(resp) => {
+                node.failures.clear(ip);
+                Ok(resp.into_inner())
+            }
+            Err(status) => {
+                let error = from_status(node, ip, status);
+                if error.suspect() == Some(ip) {
+                    self.channels.lock().unwrap().remove(&ip);
+                }
+                Err(error)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for GrpcTransport {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    async fn get_successor(&self, node: &ChordNode, ip: IpAddr) -> Result<IpAddr, ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .get_successor(self.request("GetSuccessor", Empty {}))
+            .await;
+        Ok(self.reply(node, ip, resp)?.ip.parse()?)
+    }
+
+    async fn get_predecessor(&self, node: &ChordNode, ip: IpAddr) -> Result<IpAddr, ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .get_predecessor(self.request("GetPredecessor", Empty {}))
+            .await;
+        Ok(self.reply(node, ip, resp)?.ip.parse()?)
+    }
+
+    async fn closest_preceding_finger(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        id: u64,
+    ) -> Result<IpAddr, ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .closest_preceding_finger(self.request("ClosestPrecedingFinger", Id { id }))
+            .await;
+        Ok(self.reply(node, ip, resp)?.ip.parse()?)
+    }
+
+    async fn update_finger_table(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        s: IpAddr,
+        i: u64,
+    ) -> Result<(), ChordError> {
+        let update = FingerUpdate {
+            node: s.to_string(),
+            i,
+        };
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .update_finger_table(self.request("UpdateFingerTable", update))
+            .await;
+        self.reply(node, ip, resp)?;
+        Ok(())
+    }
+
+    async fn notify(&self, node: &ChordNode, ip: IpAddr, n: IpAddr) -> Result<(), ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .notify(self.request("Notify", Node { ip: n.to_string() }))
+            .await;
+        self.reply(node, ip, resp)?;
+        Ok(())
+    }
+
+    async fn insert_replica(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        keys: Vec<String>,
+    ) -> Result<(), ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .insert_replica(self.request("InsertReplica", Keys { keys }))
+            .await;
+        self.reply(node, ip, resp)?;
+        Ok(())
+    }
+
+    async fn rejoin(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        n: IpAddr,
+        incarnation: u64,
+    ) -> Result<Vec<String>, ChordError> {
+        let request = RejoinRequest {
+            node: n.to_string(),
+            incarnation,
+        };
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .rejoin(self.request("Rejoin", request))
+            .await;
+        Ok(self.reply(node, ip, resp)?.keys)
+    }
+
+    async fn insert(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        key: String,
+    ) -> Result<String, ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .insert(self.request("Insert", Key { key }))
+            .await;
+        Ok(self.reply(node, ip, resp)?.node_id.to_string())
+    }
+
+    /// Peers that predate the handshake answer `Unimplemented`.
+    async fn hello(&self, node: &ChordNode, ip: IpAddr) -> Result<Option<Hello>, ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .hello(self.request("Hello", Empty {}))
+            .await;
+        if matches!(&resp, Err(status) if status.code() == Code::Unimplemented) {
+            node.failures.clear(ip);
+            return Ok(None);
+        }
+        let hello = self.reply(node, ip, resp)?;
+        Ok(Some(Hello {
+            protocol: hello.protocol,
+            ring_bits: hello.ring_bits,
+            hash: hello.hash,
+            replication_factor: hello.replication_factor,
+            version: hello.version,
+            transports: hello.transports,
+        }))
+    }
+
+    async fn contains(&self, node: &ChordNode, ip: IpAddr, key: &str) -> Result<bool, ChordError> {
+        let key = Key {
+            key: key.to_string(),
+        };
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .contains(self.request("Contains", key))
+            .await;
+        Ok(self.reply(node, ip, resp)?.found)
+    }
+
+    async fn delete(&self, node: &ChordNode, ip: IpAddr, key: &str) -> Result<bool, ChordError> {
+        let key = Key {
+            key: key.to_string(),
+        };
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .delete(self.request("Delete", key))
+            .await;
+        Ok(self.reply(node, ip, resp)?.found)
+    }
+
+    async fn delete_replica(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        keys: Vec<String>,
+    ) -> Result<(), ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .delete_replica(self.request("DeleteReplica", Keys { keys }))
+            .await;
+        self.reply(node, ip, resp)?;
+        Ok(())
+    }
+
+    async fn load(&self, node: &ChordNode, ip: IpAddr) -> Result<Load, ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .load(self.request("Load", Empty {}))
+            .await;
+        let load = self.reply(node, ip, resp)?;
+        Ok(Load {
+            keys: load.keys,
+            replicas: load.replicas,
+            requests_per_sec: load.requests_per_sec,
+            key_ops_per_sec: load.key_ops_per_sec,
+        })
+    }
+
+    async fn view(&self, node: &ChordNode, ip: IpAddr) -> Result<NodeView, ChordError> {
+        let resp = self
+            .connect(node, ip)
+            .await?
+            .view(self.request("View", Empty {}))
+            .await;
+        let view = self.reply(node, ip, resp)?;
+        let mut fingers = Vec::new();
+        for finger in view.fingers {
+            fingers.push(Finger {
+                start: finger.start,
+                node: finger.node.parse()?,
+            });
+        }
+        let mut successor_list = Vec::new();
+        for ip in view.successor_list {
+            successor_list.push(ip.parse()?);
+        }
+        Ok(NodeView {
+            successor: view.successor.parse()?,
+            predecessor: view.predecessor.parse()?,
+            successor_list,
+            fingers,
+            keys: view.keys,
+            replicas: view.replicas,
+        })
+    }
+
+    /// returns true if `ip` answers a `GetSuccessor` within the `liveness_timeout` of `node`.
+    async fn is_alive(&self, node: &ChordNode, ip: IpAddr) -> bool {
+        let timeout = Duration::from_secs(node.config().liveness_timeout);
+        let mut client = match self.channel(node, ip, timeout).await {
+            Ok(channel) => ChordPeerClient::new(channel),
+            Err(_) => return false,
+        };
+        let alive = matches!(
+            tokio::time::timeout(
+                timeout,
+                client.get_successor(self.request("GetSuccessor", Empty {}))
+            )
+            .await,
+            Ok(Ok(_))
+        );
+        if !alive {
+            self.channels.lock().unwrap().remove(&ip);
+        }
+        alive
+    }
+}
@@ -0,0 +1,62 @@
+use serde_derive::Serialize;
+
+/// Whether a node can serve the keys of its part of the ring, as `GET /readyz` reports it (see `ChordNode::readiness`). Orchestrators and load balancers should only send requests to a node that is `ready`.
+/// joined - the node is in the ring. Until then, nothing else is checked.
+/// successor_reachable - the successor answers (or the node is alone in the ring).
+/// predecessor_known - some node notified this one that it's its predecessor (or the node is alone in the ring), so the node knows which keys it owns.
+/// keys_transferred - none of the keys this node owns are still only replicas here, waiting to be taken over.
+/// replicas_in_sync - the last round of replica sync reached every replica, or this node owns no keys to replicate yet.
+/// reasons - why each failed check failed, for people.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub joined: bool,
+    pub successor_reachable: bool,
+    pub predecessor_known: bool,
+    pub keys_transferred: bool,
+    pub replicas_in_sync: bool,
+    pub reasons: Vec<String>,
+}
+
+impl Readiness {
+    /// The readiness of a node that is still joining the ring.
+    pub fn joining() -> Self {
+        Readiness {
+            ready: false,
+            joined: false,
+            successor_reachable: false,
+            predecessor_known: false,
+            keys_transferred: false,
+            replicas_in_sync: false,
+            reasons: vec!["Joining the ring".to_string()],
+        }
+    }
+
+    /// The readiness of a node in the ring, from the outcome of each check: `Err` holds the reason it failed.
+    pub(crate) fn joined(
+        successor_reachable: Result<(), String>,
+        predecessor_known: Result<(), String>,
+        keys_transferred: Result<(), String>,
+        replicas_in_sync: Result<(), String>,
+    ) -> Self {
+        let checks = [
+            &successor_reachable,
+            &predecessor_known,
+            &keys_transferred,
+            &replicas_in_sync,
+        ];
+        let reasons: Vec<String> = checks
+            .iter()
+            .filter_map(|check| check.as_ref().err().cloned())
+            .collect();
+        Readiness {
+            ready: reasons.is_empty(),
+            joined: true,
+            successor_reachable: successor_reachable.is_ok(),
+            predecessor_known: predecessor_known.is_ok(),
+            keys_transferred: keys_transferred.is_ok(),
+            replicas_in_sync: replicas_in_sync.is_ok(),
+            reasons,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::peer::supported_transport_names;
+use crate::{ring_bits, Hello, HASH_ALGORITHM, PROTOCOL_VERSION};
+
+/// The number of bits of an ID on the ring, unless configured otherwise (see `Config::ring_bits`).
+pub const RING_BITS: u32 = 6;
+
+/// The number of successors every key is replicated to (the length of the successor list), unless configured otherwise (see `Config::replication_factor`).
+pub const REPLICATION_FACTOR: u32 = RING_BITS;
+
+/// returns the `Hello` of this node. `Hello` itself is defined in `crust_client`, which clients share with the nodes.
+pub fn local_hello() -> Hello {
+    Hello {
+        protocol: PROTOCOL_VERSION.to_string(),
+        ring_bits: ring_bits(),
+        hash: HASH_ALGORITHM.to_string(),
+        replication_factor: REPLICATION_FACTOR,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        transports: supported_transport_names()
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+    }
+}
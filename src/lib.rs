@@ -1,21 +1,69 @@
-use gotham::handler::HandlerError;
+pub use crust_client::{identifier, ChordError, ErrorBody, Hello, VisInfo};
+pub use crust_client::{HASH_ALGORITHM, PROTOCOL_VERSION};
 use gotham_derive::StateData;
 use rand::Rng;
-use reqwest::Response;
 use serde::ser::{Serialize, SerializeStruct, Serializer};
-use serde_derive::Serialize;
-use simple_error::SimpleError;
-use std::collections::hash_map::DefaultHasher;
-use std::collections::HashSet;
-use std::hash::{Hash, Hasher};
-use std::net::{IpAddr, UdpSocket};
-use std::sync::{Arc, Mutex};
-use std::{env, fmt};
-use std::{thread, time::Duration};
-
-const M: u64 = 64; // number of "holes" in the Chord ring.
-const PORT: usize = 8000; // all nodes run on this PORT. This necessarily means that this application is intended to be used in a Docker environment.
-
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+
+mod admin;
+pub use admin::{Change, Maintenance, StateDiff};
+mod auth;
+pub use auth::{Audience, ClusterSecret, Security, Signature, ADMIN_TOKEN_ENV};
+mod config;
+pub use config::{Config, Intervals, Logging, MAX_RING_BITS};
+mod failure;
+pub use failure::{FailureDetector, RETRY_AFTER, SUSPECT_HEADER};
+mod gossip;
+pub use gossip::{start_gossip, Gossip, GossipConfig};
+mod logging;
+pub use logging::{init_logging, LogFormat, LOG_FILTER};
+mod membership;
+pub use membership::{MemberReport, MemberStatus, Membership, Update};
+mod metrics;
+pub use metrics::{route_label, Metrics};
+mod options;
+pub use options::{parse_ip, parse_seed, Cli, ClientOptions, Command, InfoCommand, KeyCommand};
+pub use options::{Options, CONFIG_ENV};
+mod stats;
+pub use stats::{Load, NodeStats, RingStats, Spread, Summary};
+mod storage;
+pub use storage::{PersistedState, Storage};
+mod tasks;
+pub use tasks::Supervisor;
+mod test_ca;
+pub use test_ca::TestCa;
+mod tls;
+pub use tls::{serve_https, TlsClient, TlsConfig};
+mod verify;
+pub use verify::{Finger, NodeView, RingCheck, Violation};
+mod health;
+pub use health::Readiness;
+mod hello;
+pub use hello::{local_hello, REPLICATION_FACTOR, RING_BITS};
+mod grpc;
+pub use grpc::{serve as serve_grpc, GrpcTransport};
+mod memory;
+pub use memory::MemoryNetwork;
+mod peer;
+pub use peer::{http_client, supported_transport_names, HttpTransport, Transport};
+
+pub const PORT: u16 = 8000; // all nodes serve the client API (and the browser UI) on this PORT by default (see `Config::port`).
+const DATA_DIR: &str = "data"; // by default, keys and the incarnation number are persisted here, so that a node restarting at the same IP can recover them (see `Config::data_dir`).
+
+// the peer ports below are not configurable: a node is known to its peers by its IP address alone (its ID, its fingers, successor list and predecessor are all IPs), so every node has to serve them on the same ports. Making them configurable would mean identifying nodes by address and port throughout the protocol.
+const GRPC_PORT: u16 = 8001; // the gRPC peer protocol is served on this port, next to the HTTP API.
+pub const PEER_PORT: u16 = 8002; // the HTTP peer API is served on this port, apart from the client API, so that peer traffic can be firewalled away from clients.
+const GOSSIP_PORT: u16 = 8003; // the gossip protocol (see `Gossip`) runs over UDP on this port.
+
+// paths of the HTTP peer API, under HTTP_PEER on PEER_PORT.
+const HTTP_PEER: &str = "peer/";
 const HTTP_SUCCESSOR: &str = "successor/";
 const HTTP_SUCCESSOR_CPF: &str = "successor/cpf/";
 const HTTP_PREDECESSOR: &str = "predecessor/";
@@ -23,11 +71,24 @@ const HTTP_FINGER_TABLE: &str = "fingertable/";
 const HTTP_NOTIFY: &str = "notify/";
 const HTTP_KEY: &str = "key/";
 const HTTP_REPLICA: &str = "replica/";
+const HTTP_REJOIN: &str = "rejoin/";
+const HTTP_LOAD: &str = "load/";
+const HTTP_VIEW: &str = "view/";
+// paths of the client API that joining nodes use. HTTP_HELLO is served to peers under HTTP_PEER too.
+const HTTP_TRANSPORTS: &str = "transports/";
+const HTTP_HELLO: &str = "hello/";
 
 // following constants represent time in seconds.
-const STABILIZE_INTERVAL: u64 = 2; // stabilize() is called this often
-const LIVENESS_TIMEOUT: u64 = 1; // a node must reply back in this time to be considered "alive". Nodes that can't reply back this fast enough are considered dead, triggering failure recovery.
-const REQ_TIMEOUT: u64 = 3; // HTTP requests that take longer this are marked as errors.
+const STABILIZE_INTERVAL: u64 = 2; // stabilize() is called this often, unless configured otherwise (see `Config::intervals`)
+const FIX_FINGERS_INTERVAL: u64 = 2; // fix_fingers() is called this often, unless configured otherwise
+const SUCCESSOR_LIST_INTERVAL: u64 = 2; // build_successor_list() is called this often, unless configured otherwise
+const REPLICA_SYNC_INTERVAL: u64 = 10; // sync_replicas() is called this often, unless configured otherwise
+const LIVENESS_TIMEOUT: u64 = 1; // a node must reply back in this time to be considered "alive", unless configured otherwise (see `Config::liveness_timeout`). Nodes that can't reply back this fast enough are considered dead, triggering failure recovery.
+const REQ_TIMEOUT: u64 = 3; // requests to peers that take longer this are marked as errors, unless configured otherwise (see `Config::request_timeout`).
+
+static RING_BITS_IN_USE: AtomicU32 = AtomicU32::new(RING_BITS); // the ring has 2^RING_BITS_IN_USE "holes". It's set from `Config::ring_bits` whenever a node is built (see `set_ring_bits`).
+const JOIN_DEADLINE: u64 = 60; // a new node keeps retrying its seeds for this long before giving up on joining the ring.
+const JOIN_BACKOFF_MAX: u64 = 16; // upper bound for the exponential backoff between two rounds of join attempts.
 
 pub enum Bracket {
     Open,
@@ -35,7 +96,7 @@ pub enum Bracket {
 }
 
 /// Represents a circular mathematical interval. For example, 5 exists in the interval [5,7] and [5,7) but it doesn't in (5,7] or (5,7).
-/// Also, if `M` is 64, then:
+/// Also, if the ring has 64 "holes" (6-bit IDs, the default), then:
 ///     1. 63 exists in the interval [45, 2]
 ///     2. 63 exists in the interval [62, 0]
 ///     3. 63 exists in the interval (1, 0).
@@ -84,25 +145,20 @@ impl Interval {
         }
     }
 
-    /// Needs improvement - right now this method uses a pretty inefficient way to check if a `val` lies between an `Interval. Selecting a `start` and `end` and walking through each value between them isn't ideal, and we need  a faster way to do this.
+    /// returns true if `val` lies in the interval, going clockwise around the ring from `val1` to `val2`. The brackets are turned into the first and last IDs of the interval, `start` and `end`, and `val` is in it if it is no further from `start` than `end` is, which takes constant time whatever the size of the ring. Like walking from `start` to `end` one ID at a time would, an interval whose `end` comes right before its `start` covers the whole ring, e.g. `(n, n]`.
     pub fn contains(&self, val: u64) -> bool {
-        let mut start = match self.bracket1 {
-            Bracket::Open => (self.val1 + 1) % M,
+        let size = ring_size();
+        let start = match self.bracket1 {
+            Bracket::Open => (self.val1 + 1) % size,
             Bracket::Closed => self.val1,
         };
 
         let end = match self.bracket2 {
-            Bracket::Open => (self.val2 + M - 1) % M,
+            Bracket::Open => (self.val2 + size - 1) % size,
             Bracket::Closed => self.val2,
         };
 
-        while start != end {
-            if start == val {
-                return true;
-            }
-            start = (start + 1) % M;
-        }
-        val == start
+        val < size && (val + size - start) % size <= (end + size - start) % size
     }
 }
 /// Represents an entry in the Chord Node's finger table.
@@ -143,28 +199,37 @@ impl FingerTableEntry {
     }
 }
 
-/// Used for constructing a JSON of successor pointers. This is used by the Javascript in `index.html` to render the Chord ring.
-#[derive(Serialize)]
-struct VisInfo {
-    from: u64,
-    to: u64,
+/// The mutable part of a `ChordNode`: finger table, successor list, predecessor pointer, hash set and replica set.
+/// All of it sits behind a single `RwLock`. Readers (like `/info`) always see every field as it was at one moment, and updates that touch several fields (like a rejoin, which moves the predecessor and hands keys back) are atomic. Guards must never be held across an `.await`.
+/// `peer_incarnations` is the latest incarnation this node has seen from each node that rejoined through it.
+/// `replicas_synced` is whether the last round of `sync_replicas` reached every replica, `None` before the first one.
+struct NodeState {
+    finger_table: Vec<FingerTableEntry>,
+    hash_set: HashSet<String>,
+    predecessor: IpAddr,
+    successor_list: Vec<IpAddr>,
+    replica_set: HashSet<String>,
+    peer_incarnations: HashMap<IpAddr, u64>,
+    replicas_synced: Option<bool>,
 }
 
-impl VisInfo {
-    fn new(from: u64, to: u64) -> Self {
-        Self { from, to }
-    }
-}
-/// In-memory data structure representing the finger tables, successor list, predecessor pointers, and hash set and replica set.
-/// Since this struct will be cloned multiple times (each time a function receives this from a `State`, it's receiving a cloned version), all writable fields in this struct should be wrapped in `Arc`. This allows fast clones and allows all function to share the same data safely (using a Mutex).
+/// A Chord node: its (immutable) address and incarnation, plus its `NodeState`.
+/// Since this struct will be cloned multiple times (each time a function receives this from a `State`, it's receiving a cloned version), the state is wrapped in an `Arc`. This allows fast clones and allows all functions to share the same data safely.
+/// `incarnation` starts at 1 and goes up by one every time the node restarts with its previous state (see `Storage`).
+/// `transport` is how this node sends requests to other nodes. Outside of tests, it's negotiated with the seed at join (see `peer::negotiate`).
+/// `config` is what the node was configured with (see `Config`). Its `replication_factor` is the length of the successor list, which every key is replicated to, and every node of a ring has to use the same (see `Hello`).
+/// `membership` is this node's gossiped view of the ring. It stays empty unless a `Gossip` runs for this node, and then takes precedence over direct probes (see `is_alive`).
 #[derive(Clone, StateData)]
 pub struct ChordNode {
-    finger_table: Arc<Mutex<Vec<FingerTableEntry>>>,
-    hash_set: Arc<Mutex<HashSet<String>>>,
+    state: Arc<RwLock<NodeState>>,
     self_ip: IpAddr,
-    predecessor: Arc<Mutex<IpAddr>>,
-    successor_list: Arc<Mutex<Vec<IpAddr>>>,
-    replica_set: Arc<Mutex<HashSet<String>>>,
+    failures: FailureDetector,
+    membership: Membership,
+    metrics: Metrics,
+    incarnation: u64,
+    config: Arc<Config>,
+    storage: Storage,
+    transport: Arc<dyn Transport>,
 }
 
 impl Serialize for ChordNode {
@@ -172,25 +237,30 @@ impl Serialize for ChordNode {
     where
         S: Serializer,
     {
-        let finger_table = self.finger_table.lock().unwrap();
+        let node_state = self.read();
         let self_id = get_identifier(&self.self_ip.to_string());
-        let predecessor = self.predecessor.lock().unwrap();
-        let predecessor_id = get_identifier(&(*predecessor).to_string());
-        let successor_list = self.successor_list.lock().unwrap();
-        let successor_list: Vec<(&IpAddr, u64)> = successor_list
+        let predecessor_id = get_identifier(&node_state.predecessor.to_string());
+        let successor_list: Vec<(&IpAddr, u64)> = node_state
+            .successor_list
             .iter()
             .map(|ip| (ip, get_identifier(&ip.to_string())))
             .collect();
-        let hash_set = self.hash_set.lock().unwrap();
+        let suspects = self.failures.snapshot();
+        let members = self.membership.snapshot();
 
-        let mut state = serializer.serialize_struct("ChordNode", 5)?;
-        state.serialize_field("finger_table", &*finger_table)?;
-        state.serialize_field("hash_set", &*hash_set)?;
+        let mut state = serializer.serialize_struct("ChordNode", 12)?;
+        state.serialize_field("finger_table", &node_state.finger_table)?;
+        state.serialize_field("hash_set", &node_state.hash_set)?;
         state.serialize_field("self_ip", &self.self_ip)?;
         state.serialize_field("self_id", &self_id)?;
-        state.serialize_field("predecessor", &*predecessor)?;
+        state.serialize_field("incarnation", &self.incarnation)?;
+        state.serialize_field("peer_transport", self.transport.name())?;
+        state.serialize_field("predecessor", &node_state.predecessor)?;
         state.serialize_field("predecessor_id", &predecessor_id)?;
-        state.serialize_field("successor_list", &*successor_list)?;
+        state.serialize_field("successor_list", &successor_list)?;
+        state.serialize_field("suspects", &suspects)?;
+        state.serialize_field("members", &members)?;
+        state.serialize_field("config", &*self.config)?;
         state.end()
     }
 }
@@ -198,47 +268,246 @@ impl Serialize for ChordNode {
 impl ChordNode {
     fn new(
         finger_table: Vec<FingerTableEntry>,
-        hash_set: HashSet<String>,
+        state: PersistedState,
         self_ip: IpAddr,
         predecessor: IpAddr,
+        config: Config,
+        storage: Storage,
+        transport: Arc<dyn Transport>,
     ) -> Self {
-        let finger_table = Arc::new(Mutex::new(finger_table));
-        let hash_set = Arc::new(Mutex::new(hash_set));
-        let predecessor = Arc::new(Mutex::new(predecessor));
-        let successor_list = Arc::new(Mutex::new(Vec::new()));
-        let replica_set = Arc::new(Mutex::new(HashSet::new()));
-        Self {
+        let node_state = NodeState {
             finger_table,
-            hash_set,
-            self_ip,
+            hash_set: state.hash_set,
             predecessor,
-            successor_list,
-            replica_set,
+            successor_list: Vec::new(),
+            replica_set: state.replica_set,
+            peer_incarnations: HashMap::new(),
+            replicas_synced: None,
+        };
+        let failures = FailureDetector::new();
+        let node = Self {
+            state: Arc::new(RwLock::new(node_state)),
+            self_ip,
+            membership: Membership::new(self_ip, state.incarnation, failures.clone()),
+            failures,
+            metrics: Metrics::new(),
+            incarnation: state.incarnation,
+            config: Arc::new(config),
+            storage,
+            transport,
+        };
+        // write the new incarnation right away, so that a crash before the first insert still counts as a restart.
+        node.persist();
+        node
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, NodeState> {
+        self.state.read().unwrap()
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, NodeState> {
+        self.state.write().unwrap()
+    }
+
+    /// writes this node's keys and incarnation to its data directory.
+    fn persist(&self) {
+        let state = {
+            let node_state = self.read();
+            PersistedState {
+                incarnation: self.incarnation,
+                hash_set: node_state.hash_set.clone(),
+                replica_set: node_state.replica_set.clone(),
+            }
+        };
+        if let Err(e) = self.storage.save(&state) {
+            warn!(
+                "Couldn't persist my keys to {}: {}",
+                self.storage.dir().display(),
+                e
+            );
+        }
+    }
+
+    pub fn self_ip(&self) -> IpAddr {
+        self.self_ip
+    }
+
+    /// returns the ID of this node on the ring, which is derived from its IP address.
+    pub fn self_id(&self) -> u64 {
+        get_identifier(&self.self_ip.to_string())
+    }
+
+    /// returns the `Hello` this node introduces itself with: the defaults of `local_hello`, with this node's ring bits and replication factor.
+    pub fn hello(&self) -> Hello {
+        Hello {
+            ring_bits: self.config.ring_bits,
+            replication_factor: self.config.replication_factor,
+            ..local_hello()
         }
     }
 
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// returns the metrics of this node in the Prometheus text format (GET /metrics): what it counted since it started (see `Metrics`), and the size of its key sets and successor list right now.
+    pub fn render_metrics(&self) -> String {
+        let (owned, replicas, successors) = {
+            let state = self.read();
+            (
+                state.hash_set.len(),
+                state.replica_set.len(),
+                state.successor_list.len(),
+            )
+        };
+        self.metrics.render(&[
+            (
+                "crust_owned_keys",
+                "Keys this node is responsible for.",
+                owned as f64,
+            ),
+            (
+                "crust_replica_keys",
+                "Keys this node holds as a replica of another node.",
+                replicas as f64,
+            ),
+            (
+                "crust_successor_list_length",
+                "Nodes in the successor list of this node.",
+                successors as f64,
+            ),
+        ])
+    }
+
+    pub fn membership(&self) -> &Membership {
+        &self.membership
+    }
+
+    /// returns every other node this node has a pointer to: fingers, successor list and predecessor.
+    fn known_nodes(&self) -> HashSet<IpAddr> {
+        let state = self.read();
+        let fingers = state.finger_table.iter().map(|entry| entry.node_ip);
+        fingers
+            .chain(state.successor_list.iter().copied())
+            .chain(std::iter::once(state.predecessor))
+            .filter(|ip| *ip != self.self_ip)
+            .collect()
+    }
+
+    /// returns true if `node` is alive. Members of the gossiped view are alive unless they were declared dead: a suspect still has time to refute, so a node that is only slow isn't routed around. Nodes the view doesn't know about are probed directly.
+    async fn is_alive(&self, node: IpAddr) -> bool {
+        match self.membership.status(node) {
+            Some(status) => status != MemberStatus::Dead,
+            None => self.transport.is_alive(self, node).await,
+        }
+    }
+
+    /// Checks whether this node is ready to serve the keys of its part of the ring (see `Readiness`). The successor is only probed if neither the failure detector nor the gossiped view already know whether it's alive.
+    pub async fn readiness(&self) -> Readiness {
+        let (successor, predecessor, owned_replicas, owns_keys, replicas_synced) = {
+            let state = self.read();
+            (
+                state.finger_table.first().unwrap().node_ip,
+                state.predecessor,
+                self.owned_replicas(&state),
+                !state.hash_set.is_empty(),
+                state.replicas_synced,
+            )
+        };
+        let alone = successor == self.self_ip;
+        let successor_reachable = if alone
+            || (!self.failures.is_suspected(successor) && self.is_alive(successor).await)
+        {
+            Ok(())
+        } else {
+            Err(format!("The successor {} doesn't answer", successor))
+        };
+        let predecessor_known = if alone || predecessor != self.self_ip {
+            Ok(())
+        } else {
+            Err("No predecessor has notified this node yet".to_string())
+        };
+        let keys_transferred = match owned_replicas {
+            Some(keys) if keys.is_empty() => Ok(()),
+            Some(keys) => Err(format!(
+                "{} keys this node owns are still replicas, waiting for the next replica sync",
+                keys.len()
+            )),
+            None => Err("This node doesn't know which keys it owns yet".to_string()),
+        };
+        let replicas_in_sync = match replicas_synced {
+            Some(true) => Ok(()),
+            Some(false) => Err("The last replica sync didn't reach every replica".to_string()),
+            None if owns_keys => Err("The keys of this node weren't replicated yet".to_string()),
+            None => Ok(()),
+        };
+        Readiness::joined(
+            successor_reachable,
+            predecessor_known,
+            keys_transferred,
+            replicas_in_sync,
+        )
+    }
+
+    /// returns the parts of this node's state that maintenance changes, as a JSON object: its successor and predecessor, successor list, finger table, suspects, how many keys and replicas it holds and whether the last replica sync worked.
+    pub fn snapshot(&self) -> serde_json::Value {
+        let state = self.read();
+        let suspects = self.failures.suspects();
+        serde_json::json!({
+            "successor": state.finger_table.first().unwrap().node_ip,
+            "predecessor": state.predecessor,
+            "successor_list": state.successor_list,
+            "finger_table": state.finger_table,
+            "suspects": suspects,
+            "keys": state.hash_set.len(),
+            "replicas": state.replica_set.len(),
+            "replicas_synced": state.replicas_synced,
+        })
+    }
+
+    /// Runs one round of `task` right away, whatever its schedule (and even while the `Supervisor` is paused), and returns how it changed this node's state (see `snapshot`). The round is recorded in `Metrics` like a scheduled one, and if it fails, this node's pointers are repaired like after a scheduled one.
+    pub async fn run_now(&self, task: Maintenance) -> StateDiff {
+        info!(action = %task, "Running maintenance on demand");
+        let before = self.snapshot();
+        let round = async {
+            match task {
+                Maintenance::Stabilize => self.stabilize().await,
+                Maintenance::Finger(index) => self.fix_finger(index).await,
+                Maintenance::Fingers => self.fix_every_finger().await,
+                Maintenance::SuccessorList => self.build_successor_list().await,
+                Maintenance::ReplicaSync => self.sync_replicas().await,
+            }
+        };
+        let result = self.maintain(task.task(), round).await;
+        StateDiff::new(task.to_string(), &before, &self.snapshot(), result)
+    }
+
     /// returns a serialized string of `Self`.
     pub fn info(&self) -> String {
         serde_json::to_string_pretty(self).expect("Can't serialize table")
     }
 
     /// walks around the Chord ring using successor pointers and returns a JSON of `to` and `from` values using `VisInfo`.
-    pub async fn ring_info(&self) -> Result<String, HandlerError> {
+    pub async fn ring_info(&self) -> Result<String, ChordError> {
         let mut set = HashSet::new();
         let mut curr_ip = self.self_ip;
         let mut current = get_identifier(&curr_ip.to_string());
         set.insert(current);
         let mut succ_ip = self.get_successor();
         let mut successor = get_identifier(&succ_ip.to_string());
-        let v = VisInfo::new(current, successor);
+        let v = VisInfo::new(curr_ip, current, successor);
         let mut result = vec![v];
 
         while !set.contains(&successor) {
             current = successor;
             curr_ip = succ_ip;
-            succ_ip = get_req(curr_ip, HTTP_SUCCESSOR, &self).await?.parse()?;
+            succ_ip = self.transport.get_successor(self, curr_ip).await?;
             successor = get_identifier(&succ_ip.to_string());
-            let vis = VisInfo::new(current, successor);
+            let vis = VisInfo::new(curr_ip, current, successor);
             result.push(vis);
             set.insert(current);
         }
@@ -246,82 +515,180 @@ impl ChordNode {
         Ok(serde_json::to_string_pretty(&result).expect("Error serializing ring info"))
     }
 
+    /// returns what this node tells `verify_ring` about itself.
+    pub fn view(&self) -> NodeView {
+        let state = self.read();
+        NodeView {
+            successor: state.finger_table.first().unwrap().node_ip,
+            predecessor: state.predecessor,
+            successor_list: state.successor_list.clone(),
+            fingers: state
+                .finger_table
+                .iter()
+                .map(|entry| Finger {
+                    start: entry.start,
+                    node: entry.node_ip,
+                })
+                .collect(),
+            keys: state.hash_set.iter().cloned().collect(),
+            replicas: state.replica_set.iter().cloned().collect(),
+        }
+    }
+
+    /// Asks every node of the ring for its `NodeView`, starting with this node and following every node a view points to, and checks them against each other (see `verify::check`): that the successor and predecessor pointers agree, that every finger points to the true successor of its start, and that every key is on its owner, with as many replicas as it should have.
+    pub async fn verify_ring(&self) -> RingCheck {
+        let mut views = BTreeMap::new();
+        let mut queue = VecDeque::from(vec![self.self_ip]);
+        while let Some(ip) = queue.pop_front() {
+            if views.contains_key(&ip) {
+                continue;
+            }
+            let view = if ip == self.self_ip {
+                Ok(self.view())
+            } else {
+                self.transport.view(self, ip).await
+            };
+            if let Ok(view) = &view {
+                queue.extend(view.neighbours());
+            }
+            views.insert(ip, view);
+        }
+        verify::check(&views, self.config.replication_factor)
+    }
+
+    /// returns what this node reports about its own load to `ring_stats`.
+    pub fn load(&self) -> Load {
+        let (requests_per_sec, key_ops_per_sec) = self.metrics.rates();
+        let state = self.read();
+        Load {
+            keys: state.hash_set.len() as u64,
+            replicas: state.replica_set.len() as u64,
+            requests_per_sec,
+            key_ops_per_sec,
+        }
+    }
+
+    /// walks around the Chord ring using successor pointers, like `ring_info`, asks every node for its `Load` and returns how the ring and the load are spread over the nodes. A node that doesn't tell its load (because it failed, or predates the `Load` RPC) is listed without one; only the walk itself fails when a node doesn't answer.
+    pub async fn ring_stats(&self) -> Result<RingStats, ChordError> {
+        let mut ring = vec![self.self_ip];
+        let mut ids = HashSet::new();
+        ids.insert(self.self_id());
+        let mut next = self.get_successor();
+        while ids.insert(get_identifier(&next.to_string())) {
+            ring.push(next);
+            next = self.transport.get_successor(self, next).await?;
+        }
+
+        let mut nodes = Vec::new();
+        for (i, ip) in ring.iter().enumerate() {
+            let id = get_identifier(&ip.to_string());
+            let previous = ring[(i + ring.len() - 1) % ring.len()];
+            let previous_id = get_identifier(&previous.to_string());
+            let range_width = match ring.len() {
+                1 => ring_size(),
+                _ => (id + ring_size() - previous_id) % ring_size(),
+            };
+            let load = if *ip == self.self_ip {
+                Ok(self.load())
+            } else {
+                self.transport.load(self, *ip).await
+            };
+            nodes.push(NodeStats {
+                node: *ip,
+                id,
+                range: Interval::new(Bracket::Open, previous_id, id, Bracket::Closed).to_string(),
+                range_width,
+                share: range_width as f64 / ring_size() as f64,
+                error: load.as_ref().err().map(|e| e.to_string()),
+                load: load.ok(),
+            });
+        }
+        Ok(RingStats::new(ring_size(), nodes))
+    }
+
     /// returns the immediate successor of this node (the first value in the finger table)
     pub fn get_successor(&self) -> IpAddr {
-        let table = self.finger_table.lock().unwrap();
-        (*table).get(0).unwrap().node_ip
+        self.read().finger_table.first().unwrap().node_ip
     }
 
     /// updates the successor of this node to a new node.
     pub fn update_successor(&self, new_succ: IpAddr) {
-        let mut table = self.finger_table.lock().unwrap();
-        let prev_entry = table.get_mut(0).unwrap();
+        let mut state = self.write();
+        let prev_entry = state.finger_table.get_mut(0).unwrap();
         let old_id = get_identifier(&prev_entry.node_ip.to_string());
         let new_id = get_identifier(&new_succ.to_string());
-        println!("prev successor: {} (id:{})", prev_entry.node_ip, old_id);
-        (*prev_entry).node_ip = new_succ;
-        (*prev_entry).successor = new_id;
-        println!(
-            "Updated successor to {} (id:{})",
-            prev_entry.node_ip, new_id
+        info!(
+            peer = %new_succ,
+            peer_id = new_id,
+            previous = %prev_entry.node_ip,
+            previous_id = old_id,
+            "Updated my successor"
         );
+        prev_entry.node_ip = new_succ;
+        prev_entry.successor = new_id;
     }
 
     pub fn get_predecessor(&self) -> IpAddr {
-        *self.predecessor.lock().unwrap()
+        self.read().predecessor
     }
 
     pub fn update_predecessor(&self, ip: IpAddr) {
-        *self.predecessor.lock().unwrap() = ip
+        self.write().predecessor = ip
     }
 
     /// calculates successor(k). This represents the first node on the Chord ring that can store the key k.
-    pub async fn calculate_successor(&self, id: &str) -> Result<IpAddr, HandlerError> {
-        let id: u64 = id.parse()?;
-        assert!(id < M);
-        let pred = self.calculate_predecessor(id).await?;
-        let successor_ip = get_req(pred, HTTP_SUCCESSOR, self).await?;
-        Ok(successor_ip.parse()?)
+    pub async fn calculate_successor(&self, id: &str) -> Result<IpAddr, ChordError> {
+        let id: u64 = id.parse().map_err(ChordError::bad_request)?;
+        if id >= ring_size() {
+            let error = format!("Invalid id {}, must be less than {}", id, ring_size());
+            return Err(ChordError::BadRequest(error));
+        }
+        self.metrics.lookup_started();
+        let (pred, hops) = self.calculate_predecessor(id).await?;
+        let successor = self.transport.get_successor(self, pred).await?;
+        self.metrics.lookup_done(hops);
+        Ok(successor)
     }
 
-    /// calculates the node that preceeds the supplied `id`. Note that this method does NOT use the predecessor pointers of `Self`; rather this method walks around the Chord ring using the successor pointers (and the finger table entries) to find the predecessor.
-    async fn calculate_predecessor(&self, id: u64) -> Result<IpAddr, HandlerError> {
+    /// calculates the node that preceeds the supplied `id`, and the number of nodes the lookup was forwarded through to find it. Note that this method does NOT use the predecessor pointers of `Self`; rather this method walks around the Chord ring using the successor pointers (and the finger table entries) to find the predecessor.
+    async fn calculate_predecessor(&self, id: u64) -> Result<(IpAddr, u32), ChordError> {
         let mut n_dash = self.self_ip;
+        let mut hops = 0;
         loop {
             let n_dash_id = get_identifier(&n_dash.to_string());
             let successor = if n_dash == self.self_ip {
-                self.get_successor().to_string()
+                self.get_successor()
             } else {
-                get_req(n_dash, HTTP_SUCCESSOR, self).await?
+                self.transport.get_successor(self, n_dash).await?
             };
-            let successor_hash = get_identifier(&successor);
+            let successor_hash = get_identifier(&successor.to_string());
             let interval = Interval::new(Bracket::Open, n_dash_id, successor_hash, Bracket::Closed);
             if interval.contains(id) {
                 break;
             }
             n_dash = if n_dash == self.self_ip {
-                self.closest_preceding_finger(&id.to_string())
+                self.closest_preceding_finger(id)
             } else {
-                get_req(n_dash, &format!("{}{}/", HTTP_SUCCESSOR_CPF, id), self)
+                self.transport
+                    .closest_preceding_finger(self, n_dash, id)
                     .await?
-                    .parse()?
             };
+            hops += 1;
         }
 
-        Ok(n_dash)
+        Ok((n_dash, hops))
     }
 
     /// Returns the closest node that `Self` thinks that can store `id`.
-    pub fn closest_preceding_finger(&self, id: &str) -> IpAddr {
-        let id: u64 = id.parse().unwrap();
-        assert!(id < M);
+    /// `id` has to be on the ring (see `check_id`).
+    pub fn closest_preceding_finger(&self, id: u64) -> IpAddr {
         let interval = Interval::new(
             Bracket::Open,
             get_identifier(&self.self_ip.to_string()),
             id,
             Bracket::Open,
         );
-        for entry in self.finger_table.lock().unwrap().iter().rev() {
+        for entry in self.read().finger_table.iter().rev() {
             if interval.contains(entry.successor) {
                 return entry.node_ip;
             }
@@ -329,81 +696,103 @@ impl ChordNode {
         self.self_ip
     }
 
-    /// called when a node wants to add itself (`s`) as an `i`th entry in `Self`'s finger table.
-    pub async fn update_finger_table(&mut self, s: IpAddr, i: u64) -> Result<(), HandlerError> {
+    /// called when a node wants to add itself (`s`) as an `i`th entry in `Self`'s finger table. Fails with `ChordError::BadRequest` if the finger table has no entry `i`.
+    pub async fn update_finger_table(&mut self, s: IpAddr, i: u64) -> Result<(), ChordError> {
         let self_id = get_identifier(&self.self_ip.to_string());
         let s_id = get_identifier(&s.to_string());
-        let ith_ip_id = {
-            let locked_table = self.finger_table.lock().unwrap();
-            (*locked_table).get(i as usize).unwrap().successor
-        };
-        let interval = Interval::new(Bracket::Closed, self_id, ith_ip_id, Bracket::Open);
-        if interval.contains(s_id) {
-            {
-                let mut lock = self.finger_table.lock().unwrap();
-                let entry = lock.get_mut(i as usize).unwrap();
+        // check and update the entry, and read the predecessor to patch next, under one lock.
+        let pred = {
+            let mut state = self.write();
+            // `i` comes from a peer: an index out of range must not panic while the lock is held.
+            let entries = state.finger_table.len();
+            let entry = match state.finger_table.get_mut(i as usize) {
+                Some(entry) => entry,
+                None => {
+                    return Err(ChordError::BadRequest(format!(
+                        "Invalid finger {}, the finger table has {} entries",
+                        i, entries
+                    )))
+                }
+            };
+            let interval = Interval::new(Bracket::Closed, self_id, entry.successor, Bracket::Open);
+            if interval.contains(s_id) {
                 entry.successor = s_id;
                 entry.node_ip = s;
+                Some(state.predecessor)
+            } else {
+                None
             }
-            let pred = *self.predecessor.lock().unwrap();
+        };
+        if let Some(pred) = pred {
             let pred_id = get_identifier(&pred.to_string());
             if pred_id == s_id {
-                println!(
-                    "Warning: Skipping patching my predecessor because it's the same as s_id!"
-                );
+                warn!("Skipping patching my predecessor because it's the same as s_id!");
                 return Ok(());
             }
-            println!("Done. Patching my predecessor ({})", pred_id);
-            data_req(
-                pred,
-                HTTP_FINGER_TABLE,
-                vec![("n", s.to_string()), ("i", i.to_string())],
-                self,
-                "PATCH",
-            )
-            .await?;
+            debug!(peer = %pred, peer_id = pred_id, "Patching my predecessor");
+            self.transport.update_finger_table(self, pred, s, i).await?;
         }
 
         Ok(())
     }
 
-    /// Sees if there's a possible better successor for `Self` and updates if possible. This function is run periodically `STABILIZE_INTERVAL` times.
-    async fn stabilize(&self) -> Result<(), HandlerError> {
-        let client = reqwest::Client::new();
-        loop {
-            tokio::time::sleep(Duration::from_secs(STABILIZE_INTERVAL)).await;
-            let succ_ip = self.get_successor();
-            let successors_predecessor = get_req(succ_ip, HTTP_PREDECESSOR, self).await?;
-            if is_node_alive(successors_predecessor.parse()?, Some(&client)).await
-                && successors_predecessor != self.self_ip.to_string()
-            {
-                let successors_predecessor_id = get_identifier(&successors_predecessor);
-                let self_id = get_identifier(&self.self_ip.to_string());
-                let succ_id = get_identifier(&succ_ip.to_string());
-                let int_self_to_successor =
-                    Interval::new(Bracket::Open, self_id, succ_id, Bracket::Open);
-                if int_self_to_successor.contains(successors_predecessor_id) {
-                    println!("stabilize() found a new successor, updating...");
-                    self.update_successor(successors_predecessor.parse()?);
-                }
-
-                // notify successor that this node should be their predecessor
-                data_req(
-                    succ_ip,
-                    HTTP_NOTIFY,
-                    vec![("n", self.self_ip.to_string())],
-                    self,
-                    "PATCH",
-                )
-                .await?;
+    /// Sees if there's a possible better successor for `Self` and updates if possible. This function is run every `Intervals::stabilize` seconds by the `Supervisor` (see `start_maintenance`).
+    async fn stabilize(&self) -> Result<(), ChordError> {
+        if !self.failures.suspects().is_empty() {
+            // a lookup or an earlier round reported a dead node; repair pointers before using them.
+            self.handle_failure().await;
+        }
+        let succ_ip = self.get_successor();
+        let successors_predecessor = self.transport.get_predecessor(self, succ_ip).await?;
+        if self.is_alive(successors_predecessor).await && successors_predecessor != self.self_ip {
+            let successors_predecessor_id = get_identifier(&successors_predecessor.to_string());
+            let self_id = get_identifier(&self.self_ip.to_string());
+            let succ_id = get_identifier(&succ_ip.to_string());
+            let int_self_to_successor =
+                Interval::new(Bracket::Open, self_id, succ_id, Bracket::Open);
+            if int_self_to_successor.contains(successors_predecessor_id) {
+                debug!(peer = %successors_predecessor, "stabilize() found a new successor, updating...");
+                self.update_successor(successors_predecessor);
             }
 
-            // fix fingers
-            self.fix_fingers().await?;
+            // notify successor that this node should be their predecessor
+            self.transport.notify(self, succ_ip, self.self_ip).await?;
+        }
+        Ok(())
+    }
 
-            // rebuild successor list
-            self.build_successor_list().await?;
+    /// Runs one round of the maintenance task `task`, and records how long it took (see `Metrics::task_round`). If the round fails, this node's pointers are repaired with `handle_failure` before the error is handed back to the `Supervisor`.
+    async fn maintain(
+        &self,
+        task: &'static str,
+        round: impl Future<Output = Result<(), ChordError>>,
+    ) -> Result<(), ChordError> {
+        let span = info_span!("maintenance", task, node_id = self.self_id());
+        async {
+            let started = Instant::now();
+            let result = round.await;
+            self.metrics
+                .task_round(task, started.elapsed(), result.is_err());
+            if let Err(e) = &result {
+                debug!("Round failed: {}", e);
+                self.handle_failure().await;
+            }
+            result
         }
+        .instrument(span)
+        .await
+    }
+
+    /// Runs one round of every maintenance task, one after the other. This drives a node by hand instead of on the timers of `start_maintenance`, for example in tests over a `MemoryNetwork`. Every task runs even if an earlier one fails; the first error is returned.
+    pub async fn maintenance_round(&self) -> Result<(), ChordError> {
+        let results = vec![
+            self.maintain("stabilize", self.stabilize()).await,
+            self.maintain("fix_fingers", self.fix_fingers()).await,
+            self.maintain("successor_list", self.build_successor_list())
+                .await,
+            self.maintain("replica_sync", self.sync_replicas()).await,
+        ];
+        results.into_iter().collect()
     }
 
     /// `other_node` thinks that it should be `Self`'s direct predecessor.
@@ -413,439 +802,683 @@ impl ChordNode {
         let other_id = get_identifier(&other_node.to_string());
         let self_id = get_identifier(&self.self_ip.to_string());
         let int_predecessor_to_self = Interval::new(Bracket::Open, pred_id, self_id, Bracket::Open);
-        let is_predecessor_alive = is_node_alive(predecessor, None).await;
+        let is_predecessor_alive = self.is_alive(predecessor).await;
         if !is_predecessor_alive
             || (predecessor == self.self_ip)
             || (int_predecessor_to_self.contains(other_id))
         {
+            let mut state = self.write();
+            if state.predecessor != predecessor {
+                // someone else updated the predecessor while we were checking liveness; their update wins.
+                return;
+            }
             if other_node != predecessor {
-                println!(
-                    "notify found a better predecessor. Updating to {} (id:{})",
-                    other_node,
-                    get_identifier(&other_node.to_string())
+                info!(
+                    peer = %other_node,
+                    peer_id = other_id,
+                    "notify found a better predecessor, updating my predecessor"
                 );
             }
-            self.update_predecessor(other_node);
+            state.predecessor = other_node;
         }
     }
 
-    async fn fix_fingers(&self) -> Result<(), HandlerError> {
-        let m = (M as f64).log2() as u32;
-        let rand_idx = rand::thread_rng().gen_range(0..m) as usize;
+    /// Refreshes a random entry of the finger table.
+    async fn fix_fingers(&self) -> Result<(), ChordError> {
+        let rand_idx = rand::thread_rng().gen_range(0..ring_bits()) as usize;
+        self.fix_finger(rand_idx).await
+    }
 
-        let start = {
-            let table = self.finger_table.lock().unwrap();
-            let rand_entry = table.get(rand_idx).unwrap();
-            rand_entry.start
-        };
+    /// Looks up the successor of the start of finger `idx` again, and points the finger at it.
+    async fn fix_finger(&self, idx: usize) -> Result<(), ChordError> {
+        let start = self.read().finger_table.get(idx).unwrap().start;
 
         let succ = self.calculate_successor(&start.to_string()).await?;
         let succ_id = get_identifier(&succ.to_string());
-        {
-            let mut table = self.finger_table.lock().unwrap();
-            let rand_entry = table.get_mut(rand_idx).unwrap();
-            rand_entry.node_ip = succ;
-            rand_entry.successor = succ_id;
+        let mut state = self.write();
+        let entry = state.finger_table.get_mut(idx).unwrap();
+        entry.node_ip = succ;
+        entry.successor = succ_id;
+        Ok(())
+    }
+
+    /// Refreshes every entry of the finger table, in order, stopping at the first one that fails.
+    async fn fix_every_finger(&self) -> Result<(), ChordError> {
+        for idx in 0..ring_bits() as usize {
+            self.fix_finger(idx).await?;
+        }
+        Ok(())
+    }
+
+    /// Pushes every key this node owns to the nodes in `successor_list`, in case a replica missed an insert (for example because it joined or restarted later). Replicas of keys that this node now owns (because the previous owner failed) are moved to `hash_set`. Whether the push worked is remembered for `readiness`.
+    async fn sync_replicas(&self) -> Result<(), ChordError> {
+        let promoted = {
+            let mut state = self.write();
+            let keys = self.owned_replicas(&state).unwrap_or_default();
+            for key in &keys {
+                state.replica_set.remove(key);
+            }
+            let promoted = keys.len();
+            state.hash_set.extend(keys);
+            promoted
+        };
+        if promoted > 0 {
+            info!(
+                "Now the owner of {} keys that were replicas, moved them to my hash set",
+                promoted
+            );
+            self.persist();
+        }
+
+        let (keys, list) = {
+            let state = self.read();
+            let keys: Vec<String> = state.hash_set.iter().cloned().collect();
+            (keys, state.successor_list.clone())
+        };
+        let result = self.push_replicas(keys, list).await;
+        self.write().replicas_synced = Some(result.is_ok());
+        result
+    }
+
+    /// returns the keys in `state.replica_set` that this node is responsible for, or `None` if this node just joined and doesn't know its range yet.
+    fn owned_replicas(&self, state: &NodeState) -> Option<Vec<String>> {
+        let alone = state.finger_table.first().unwrap().node_ip == self.self_ip;
+        if state.predecessor == self.self_ip && !alone {
+            return None;
+        }
+        let pred_id = get_identifier(&state.predecessor.to_string());
+        let int_predecessor_to_self =
+            Interval::new(Bracket::Open, pred_id, self.self_id(), Bracket::Closed);
+        let keys = state
+            .replica_set
+            .iter()
+            .filter(|key| int_predecessor_to_self.contains(get_identifier(key)))
+            .cloned()
+            .collect();
+        Some(keys)
+    }
+
+    /// sends `keys` to every node of `list` but this one, as replicas.
+    async fn push_replicas(&self, keys: Vec<String>, list: Vec<IpAddr>) -> Result<(), ChordError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        for node in list {
+            if node != self.self_ip {
+                self.transport
+                    .insert_replica(self, node, keys.clone())
+                    .await?;
+            }
         }
         Ok(())
     }
 
-    async fn build_successor_list(&self) -> Result<(), HandlerError> {
-        let m = (M as f64).log2() as u32;
-        let client = reqwest::Client::new();
+    async fn build_successor_list(&self) -> Result<(), ChordError> {
         let mut successor = self.get_successor();
         let mut new_successors = Vec::new();
-        for _ in 0..m {
-            let next_successor = client
-                .get(format!("http://{}:{}/{}", successor, PORT, HTTP_SUCCESSOR))
-                .timeout(Duration::from_secs(REQ_TIMEOUT))
-                .send()
-                .await;
-            match next_successor {
-                Ok(s) => successor = s.text().await?.parse()?,
+        for _ in 0..self.config.replication_factor {
+            match self.transport.get_successor(self, successor).await {
+                Ok(s) => successor = s,
                 //if a potential successor is down, skip adding it to the list.
                 Err(_) => break,
             };
             new_successors.push(successor);
         }
-        *self.successor_list.lock().unwrap() = new_successors;
+        self.write().successor_list = new_successors;
         Ok(())
     }
 
-    /// This function is called by the first function that detects that an HTTP request failed. Unfrotunately, that also means it's very hard to identify which method called `handle_failure`.
-    /// For example, this method can be called during `stabilize()` or when calculating a successor. Although in an ideal case both functions should have handled this very differently (for example, in an ideal scenario, `calculate_successor()` should notify the user that there was a failure and that they should try again; instead of just calling `handle_failure`).
-    /// Right now, this method simply contacts the successor and predecessor and attempts to fix these pointers by using the `successor_list`.
+    /// Repairs this node's pointers after a failure. Only maintenance calls this (`stabilize()`, and `maintain()` when a round fails): client operations like `insert` or `calculate_successor` just report the node that didn't respond to `self.failures` and return `ChordError::Timeout` or `ChordError::Unreachable`, so the client can retry once this has run.
+    /// This method contacts the successor and predecessor and attempts to fix these pointers by using the `successor_list`. Suspects that respond again are cleared from the failure detector, and so are suspects that this node no longer points to.
     async fn handle_failure(&self) {
         // check if successor is alive
-        println!("Failure detected, attempting to fix pointers...");
-        let client = reqwest::Client::new();
+        info!("Failure detected, attempting to fix pointers...");
+        self.metrics.handle_failure_started();
         let successor_ip = self.get_successor();
-        let resp = client
-            .get(format!(
-                "http://{}:{}/{}",
-                successor_ip, PORT, HTTP_SUCCESSOR
-            ))
-            .timeout(Duration::from_secs(REQ_TIMEOUT))
-            .send()
-            .await;
-        match resp {
+        // peer calls clear or report the node they contact, so the failure detector is kept up to date here.
+        match self.transport.get_successor(self, successor_ip).await {
             Ok(_) => {}
             Err(_) => {
-                println!("Successor is down. Fixing...");
-                let new_succ = self.get_first_live_successor(&client).await;
+                warn!(peer = %successor_ip, "Successor is down. Fixing...");
+                let new_succ = self.get_first_live_successor().await;
                 self.update_successor(new_succ);
-                println!(
-                    "Notifying my new successor (id:{}) to update their predecessor...",
-                    get_identifier(&new_succ.to_string())
-                );
-                client
-                    .patch(format!("http://{}:{}/{}", new_succ, PORT, HTTP_NOTIFY))
-                    .timeout(Duration::from_secs(REQ_TIMEOUT))
-                    .form(&vec![("n", self.self_ip.to_string())])
-                    .send()
-                    .await
-                    .unwrap();
+                debug!(peer = %new_succ, "Notifying my new successor to update their predecessor...");
+                if let Err(e) = self.transport.notify(self, new_succ, self.self_ip).await {
+                    warn!(peer = %new_succ, "Couldn't notify my new successor: {}", e);
+                }
             }
         };
 
         // check if predecessor is alive
         let predecessor_ip = self.get_predecessor();
-        let resp = client
-            .get(format!(
-                "http://{}:{}/{}",
-                predecessor_ip, PORT, HTTP_SUCCESSOR
-            ))
-            .timeout(Duration::from_secs(REQ_TIMEOUT))
-            .send()
-            .await;
-        match resp {
+        match self.transport.get_successor(self, predecessor_ip).await {
             Ok(_) => {}
             Err(_) => {
-                println!("Predecessor is down. Fixing to self IP.");
+                warn!(peer = %predecessor_ip, "Predecessor is down. Fixing to self IP.");
                 self.update_predecessor(self.self_ip)
             }
         }
+
+        // any other suspect that answers now was only slow (or was already routed around); forget about it.
+        for suspect in self.failures.suspects() {
+            if self.is_alive(suspect).await {
+                self.failures.clear(suspect);
+            }
+        }
+        // a dead node that was routed out of the fingers, successor list and predecessor can't break anything anymore; suspecting it would only make every stabilize round repair pointers again.
+        let known = self.known_nodes();
+        self.failures.retain(|node| known.contains(&node));
     }
 
     /// used by `handle_failure` to contact each potential successor in `successor_list` and returning the first node that responds.
-    async fn get_first_live_successor(&self, client: &reqwest::Client) -> IpAddr {
-        let entries: Vec<IpAddr> = {
-            let table = self.successor_list.lock().unwrap();
-            table.iter().copied().collect()
-        };
+    async fn get_first_live_successor(&self) -> IpAddr {
+        let entries: Vec<IpAddr> = self.read().successor_list.clone();
 
         for possible_succ in entries {
-            println!("Trying to contact {}", possible_succ);
-            let resp = client
-                .get(format!(
-                    "http://{}:{}/{}",
-                    possible_succ, PORT, HTTP_SUCCESSOR
-                ))
-                .timeout(Duration::from_secs(REQ_TIMEOUT))
-                .send()
-                .await;
-            match resp {
+            debug!(peer = %possible_succ, "Trying to contact a possible successor");
+            match self.transport.get_successor(self, possible_succ).await {
                 Ok(_) => return possible_succ,
                 Err(_) => continue,
             }
         }
-        return self.self_ip;
+        self.self_ip
     }
 
     /// uses `calculate_successor()` to find which node a key should be inserted in, then inserts the key on that node.
-    pub async fn insert(&self, key: String) -> Result<String, HandlerError> {
+    pub async fn insert(&self, key: String) -> Result<String, ChordError> {
         let key_id = get_identifier(&key);
+        Span::current().record("key_id", key_id);
         let key_successor = self.calculate_successor(&key_id.to_string()).await?;
+        debug!(key_id, owner = %key_successor, "Inserting a key");
         if key_successor == self.self_ip {
             //insert here!
-            (*self.hash_set.lock().unwrap()).insert(key.clone());
+            self.metrics.key_operation_served();
+            self.write().hash_set.insert(key.clone());
+            self.persist();
             self.send_to_replicas(key).await?;
         } else {
-            let inserted_at =
-                data_req(key_successor, HTTP_KEY, vec![("key", key)], &self, "POST").await?;
-            return Ok(inserted_at);
+            return self.transport.insert(self, key_successor, key).await;
         }
         let self_id = get_identifier(&self.self_ip.to_string());
         Ok(self_id.to_string())
     }
 
     /// Make copies of `key` and send it to all nodes in `successor_list` to be inserted as replicas.
-    async fn send_to_replicas(&self, key: String) -> Result<(), HandlerError> {
-        let list = self.successor_list.lock().unwrap().clone();
+    async fn send_to_replicas(&self, key: String) -> Result<(), ChordError> {
+        let list = self.read().successor_list.clone();
         for node in list {
-            data_req(
-                node,
-                HTTP_REPLICA,
-                vec![("key", key.clone())],
-                &self,
-                "POST",
-            )
-            .await?;
+            self.transport
+                .insert_replica(self, node, vec![key.clone()])
+                .await?;
         }
         Ok(())
     }
 
-    pub fn insert_replica(&self, key: String) {
-        (*self.replica_set.lock().unwrap()).insert(key);
+    pub fn insert_replica(&self, keys: Vec<String>) {
+        self.write().replica_set.extend(keys);
+        self.persist();
+    }
+
+    /// uses `calculate_successor()` to find the node responsible for `key`, then deletes the key from that node and its replicas. returns true if the key was there.
+    pub async fn delete(&self, key: &str) -> Result<bool, ChordError> {
+        let key_id = get_identifier(key);
+        Span::current().record("key_id", key_id);
+        let key_successor = self.calculate_successor(&key_id.to_string()).await?;
+        debug!(key_id, owner = %key_successor, "Deleting a key");
+        if key_successor != self.self_ip {
+            return self.transport.delete(self, key_successor, key).await;
+        }
+        self.metrics.key_operation_served();
+        let found = {
+            let mut state = self.write();
+            let owned = state.hash_set.remove(key);
+            // a replica this node didn't promote yet (see `sync_replicas`) counts too.
+            state.replica_set.remove(key) || owned
+        };
+        self.persist();
+        let list = self.read().successor_list.clone();
+        for node in list {
+            self.transport
+                .delete_replica(self, node, vec![key.to_string()])
+                .await?;
+        }
+        Ok(found)
+    }
+
+    pub fn delete_replica(&self, keys: Vec<String>) {
+        let mut state = self.write();
+        for key in &keys {
+            state.replica_set.remove(key);
+        }
+        drop(state);
+        self.persist();
+    }
+
+    /// Called on a recovering node right after it rejoined the ring. Tells the successor that this node is back (with a new incarnation) and takes back the keys the successor held for it while it was down. Keys this node had before the crash were already reloaded from disk.
+    async fn rejoin(&self) -> Result<(), ChordError> {
+        let successor = self.get_successor();
+        if successor == self.self_ip {
+            return Ok(());
+        }
+        let resp = self
+            .transport
+            .rejoin(self, successor, self.self_ip, self.incarnation)
+            .await;
+        let keys: Vec<String> = match resp {
+            Ok(keys) => keys,
+            Err(ChordError::Conflict(_)) => {
+                warn!("My successor has already seen a newer incarnation of me; not taking any keys back.");
+                return Ok(());
+            }
+            Err(ChordError::NotOwner(e)) => {
+                info!(
+                    "Not taking any keys back, {}; stabilize() will find my real successor.",
+                    e
+                );
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        info!(
+            peer = %successor,
+            "Took back {} keys that my successor held while I was down",
+            keys.len()
+        );
+        self.write().hash_set.extend(keys);
+        self.persist();
+        Ok(())
+    }
+
+    /// `other_node` restarted after a crash and says it's back as `incarnation`. If it's a newer incarnation than any seen before and `other_node` should be this node's predecessor, this node stops suspecting it, makes it its predecessor, and returns the keys it took over while `other_node` was down. Those keys are kept as replicas here, since this node is `other_node`'s successor.
+    /// A stale incarnation is a `Conflict`, and a rejoin from a node that isn't this node's predecessor is `NotOwner`.
+    pub fn handle_rejoin(
+        &self,
+        other_node: IpAddr,
+        incarnation: u64,
+    ) -> Result<Vec<String>, ChordError> {
+        let other_id = get_identifier(&other_node.to_string());
+        let self_id = get_identifier(&self.self_ip.to_string());
+        // the incarnation check, the predecessor update and the key handover happen under one lock, so that a concurrent notify() or insert can't interleave with them.
+        let handed_back = {
+            let mut state = self.write();
+            let known = state
+                .peer_incarnations
+                .get(&other_node)
+                .copied()
+                .unwrap_or(0);
+            if incarnation <= known {
+                return Err(ChordError::Conflict(format!(
+                    "Stale rejoin from {}: incarnation {} but already saw {}",
+                    other_node, incarnation, known
+                )));
+            }
+            state.peer_incarnations.insert(other_node, incarnation);
+            info!(
+                peer = %other_node,
+                peer_id = other_id,
+                incarnation,
+                "A peer is back as a new incarnation"
+            );
+            self.failures.clear(other_node);
+
+            let predecessor = state.predecessor;
+            let pred_id = get_identifier(&predecessor.to_string());
+            let int_predecessor_to_self =
+                Interval::new(Bracket::Open, pred_id, self_id, Bracket::Open);
+            if predecessor != self.self_ip
+                && predecessor != other_node
+                && !self.failures.is_suspected(predecessor)
+                && !int_predecessor_to_self.contains(other_id)
+            {
+                // someone else sits between `other_node` and this node; leave it to stabilize().
+                return Err(ChordError::NotOwner(format!(
+                    "{} isn't the successor of {}, its predecessor is {}",
+                    self.self_ip, other_node, predecessor
+                )));
+            }
+            state.predecessor = other_node;
+
+            let int_other_to_self =
+                Interval::new(Bracket::Open, other_id, self_id, Bracket::Closed);
+            let keys: Vec<String> = state
+                .hash_set
+                .iter()
+                .filter(|key| !int_other_to_self.contains(get_identifier(key)))
+                .cloned()
+                .collect();
+            for key in &keys {
+                state.hash_set.remove(key);
+            }
+            state.replica_set.extend(keys.iter().cloned());
+            keys
+        };
+        self.persist();
+        Ok(handed_back)
     }
 
     /// Uses `calculate_successor()` to find the node that's responsible for `key`, then asks that node if it has a key.
-    pub async fn contains(&self, key: &str) -> Result<bool, HandlerError> {
+    pub async fn contains(&self, key: &str) -> Result<bool, ChordError> {
         let key_id = get_identifier(key);
+        Span::current().record("key_id", key_id);
         let key_successor = self.calculate_successor(&key_id.to_string()).await?;
+        debug!(key_id, owner = %key_successor, "Looking for a key");
         if key_successor == self.self_ip {
             // this node is responsible for this key!
-            match (*self.hash_set.lock().unwrap()).contains(key) {
-                true => return Ok(true),
-                false => match (*self.replica_set.lock().unwrap()).contains(key) {
+            self.metrics.key_operation_served();
+            let state = self.read();
+            match state.hash_set.contains(key) {
+                true => Ok(true),
+                false => match state.replica_set.contains(key) {
                     true => {
-                        println!("Warning: Key found, but in replica set. This means this node is now the new owner of this key (as opposed to being just a replica). This key should be moved from replica set to hash set.");
-                        return Ok(true);
-                    }
-                    false => {
-                        return Ok(false);
+                        warn!(key_id, "Key found, but in replica set. This means this node is now the new owner of this key (as opposed to being just a replica). This key should be moved from replica set to hash set.");
+                        Ok(true)
                     }
+                    false => Ok(false),
                 },
             }
         } else {
             // this node isn't responsible, contact key_successor.
-            let result: bool = get_req(key_successor, &format!("{}{}", HTTP_KEY, key), self)
-                .await?
-                .parse()?;
-            Ok(result)
+            self.transport.contains(self, key_successor, key).await
         }
     }
 }
 
 /// Creates and returns a new `ChordNode`.
-/// This is comparatively easier when there are no arguments; this means that this node will be the first node in the ring. If there's an argument, it must be an IP address; that IP address will then be contacted and used to initialize this node's successor and predecessor fields.
-pub fn initialize_node() -> ChordNode {
-    let args: Vec<String> = env::args().collect();
-    let self_ip = get_self_ip();
+/// This is comparatively easier when there are no `seeds` (`--create`); this means that this node will be the first node in the ring. Otherwise, every seed must be a node already in the ring. Seeds are tried in order (see `join_any`) and the first one that responds is used to initialize this node's successor and predecessor fields.
+/// If a previous run left its state in the data directory, its keys are reloaded and this node comes back as the next incarnation. A recovering node that joins through a seed then calls `rejoin()` so that its successor hands back the keys it held in the meantime.
+/// The node runs with `config`, and is identified by its advertised address (see `Config::advertised_ip`). `security` says how it authenticates itself to its peers (see `Security`).
+pub async fn initialize_node(
+    config: &Config,
+    seeds: &[SocketAddr],
+    security: &Security,
+) -> ChordNode {
+    set_ring_bits(config.ring_bits);
+    let self_ip = match config.advertised_ip() {
+        Ok(ip) => ip,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
     let self_id = get_identifier(&self_ip.to_string());
-    println!("My ip is {} and my ID is {}", self_ip, self_id);
-    if args.len() == 1 {
+    info!(node_id = self_id, ip = %self_ip, "Starting node");
+    let storage = Storage::new(&config.data_dir.to_string_lossy());
+    let state = match storage.load() {
+        Some(previous) => {
+            info!(
+                "Recovering from a previous run: incarnation {}, {} keys, {} replicas",
+                previous.incarnation + 1,
+                previous.hash_set.len(),
+                previous.replica_set.len()
+            );
+            PersistedState {
+                incarnation: previous.incarnation + 1,
+                ..previous
+            }
+        }
+        None => PersistedState {
+            incarnation: 1,
+            ..PersistedState::default()
+        },
+    };
+    if seeds.is_empty() {
         // first node
-        let mut finger_table = Vec::new();
-        let hash_map = HashSet::new();
-        let m = (M as f64).log2() as u32;
-        for i in 0..m {
-            let start = get_start(self_id, i);
-            let k_plus_one_start = get_start(self_id, i + 1);
-            let interval = Interval::new(Bracket::Closed, start, k_plus_one_start, Bracket::Open);
-            let first_entry = FingerTableEntry::new(start, interval, self_id, self_ip);
-            finger_table.push(first_entry);
-        }
-
-        ChordNode::new(finger_table, hash_map, self_ip, self_ip)
+        let transport = peer::transport_named(peer::SUPPORTED_TRANSPORTS[0], security).unwrap();
+        info!("Talking to peers over {}", transport.name());
+        create_ring(self_ip, state, config.clone(), storage, transport)
     } else {
-        tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(join(self_ip, args[1].parse().unwrap()))
-            .unwrap()
+        let mut other_seeds = Vec::new();
+        for &seed in seeds {
+            if seed.ip() == self_ip {
+                warn!(%seed, "Ignoring seed: that's me!");
+            } else {
+                other_seeds.push(seed);
+            }
+        }
+        let joined = join_any(self_ip, &other_seeds, state, config, storage, security);
+        match joined
+            .instrument(info_span!("join", node_id = self_id))
+            .await
+        {
+            Ok(node) => node,
+            Err(e) => {
+                error!("Couldn't join the ring: {}", e);
+                std::process::exit(1);
+            }
+        }
     }
 }
 
-/// `start` is a Chord term. n.finger[k].start=(n+2^k)%M.
-fn get_start(n: u64, k: u32) -> u64 {
-    (n + u64::pow(2, k)) % M
+/// Tries to `join` the ring through each of `seeds`, in order. If none of them respond (for example because the whole cluster is starting at once), this waits and tries all seeds again, doubling the wait each round up to `JOIN_BACKOFF_MAX` seconds. Gives up after `JOIN_DEADLINE` seconds, or as soon as a seed turns out to be incompatible, since retrying won't change that.
+async fn join_any(
+    self_ip: IpAddr,
+    seeds: &[SocketAddr],
+    state: PersistedState,
+    config: &Config,
+    storage: Storage,
+    security: &Security,
+) -> Result<ChordNode, ChordError> {
+    if seeds.is_empty() {
+        let error = "No valid seed to join the ring through";
+        return Err(ChordError::BadRequest(error.to_string()));
+    }
+    let deadline = Instant::now() + Duration::from_secs(JOIN_DEADLINE);
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        for seed in seeds {
+            info!(%seed, "Trying to join the ring through a seed...");
+            let timeout = Duration::from_secs(config.request_timeout);
+            let transport = peer::negotiate(*seed, security, timeout).await;
+            info!("Talking to peers over {}", transport.name());
+            let seed_ip = seed.ip();
+            let (state, config, storage) = (state.clone(), config.clone(), storage.clone());
+            match join(self_ip, seed_ip, state, config, storage, transport).await {
+                Ok(node) => {
+                    info!(%seed, "Joined the ring");
+                    return Ok(node);
+                }
+                Err(e @ ChordError::Incompatible(_)) => return Err(e),
+                Err(e) => warn!(%seed, "Couldn't join through this seed: {}", e),
+            }
+        }
+        if Instant::now() + backoff > deadline {
+            return Err(ChordError::Internal(format!(
+                "None of the seeds {:?} responded within {} seconds",
+                seeds, JOIN_DEADLINE
+            )));
+        }
+        warn!("No seed responded, retrying in {}s...", backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = std::cmp::min(backoff * 2, Duration::from_secs(JOIN_BACKOFF_MAX));
+    }
 }
 
-/// Use an `existing_node` to initialize this `ChordNode`'s fields.
-async fn join(self_ip: IpAddr, existing_node: IpAddr) -> Result<ChordNode, HandlerError> {
-    println!("Initializing my finger tables...");
-    let node = init_finger_table(self_ip, existing_node).await?;
-    println!("Done.");
-    println!("Skipping moving keys...");
-    move_keys().await?;
-    Ok(node)
+/// Creates the first node of a new ring, where every finger (and the predecessor) points to the node itself. The ring has `config.ring_bits` bits (see `set_ring_bits`).
+pub fn create_ring(
+    self_ip: IpAddr,
+    state: PersistedState,
+    config: Config,
+    storage: Storage,
+    transport: Arc<dyn Transport>,
+) -> ChordNode {
+    set_ring_bits(config.ring_bits);
+    let finger_table = blank_finger_table(self_ip);
+    ChordNode::new(
+        finger_table,
+        state,
+        self_ip,
+        self_ip,
+        config,
+        storage,
+        transport,
+    )
 }
 
-/// Create a blank finger table (where all entries point to `self_ip`) and return it. Only the first entry is initialized properly using `find_successor`.
-async fn init_finger_table(
-    self_ip: IpAddr,
-    existing_node: IpAddr,
-) -> Result<ChordNode, HandlerError> {
+/// returns a finger table for `self_ip` where all entries point to `self_ip`.
+fn blank_finger_table(self_ip: IpAddr) -> Vec<FingerTableEntry> {
     let self_id = get_identifier(&self_ip.to_string());
-    let m = (M as f64).log2() as u32;
     let mut finger_table = Vec::new();
-    for i in 0..m {
+    for i in 0..ring_bits() {
         let start = get_start(self_id, i);
-        let start_plus_one = get_start(self_id, i + 1);
-        let interval = Interval::new(Bracket::Closed, start, start_plus_one, Bracket::Open);
-        let succ_ip = if i == 0 {
-            let succ_ip = reqwest::get(format!(
-                "http://{}:{}/{}",
-                existing_node, PORT, HTTP_SUCCESSOR
-            ))
-            .await?
-            .text()
-            .await?;
-            println!(
-                "My successor is {} (id:{})",
-                succ_ip,
-                get_identifier(&succ_ip.to_string())
-            );
-            succ_ip
-        } else {
-            self_ip.to_string()
-        };
-        let succ_id = get_identifier(&succ_ip.to_string());
-        let entry = FingerTableEntry::new(start, interval, succ_id, succ_ip.parse()?);
-        finger_table.push(entry);
+        let k_plus_one_start = get_start(self_id, i + 1);
+        let interval = Interval::new(Bracket::Closed, start, k_plus_one_start, Bracket::Open);
+        finger_table.push(FingerTableEntry::new(start, interval, self_id, self_ip));
     }
+    finger_table
+}
 
-    let predecessor = self_ip;
-    println!("Setting my predecessor as me. This will be fixed later by notify()");
+/// `start` is a Chord term. n.finger[k].start=(n+2^k)%2^m, where m is `ring_bits()`.
+fn get_start(n: u64, k: u32) -> u64 {
+    (n + u64::pow(2, k)) % ring_size()
+}
+
+/// Use an `existing_node` to initialize this `ChordNode`'s fields. The ring has `config.ring_bits` bits (see `set_ring_bits`), which the handshake with `existing_node` checks.
+pub async fn join(
+    self_ip: IpAddr,
+    existing_node: IpAddr,
+    state: PersistedState,
+    config: Config,
+    storage: Storage,
+    transport: Arc<dyn Transport>,
+) -> Result<ChordNode, ChordError> {
+    set_ring_bits(config.ring_bits);
+    debug!("Initializing my finger tables...");
+    let node = init_finger_table(self_ip, existing_node, state, config, storage, transport).await?;
+    debug!("Done.");
+    if node.incarnation > 1 {
+        info!(
+            "Rejoining as incarnation {}, reconciling keys with my successor...",
+            node.incarnation
+        );
+        node.rejoin().await?;
+    } else {
+        debug!("Skipping moving keys...");
+        move_keys().await?;
+    }
+    Ok(node)
+}
 
-    Ok(ChordNode::new(
+/// Create a blank finger table (where all entries point to `self_ip`) and return it. Only the first entry is initialized properly, by asking `existing_node` for its successor.
+async fn init_finger_table(
+    self_ip: IpAddr,
+    existing_node: IpAddr,
+    state: PersistedState,
+    config: Config,
+    storage: Storage,
+    transport: Arc<dyn Transport>,
+) -> Result<ChordNode, ChordError> {
+    let finger_table = blank_finger_table(self_ip);
+    let predecessor = self_ip;
+    debug!("Setting my predecessor as me. This will be fixed later by notify()");
+    let node = ChordNode::new(
         finger_table,
-        HashSet::new(),
+        state,
         self_ip,
         predecessor,
-    ))
-}
-
-async fn move_keys() -> Result<(), HandlerError> {
-    Ok(())
+        config,
+        storage,
+        transport,
+    );
+    handshake(&node, existing_node).await?;
+
+    let succ_ip = node.transport.get_successor(&node, existing_node).await?;
+    debug!(peer = %succ_ip, "Found my successor");
+    node.update_successor(succ_ip);
+    Ok(node)
 }
 
-/// Send a GET request and call `handle_failure` on request timeout/error.
-async fn get_req(ip: IpAddr, path: &str, chord_node: &ChordNode) -> Result<String, HandlerError> {
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(format!("http://{}:{}/{}", ip, PORT, path))
-        .timeout(Duration::from_secs(REQ_TIMEOUT))
-        .send()
-        .await;
-    let resp = match resp {
-        Ok(resp) => resp,
-        Err(e) => {
-            chord_node.handle_failure().await;
-            return Err(e.into());
+/// Makes sure that `seed` runs with the same ring parameters as `node` (see `Hello::check_compatible`). Seeds that predate the handshake can't be checked, so they are trusted.
+async fn handshake(node: &ChordNode, seed: IpAddr) -> Result<(), ChordError> {
+    match node.transport.hello(node, seed).await? {
+        Some(hello) => node.hello().check_compatible(&hello),
+        None => {
+            warn!(
+                %seed,
+                "The seed predates the handshake, can't check that it uses the same ring parameters"
+            );
+            Ok(())
         }
-    };
-    let text = request_unsuccessful(resp, "GET").await?;
-    return Ok(text);
+    }
 }
 
-/// create a request with a payload (POST, PATCH or DELETE) and send it to `ip`. Call `handle_failure` on request failure/timeout.
-async fn data_req<T, U>(
-    ip: IpAddr,
-    path: &str,
-    data: Vec<(T, U)>,
-    chord_node: &ChordNode,
-    req_type: &str,
-) -> Result<String, HandlerError>
-where
-    T: Serialize + Sized,
-    U: Serialize + Sized,
-{
-    let client = reqwest::Client::new();
-    let response = match req_type {
-        "PATCH" => {
-            client
-                .patch(format!("http://{}:{}/{}", ip, PORT, path))
-                .timeout(Duration::from_secs(REQ_TIMEOUT))
-                .form(&data)
-                .send()
-                .await
-        }
-        "POST" => {
-            client
-                .post(format!("http://{}:{}/{}", ip, PORT, path))
-                .timeout(Duration::from_secs(REQ_TIMEOUT))
-                .form(&data)
-                .send()
-                .await
-        }
-        "DELETE" => {
-            client
-                .delete(format!("http://{}:{}/{}", ip, PORT, path))
-                .timeout(Duration::from_secs(REQ_TIMEOUT))
-                .form(&data)
-                .send()
-                .await
-        }
-        _ => {
-            panic!("That's not a valid request type")
-        }
-    };
-    let response = match response {
-        Ok(resp) => resp,
-        Err(e) => {
-            chord_node.handle_failure().await;
-            return Err(e.into());
-        }
-    };
-
-    let text = request_unsuccessful(response, req_type).await?;
-    Ok(text)
+async fn move_keys() -> Result<(), ChordError> {
+    Ok(())
 }
 
-/// Mark a request as failed if the server response is not 200.
-async fn request_unsuccessful(response: Response, req_type: &str) -> Result<String, HandlerError> {
-    let status = response.status();
-    if status != 200 {
-        let error = SimpleError::new(format!(
-            "Received error from {} req: {}",
-            req_type,
-            response.text().await?
-        ));
-        let handler_error = HandlerError::from(error).with_status(status);
-        return Err(handler_error);
-    }
-    Ok(response.text().await?)
+/// Spawns every maintenance task of `chord_node` on `supervisor`, each one running as often as its configuration says (see `Config::intervals`). A round that fails repairs this node's pointers (see `ChordNode::maintain`) and is retried with backoff by the supervisor.
+pub fn start_maintenance(chord_node: &ChordNode, supervisor: &Supervisor) {
+    let intervals = &chord_node.config.intervals;
+    let node = chord_node.clone();
+    supervisor.spawn(
+        "stabilize",
+        Duration::from_secs(intervals.stabilize),
+        move || {
+            let node = node.clone();
+            async move { node.maintain("stabilize", node.stabilize()).await }
+        },
+    );
+    let node = chord_node.clone();
+    supervisor.spawn(
+        "fix_fingers",
+        Duration::from_secs(intervals.fix_fingers),
+        move || {
+            let node = node.clone();
+            async move { node.maintain("fix_fingers", node.fix_fingers()).await }
+        },
+    );
+    let node = chord_node.clone();
+    supervisor.spawn(
+        "successor_list",
+        Duration::from_secs(intervals.successor_list),
+        move || {
+            let node = node.clone();
+            async move {
+                node.maintain("successor_list", node.build_successor_list())
+                    .await
+            }
+        },
+    );
+    let node = chord_node.clone();
+    supervisor.spawn(
+        "replica_sync",
+        Duration::from_secs(intervals.replica_sync),
+        move || {
+            let node = node.clone();
+            async move { node.maintain("replica_sync", node.sync_replicas()).await }
+        },
+    );
 }
 
-/// Mark a node as dead if it doesn't response within `LIVENESS_TIMEOUT`.
-async fn is_node_alive(ip: IpAddr, client: Option<&reqwest::Client>) -> bool {
-    let client = match client {
-        Some(client) => client.clone(),
-        None => reqwest::Client::new(),
-    };
-
-    let response = client
-        .get(format!("http://{}:{}/{}", ip, PORT, HTTP_SUCCESSOR))
-        .timeout(Duration::from_secs(LIVENESS_TIMEOUT))
-        .send()
-        .await;
-    response.is_ok()
+/// Hash a key and return hash(key)%2^m, where m is `ring_bits()`.
+pub fn get_identifier(key: &str) -> u64 {
+    identifier(key, ring_bits())
 }
 
-pub fn start_stabilize_thread(chord_node: ChordNode) {
-    thread::spawn(move || {
-        tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(err_stabilize(chord_node));
-    });
+/// Sets the number of bits of the IDs on the ring, which is `RING_BITS` until then. `create_ring` and `join` call it with `Config::ring_bits` before computing any ID, so every way of building a node hashes into the ring it was configured with. It's shared by the whole process: the nodes of a process are expected to be in the same ring, and so to have the same ring bits.
+pub fn set_ring_bits(bits: u32) {
+    assert!(
+        (1..=MAX_RING_BITS).contains(&bits),
+        "Invalid ring_bits {}",
+        bits
+    );
+    RING_BITS_IN_USE.store(bits, Ordering::Relaxed);
 }
 
-/// If an error originates anywhere within `stabilize()`, we assume that it'll be fixed soon by `handle_failure`. This method ignores that error and calls `stabilize()` again.
-async fn err_stabilize(chord_node: ChordNode) {
-    loop {
-        match chord_node.stabilize().await {
-            Ok(()) => {}
-            Err(_e) => {
-                println!("Warning: Retrying stabilize() to see if the issue was fixed automatically (there's a good chance it was). If you see this warning more than 5-6 times in a row, exit the program and debug - but if you see nothing after this, the issue was likely self-fixed.")
-            }
-        }
-    }
+/// returns the number of bits of the IDs on the ring (see `set_ring_bits`).
+pub fn ring_bits() -> u32 {
+    RING_BITS_IN_USE.load(Ordering::Relaxed)
 }
 
-/// Contact Google and return the IP address of this node.
-fn get_self_ip() -> IpAddr {
-    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
-    socket.connect("8.8.8.8:80").unwrap();
-    socket.local_addr().unwrap().ip()
+/// returns the number of "holes" in the Chord ring, 2^`ring_bits()`.
+fn ring_size() -> u64 {
+    1 << ring_bits()
 }
 
-/// Hash a key and return hash(key)%M.
-pub fn get_identifier(key: &str) -> u64 {
-    fn hasher(key: &str) -> u64 {
-        let mut s = DefaultHasher::new();
-        key.hash(&mut s);
-        s.finish()
+/// returns `id` if it is on the ring, and fails with `ChordError::BadRequest` if it's too large.
+pub fn check_id(id: u64) -> Result<u64, ChordError> {
+    if id >= ring_size() {
+        let error = format!("Invalid id {}, must be less than {}", id, ring_size());
+        return Err(ChordError::BadRequest(error));
     }
-    hasher(&key.to_string()) % M
+    Ok(id)
 }
@@ -0,0 +1,45 @@
+use crate::config::Logging;
+use crate::ChordError;
+use clap::ValueEnum;
+use serde_derive::{Deserialize, Serialize};
+use std::io::IsTerminal;
+use std::str::FromStr;
+use tracing_subscriber::EnvFilter;
+
+/// The events a node logs unless `Logging::filter` says otherwise.
+pub const LOG_FILTER: &str = "info";
+
+/// How log events are written: `Text` is one human readable line per event, with the fields of its spans in front of it. `Json` is one object per line, with the fields of the event at the top level and its spans under `span` (the innermost one) and `spans`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        <Self as ValueEnum>::from_str(value, true)
+    }
+}
+
+/// Sends the events of this process (and the `log` records of the libraries that still use it, like gotham) to stdout, filtered and formatted as `logging` says. This can only be done once per process.
+/// Every incoming request runs in a `request` span (see `RequestLog` in the binary, and `serve_grpc`), and every maintenance round in a `maintenance` span (see `ChordNode::maintain`), both carrying the ID of the node.
+pub fn init_logging(logging: &Logging) -> Result<(), ChordError> {
+    let filter = EnvFilter::try_new(&logging.filter).map_err(ChordError::bad_request)?;
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let result = match logging.format {
+        LogFormat::Text => builder
+            .with_ansi(std::io::stdout().is_terminal())
+            .try_init(),
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    };
+    result.map_err(|e| ChordError::Internal(format!("Can't set up logging: {}", e)))
+}
@@ -1,126 +1,223 @@
+use clap::Parser;
+use crust::SUSPECT_HEADER;
+use crust::{check_id, Audience, Maintenance, StateDiff};
+use crust::{init_logging, start_gossip, ChordNode, Gossip, GossipConfig, Options, Supervisor};
+use crust::{initialize_node, serve_grpc, start_maintenance, supported_transport_names, Cli};
+use crust::{serve_https, ChordError, Command, Readiness, Security, PEER_PORT, RETRY_AFTER};
 use gotham::handler::HandlerError;
 use gotham::helpers::http::response::create_response;
+use gotham::hyper::header::{self, HeaderValue};
 use gotham::hyper::{body, Body, Response, StatusCode};
 use gotham::middleware::state::StateMiddleware;
+use gotham::pipeline::new_pipeline;
+use gotham::pipeline::set::{finalize_pipeline_set, new_pipeline_set};
 use gotham::pipeline::single::single_pipeline;
-use gotham::pipeline::single_middleware;
 use gotham::router::builder::*;
 use gotham::router::Router;
 use gotham::state::{FromState, State};
 use mime::TEXT_PLAIN;
-use simple_error::SimpleError;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
+use tracing::{debug, error, info, warn};
 use url::form_urlencoded;
 
+mod client;
 mod extractor;
 use extractor::PathExtractor;
-mod lib;
-use lib::initialize_node;
-use lib::start_stabilize_thread;
-use lib::ChordNode;
+mod middleware;
+use middleware::{PeerAuth, RateLimit, RequestLog, TokenAuth};
 
-const PORT: usize = 8000;
+// every client IP address can send this many requests a second to the client API on average, in bursts of up to CLIENT_BURST.
+const CLIENT_RATE: u32 = 20;
+const CLIENT_BURST: u32 = 40;
 
 fn empty_response(state: &State) -> Result<Response<Body>, HandlerError> {
     Ok(create_response(
-        &state,
+        state,
         StatusCode::OK,
         TEXT_PLAIN,
         "".to_string(),
     ))
 }
 
-async fn extract_val_from_req(state: &mut State, key: String) -> Result<String, HandlerError> {
-    let full_body = body::to_bytes(Body::take_from(state)).await?;
-    let data = form_urlencoded::parse(&full_body).into_owned();
-    for (k, v) in data {
+/// Turns a `ChordError` into a response with its status and a JSON `ErrorBody`. When the error is about a node that didn't answer, the response also tells the client which node is suspected to be down (`SUSPECT_HEADER`) and when to retry (`Retry-After`).
+fn error_response(state: &State, error: ChordError) -> Response<Body> {
+    warn!(code = error.code(), "Request failed: {}", error);
+    let mut resp = create_response(
+        state,
+        error.status(),
+        mime::APPLICATION_JSON,
+        error.to_json(),
+    );
+    if let Some(suspect) = error.suspect() {
+        let headers = resp.headers_mut();
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER));
+        if let Ok(value) = HeaderValue::from_str(&suspect.to_string()) {
+            headers.insert(SUSPECT_HEADER, value);
+        }
+    }
+    resp
+}
+
+/// Unwraps a `Result<_, ChordError>` in a handler, or returns early with the error as a response (see `error_response`).
+macro_rules! try_or_respond {
+    ($state:expr, $result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(e) => return Ok(error_response($state, e)),
+        }
+    };
+}
+
+/// returns the form fields in the body of the request.
+async fn read_form(state: &mut State) -> Result<Vec<(String, String)>, ChordError> {
+    let full_body = body::to_bytes(Body::take_from(state))
+        .await
+        .map_err(ChordError::bad_request)?;
+    Ok(form_urlencoded::parse(&full_body).into_owned().collect())
+}
+
+async fn extract_val_from_req(state: &mut State, key: &str) -> Result<String, ChordError> {
+    for (k, v) in read_form(state).await? {
         if k == key {
             return Ok(v);
         }
     }
+    Err(ChordError::BadRequest(format!("Missing key {}.", key)))
+}
 
-    let error = SimpleError::new(format!("Invalid key {}, expected key: ip.", key));
-    let handler_error = HandlerError::from(error).with_status(StatusCode::BAD_REQUEST);
-    Err(handler_error)
+/// parses a value supplied by the client, failing with `ChordError::BadRequest`.
+fn parse<T>(value: &str) -> Result<T, ChordError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| ChordError::BadRequest(format!("Invalid value {}: {}", value, e)))
 }
 
-/// returns the immediate successor of this node (GET /successor/)
+/// returns the immediate successor of this node (GET /peer/successor/)
 fn get_successor(state: State) -> (State, String) {
     let node = ChordNode::borrow_from(&state);
     let successor = node.get_successor();
     (state, successor.to_string())
 }
 
-/// Update a node's successor to a new node (PATCH /successor/)
+/// Update a node's successor to a new node (PATCH /peer/successor/)
 async fn update_successor(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let ip = extract_val_from_req(state, "ip".to_string()).await?;
+    let ip = try_or_respond!(state, extract_val_from_req(state, "ip").await);
+    let ip = try_or_respond!(state, parse(&ip));
     let node = state.borrow_mut::<ChordNode>();
-    println!("Will update my successor to {}", ip);
-    node.update_successor(ip.parse()?);
-    empty_response(&state)
+    debug!(peer = %ip, "Will update my successor");
+    node.update_successor(ip);
+    empty_response(state)
 }
 
-/// returns the immediate successor of this node (GET /predecessor/)
+/// returns the immediate successor of this node (GET /peer/predecessor/)
 fn get_predecessor(state: State) -> (State, String) {
     let node = ChordNode::borrow_from(&state);
     let predecessor = node.get_predecessor();
     (state, predecessor.to_string())
 }
 
-/// update a node's predecessor pointer (PATCH /predecessor/)
+/// update a node's predecessor pointer (PATCH /peer/predecessor/)
 async fn update_predecessor(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let ip = extract_val_from_req(state, "ip".to_string()).await?;
+    let ip = try_or_respond!(state, extract_val_from_req(state, "ip").await);
+    let ip = try_or_respond!(state, parse(&ip));
     let node = state.borrow::<ChordNode>();
-    println!("Will update my predecessor to {}", ip);
-    node.update_predecessor(ip.parse()?);
-    empty_response(&state)
+    debug!(peer = %ip, "Will update my predecessor");
+    node.update_predecessor(ip);
+    empty_response(state)
 }
 
-/// calculates the successor(key) and returns the IP address and ID of the node.
+/// calculates the successor(key) and returns the IP address and ID of the node (GET /v1/lookup/:key)
 async fn calculate_successor(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let node = ChordNode::borrow_from(&state);
-    let id = &PathExtractor::borrow_from(&state).key;
-    println!("calculate_successor: calculating successor for: {}", id);
-    let res = node.calculate_successor(id).await?;
+    let node = ChordNode::borrow_from(state);
+    let id = &PathExtractor::borrow_from(state).key;
+    debug!(id = %id, "Calculating the successor");
+    let res = try_or_respond!(state, node.calculate_successor(id).await);
     Ok(create_response(
-        &state,
+        state,
         StatusCode::OK,
         TEXT_PLAIN,
         res.to_string(),
     ))
 }
 
-/// Find the closest predecessing finger for a given id (GET /successor/cfp/:id)
-fn closest_preceding_finger(state: State) -> (State, String) {
-    let node = ChordNode::borrow_from(&state);
-    let id = &PathExtractor::borrow_from(&state).key;
-    let res = node.closest_preceding_finger(id);
-    (state, res.to_string())
+/// Find the closest predecessing finger for a given id (GET /peer/successor/cpf/:id). Fails with `ChordError::BadRequest` if the id isn't on the ring.
+async fn closest_preceding_finger(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    let id = try_or_respond!(state, parse(&PathExtractor::borrow_from(state).key));
+    let id = try_or_respond!(state, check_id(id));
+    let res = ChordNode::borrow_from(state).closest_preceding_finger(id);
+    Ok(create_response(
+        state,
+        StatusCode::OK,
+        TEXT_PLAIN,
+        res.to_string(),
+    ))
 }
 
-/// return all information about this node (GET /info/)
+/// return all information about this node (GET /v1/info/)
 async fn info(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let node = ChordNode::borrow_from(&state);
-    let resp = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, node.info());
+    let node = ChordNode::borrow_from(state);
+    let resp = create_response(state, StatusCode::OK, mime::APPLICATION_JSON, node.info());
     Ok(resp)
 }
 
-/// return a JSON representing the structure of the Chord ring (GET /ring/)
+/// return a JSON representing the structure of the Chord ring (GET /v1/ring/)
 async fn get_ring(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let node = ChordNode::borrow_from(&state);
-    let ring = node.ring_info().await?;
+    let node = ChordNode::borrow_from(state);
+    let ring = try_or_respond!(state, node.ring_info().await);
     Ok(create_response(
-        &state,
+        state,
         StatusCode::OK,
         mime::APPLICATION_JSON,
         ring,
     ))
 }
 
-/// update the finger tables of a node (PATCH /fingertable/)
+/// returns how the ring and its load are spread over the nodes, with a summary of the imbalance (GET /v1/ring/stats/), see `ChordNode::ring_stats`.
+async fn ring_stats(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    let node = ChordNode::borrow_from(state);
+    let stats = try_or_respond!(state, node.ring_stats().await);
+    Ok(create_response(
+        state,
+        StatusCode::OK,
+        mime::APPLICATION_JSON,
+        serde_json::to_string_pretty(&stats)?,
+    ))
+}
+
+/// checks that the pointers, fingers and keys of every node of the ring are where they should be, and returns what isn't (GET /v1/ring/verify/), see `ChordNode::verify_ring`.
+async fn verify_ring(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    let check = ChordNode::borrow_from(state).verify_ring().await;
+    Ok(create_response(
+        state,
+        StatusCode::OK,
+        mime::APPLICATION_JSON,
+        serde_json::to_string_pretty(&check)?,
+    ))
+}
+
+/// returns this node's pointers and keys to the node verifying the ring (GET /peer/view/)
+fn view(state: State) -> (State, Response<Body>) {
+    let view = ChordNode::borrow_from(&state).view();
+    let body = serde_json::to_string(&view).expect("Can't serialize the view");
+    let resp = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, body);
+    (state, resp)
+}
+
+/// returns what this node reports about its load to the node walking the ring for `/v1/ring/stats/` (GET /peer/load/)
+fn load(state: State) -> (State, Response<Body>) {
+    let load = ChordNode::borrow_from(&state).load();
+    let body = serde_json::to_string(&load).expect("Can't serialize the load");
+    let resp = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, body);
+    (state, resp)
+}
+
+/// update the finger tables of a node (PATCH /peer/fingertable/)
 async fn update_finger_table(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let full_body = body::to_bytes(Body::take_from(state)).await?;
-    let data = form_urlencoded::parse(&full_body).into_owned();
+    let data = try_or_respond!(state, read_form(state).await);
     let mut n = String::new();
     let mut i = String::new();
     for (key, value) in data {
@@ -129,104 +226,460 @@ async fn update_finger_table(state: &mut State) -> Result<Response<Body>, Handle
         } else if key == "i" {
             i = value;
         } else {
-            let error = SimpleError::new(format!("Invalid key {}, expected key: n or i.", key));
-            let handler_error = HandlerError::from(error).with_status(StatusCode::BAD_REQUEST);
-            return Err(handler_error);
+            let error = format!("Invalid key {}, expected key: n or i.", key);
+            return Ok(error_response(state, ChordError::BadRequest(error)));
         }
     }
-    let s: IpAddr = n.parse()?;
-    let i: u64 = i.parse()?;
+    let s: IpAddr = try_or_respond!(state, parse(&n));
+    let i: u64 = try_or_respond!(state, parse(&i));
     let node = state.borrow_mut::<ChordNode>();
-    node.update_finger_table(s, i).await?;
-    empty_response(&state)
+    try_or_respond!(state, node.update_finger_table(s, i).await);
+    empty_response(state)
 }
 
-/// Notify a node that there might be a better predecessor (PATCH /notify/)
+/// Notify a node that there might be a better predecessor (PATCH /peer/notify/)
 async fn notify(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let n = extract_val_from_req(state, "n".to_string()).await?;
+    let n = try_or_respond!(state, extract_val_from_req(state, "n").await);
+    let n = try_or_respond!(state, parse(&n));
     let node = state.borrow::<ChordNode>();
-    node.notify(n.parse()?).await;
-    empty_response(&state)
+    node.notify(n).await;
+    empty_response(state)
 }
 
-/// add a new key to the DST (supplied as POST to /key/)
+/// add a new key to the DST (supplied as POST to /v1/key/, or /peer/key/ by a peer)
 async fn insert(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let key = extract_val_from_req(state, "key".to_string()).await?;
+    let key = try_or_respond!(state, extract_val_from_req(state, "key").await);
     let node = state.borrow::<ChordNode>();
-    let inserted_at_id = node.insert(key).await?;
+    let inserted_at_id = try_or_respond!(state, node.insert(key).await);
     Ok(create_response(
-        &state,
+        state,
         StatusCode::OK,
         TEXT_PLAIN,
         inserted_at_id,
     ))
 }
 
-/// Adds a key to a node's replica_state field. (POST /replica/)
+/// Adds one or more keys (each supplied as a `key` field) to a node's replica_state field. (POST /peer/replica/)
 async fn insert_replica(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let key = extract_val_from_req(state, "key".to_string()).await?;
+    let keys = try_or_respond!(state, read_keys(state).await);
     let node = state.borrow::<ChordNode>();
-    node.insert_replica(key);
-    empty_response(&state)
+    node.insert_replica(keys);
+    empty_response(state)
+}
+
+/// Removes one or more keys (each supplied as a `key` field) from a node's replica_state field. (DELETE /peer/replica/)
+async fn delete_replica(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    let keys = try_or_respond!(state, read_keys(state).await);
+    let node = state.borrow::<ChordNode>();
+    node.delete_replica(keys);
+    empty_response(state)
+}
+
+/// reads the `key` fields of a form.
+async fn read_keys(state: &mut State) -> Result<Vec<String>, ChordError> {
+    let mut keys = Vec::new();
+    for (k, v) in read_form(state).await? {
+        if k != "key" {
+            let error = format!("Invalid key {}, expected key: key.", k);
+            return Err(ChordError::BadRequest(error));
+        }
+        keys.push(v);
+    }
+    Ok(keys)
 }
 
-/// returns the value corresponsing to the key in (GET /key/:key)
+/// A node restarted after a crash and is telling its successor that it's back (POST /peer/rejoin/). Returns a JSON list of the keys that were taken over while it was down.
+async fn rejoin(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    let data = try_or_respond!(state, read_form(state).await);
+    let mut n = String::new();
+    let mut incarnation = String::new();
+    for (key, value) in data {
+        if key == "n" {
+            n = value;
+        } else if key == "incarnation" {
+            incarnation = value;
+        } else {
+            let error = format!("Invalid key {}, expected key: n or incarnation.", key);
+            return Ok(error_response(state, ChordError::BadRequest(error)));
+        }
+    }
+    let n: IpAddr = try_or_respond!(state, parse(&n));
+    let incarnation: u64 = try_or_respond!(state, parse(&incarnation));
+    let node = state.borrow::<ChordNode>();
+    let keys = try_or_respond!(state, node.handle_rejoin(n, incarnation));
+    Ok(create_response(
+        state,
+        StatusCode::OK,
+        mime::APPLICATION_JSON,
+        serde_json::to_string(&keys)?,
+    ))
+}
+
+/// returns the value corresponsing to the key in (GET /v1/key/:key, or /peer/key/:key for a peer)
 async fn contains(state: &mut State) -> Result<Response<Body>, HandlerError> {
-    let node = ChordNode::borrow_from(&state);
-    let key = &PathExtractor::borrow_from(&state).key;
-    let contains = node.contains(key).await?;
+    let node = ChordNode::borrow_from(state);
+    let key = &PathExtractor::borrow_from(state).key;
+    let contains = try_or_respond!(state, node.contains(key).await);
     Ok(create_response(
-        &state,
+        state,
         StatusCode::OK,
         TEXT_PLAIN,
         contains.to_string(),
     ))
 }
 
-fn router(chord: ChordNode) -> Router {
-    let middleware = StateMiddleware::new(chord);
-    let pipeline = single_middleware(middleware);
-    let (chain, pipelines) = single_pipeline(pipeline);
+/// deletes a key, and returns whether it was there (DELETE /v1/key/:key, or /peer/key/:key for a peer)
+async fn delete(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    let node = ChordNode::borrow_from(state);
+    let key = &PathExtractor::borrow_from(state).key;
+    let deleted = try_or_respond!(state, node.delete(key).await);
+    Ok(create_response(
+        state,
+        StatusCode::OK,
+        TEXT_PLAIN,
+        deleted.to_string(),
+    ))
+}
 
-    build_router(chain, pipelines, |route| {
-        route.get("/ring").to_async_borrowing(get_ring);
+/// returns the status of every maintenance task of this node (GET /v1/tasks/)
+fn tasks(state: State) -> (State, Response<Body>) {
+    let supervisor = Supervisor::borrow_from(&state);
+    let resp = create_response(
+        &state,
+        StatusCode::OK,
+        mime::APPLICATION_JSON,
+        supervisor.report(),
+    );
+    (state, resp)
+}
+
+/// runs `task` on this node right away, and returns how it changed the node as a JSON `StateDiff`.
+async fn run_now(state: &mut State, task: Maintenance) -> Result<Response<Body>, HandlerError> {
+    let diff = ChordNode::borrow_from(state).run_now(task).await;
+    Ok(diff_response(state, &diff))
+}
+
+fn diff_response(state: &State, diff: &StateDiff) -> Response<Body> {
+    let body = serde_json::to_string_pretty(diff).expect("Can't serialize the diff");
+    create_response(state, StatusCode::OK, mime::APPLICATION_JSON, body)
+}
+
+/// runs a stabilize round now (POST /v1/admin/stabilize/)
+async fn admin_stabilize(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    run_now(state, Maintenance::Stabilize).await
+}
+
+/// refreshes every finger now (POST /v1/admin/fingers/)
+async fn admin_fix_fingers(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    run_now(state, Maintenance::Fingers).await
+}
+
+/// refreshes one finger now (POST /v1/admin/fingers/:index)
+async fn admin_fix_finger(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    let index = try_or_respond!(state, parse(&PathExtractor::borrow_from(state).key));
+    let task = try_or_respond!(state, Maintenance::finger(index));
+    run_now(state, task).await
+}
+
+/// rebuilds the successor list now (POST /v1/admin/successor_list/)
+async fn admin_successor_list(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    run_now(state, Maintenance::SuccessorList).await
+}
+
+/// runs a replica sync round now (POST /v1/admin/replica_sync/)
+async fn admin_replica_sync(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    run_now(state, Maintenance::ReplicaSync).await
+}
+
+/// stops the maintenance tasks of this node from running on their own (POST /v1/admin/pause/), see `Supervisor::pause`. Tasks can still be run with the other admin endpoints.
+fn admin_pause(state: State) -> (State, Response<Body>) {
+    let resp = set_paused(&state, true);
+    (state, resp)
+}
+
+/// lets the maintenance tasks of this node run on their own again (POST /v1/admin/resume/)
+fn admin_resume(state: State) -> (State, Response<Body>) {
+    let resp = set_paused(&state, false);
+    (state, resp)
+}
+
+fn set_paused(state: &State, paused: bool) -> Response<Body> {
+    let supervisor = Supervisor::borrow_from(state);
+    let before = serde_json::json!({ "maintenance_paused": supervisor.is_paused() });
+    if paused {
+        supervisor.pause();
+    } else {
+        supervisor.resume();
+    }
+    let after = serde_json::json!({ "maintenance_paused": supervisor.is_paused() });
+    let action = if paused { "pause" } else { "resume" };
+    let diff = StateDiff::new(action.to_string(), &before, &after, Ok(()));
+    diff_response(state, &diff)
+}
+
+/// answers as long as the process is up, even while the node is still joining the ring (GET /healthz).
+fn healthz(state: State) -> (State, Response<Body>) {
+    let body = serde_json::json!({"alive": true}).to_string();
+    let resp = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, body);
+    (state, resp)
+}
+
+/// returns whether this node is ready to serve its keys, and why not if it isn't, with `503 Service Unavailable` then (GET /readyz). See `ChordNode::readiness`.
+async fn readyz(state: &mut State) -> Result<Response<Body>, HandlerError> {
+    let readiness = ChordNode::borrow_from(state).readiness().await;
+    Ok(readiness_response(state, &readiness))
+}
+
+/// GET /readyz while the node is still joining the ring.
+fn readyz_joining(state: State) -> (State, Response<Body>) {
+    let resp = readiness_response(&state, &Readiness::joining());
+    (state, resp)
+}
+
+fn readiness_response(state: &State, readiness: &Readiness) -> Response<Body> {
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = serde_json::to_string(readiness).expect("Can't serialize the readiness");
+    create_response(state, status, mime::APPLICATION_JSON, body)
+}
+
+/// returns the metrics of this node in the Prometheus text format (GET /metrics), see `ChordNode::render_metrics`.
+fn metrics(state: State) -> (State, Response<Body>) {
+    let text = ChordNode::borrow_from(&state).render_metrics();
+    let resp = create_response(&state, StatusCode::OK, TEXT_PLAIN, text);
+    (state, resp)
+}
+
+/// returns the protocol version, ring parameters, crust version and peer transports of this node (GET /hello/ and GET /peer/hello/). Joining nodes use this to check that they can join the ring through this node, and to pick a transport.
+fn hello(state: State) -> (State, Response<Body>) {
+    let chord = ChordNode::borrow_from(&state);
+    let hello = serde_json::to_string(&chord.hello()).expect("Can't serialize hello");
+    let resp = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, hello);
+    (state, resp)
+}
+
+/// returns the peer transports this node supports, most preferred first (GET /transports/). Nodes that predate `/hello/` use this to pick one.
+fn transports(state: State) -> (State, Response<Body>) {
+    let names =
+        serde_json::to_string(&supported_transport_names()).expect("Can't serialize transports");
+    let resp = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, names);
+    (state, resp)
+}
+
+/// The client API (on `PORT`, unless `Config::port` says otherwise): the browser UI, the `/v1/` API, and what joining nodes ask for before they can talk to the peer API. Every client is rate limited, the `/v1/key` API requires the client token, if there is one, and the `/v1/admin` API requires the admin token.
+fn client_router(chord: ChordNode, supervisor: Supervisor, security: Security) -> Router {
+    let pipelines = new_pipeline_set();
+    let (pipelines, default) = pipelines.add(
+        new_pipeline()
+            .add(RequestLog::every_request("client", &chord))
+            .add(RateLimit::new(CLIENT_RATE, CLIENT_BURST))
+            .add(StateMiddleware::new(chord))
+            .add(StateMiddleware::new(supervisor))
+            .add(StateMiddleware::new(security))
+            .build(),
+    );
+    let (pipelines, authorized) =
+        pipelines.add(new_pipeline().add(TokenAuth(Audience::Client)).build());
+    let (pipelines, admin) = pipelines.add(new_pipeline().add(TokenAuth(Audience::Admin)).build());
+    let pipelines = finalize_pipeline_set(pipelines);
+    let default_chain = (default, ());
+    let authorized_chain = (authorized, default_chain);
+    let admin_chain = (admin, default_chain);
+
+    build_router(default_chain, pipelines, |route| {
         route.get("/").to_file("assets/index.html");
-        route.scope("/successor", |route| {
-            route.get("/").to(get_successor);
-            route.patch("/").to_async_borrowing(update_successor);
+        route.get("/hello").to(hello);
+        route.get("/transports").to(transports);
+        route.get("/metrics").to(metrics);
+        route.get("/healthz").to(healthz);
+        route.get("/readyz").to_async_borrowing(readyz);
+        route.scope("/v1", |route| {
+            route.get("/ring").to_async_borrowing(get_ring);
+            route.get("/ring/stats").to_async_borrowing(ring_stats);
+            route.get("/ring/verify").to_async_borrowing(verify_ring);
+            route.get("/info").to_async_borrowing(info);
+            route.get("/tasks").to(tasks);
             route
-                .get("/:key")
+                .get("/lookup/:key")
                 .with_path_extractor::<PathExtractor>()
                 .to_async_borrowing(calculate_successor);
-            route
-                .get("/cpf/:key")
-                .with_path_extractor::<PathExtractor>()
-                .to(closest_preceding_finger);
-        });
-        route.scope("/predecessor", |route| {
-            route.get("/").to(get_predecessor);
-            route.patch("/").to_async_borrowing(update_predecessor);
+            route.with_pipeline_chain(authorized_chain, |route| {
+                route.scope("/key", |route| {
+                    route.post("/").to_async_borrowing(insert);
+                    route
+                        .get("/:key")
+                        .with_path_extractor::<PathExtractor>()
+                        .to_async_borrowing(contains);
+                    route
+                        .delete("/:key")
+                        .with_path_extractor::<PathExtractor>()
+                        .to_async_borrowing(delete);
+                });
+            });
+            route.with_pipeline_chain(admin_chain, |route| {
+                route.scope("/admin", |route| {
+                    route.post("/stabilize").to_async_borrowing(admin_stabilize);
+                    route.post("/fingers").to_async_borrowing(admin_fix_fingers);
+                    route
+                        .post("/fingers/:key")
+                        .with_path_extractor::<PathExtractor>()
+                        .to_async_borrowing(admin_fix_finger);
+                    route
+                        .post("/successor_list")
+                        .to_async_borrowing(admin_successor_list);
+                    route
+                        .post("/replica_sync")
+                        .to_async_borrowing(admin_replica_sync);
+                    route.post("/pause").to(admin_pause);
+                    route.post("/resume").to(admin_resume);
+                });
+            });
         });
-        route.get("/info").to_async_borrowing(info);
-        route
-            .patch("/fingertable")
-            .to_async_borrowing(update_finger_table);
-        route.patch("/notify").to_async_borrowing(notify);
-        route.scope("/key", |route| {
-            route.post("/").to_async_borrowing(insert);
+    })
+}
+
+/// What the client port serves while the node is joining the ring: only `/healthz` and `/readyz`, which says that the node is joining.
+fn joining_router() -> Router {
+    build_simple_router(|route| {
+        route.get("/healthz").to(healthz);
+        route.get("/readyz").to(readyz_joining);
+    })
+}
+
+/// The HTTP peer API on `PEER_PORT`, under `/peer/`, which only serves peers (see `PeerAuth`). Only its failed requests are logged, since maintenance sends a steady stream of them.
+fn peer_router(chord: ChordNode, security: Security) -> Router {
+    let pipeline = new_pipeline()
+        .add(RequestLog::failures("peer", &chord))
+        .add(StateMiddleware::new(chord))
+        .add(StateMiddleware::new(security))
+        .add(PeerAuth)
+        .build();
+    let (chain, pipelines) = single_pipeline(pipeline);
+
+    build_router(chain, pipelines, |route| {
+        route.scope("/peer", |route| {
+            route.get("/hello").to(hello);
+            route.scope("/successor", |route| {
+                route.get("/").to(get_successor);
+                route.patch("/").to_async_borrowing(update_successor);
+                route
+                    .get("/cpf/:key")
+                    .with_path_extractor::<PathExtractor>()
+                    .to_async_borrowing(closest_preceding_finger);
+            });
+            route.scope("/predecessor", |route| {
+                route.get("/").to(get_predecessor);
+                route.patch("/").to_async_borrowing(update_predecessor);
+            });
             route
-                .get("/:key")
-                .with_path_extractor::<PathExtractor>()
-                .to_async_borrowing(contains);
+                .patch("/fingertable")
+                .to_async_borrowing(update_finger_table);
+            route.patch("/notify").to_async_borrowing(notify);
+            route.scope("/key", |route| {
+                route.post("/").to_async_borrowing(insert);
+                route
+                    .get("/:key")
+                    .with_path_extractor::<PathExtractor>()
+                    .to_async_borrowing(contains);
+                route
+                    .delete("/:key")
+                    .with_path_extractor::<PathExtractor>()
+                    .to_async_borrowing(delete);
+            });
+            route.post("/replica").to_async_borrowing(insert_replica);
+            route.delete("/replica").to_async_borrowing(delete_replica);
+            route.post("/rejoin").to_async_borrowing(rejoin);
+            route.get("/load").to(load);
+            route.get("/view").to(view);
         });
-        route.post("/replica").to_async_borrowing(insert_replica);
     })
 }
 
-fn main() {
-    let chord = initialize_node();
-    start_stabilize_thread(chord.clone());
-    let addr = format!("0.0.0.0:{}", PORT);
-    println!("Listening for requests at http://{}", addr);
-    gotham::start(addr, router(chord));
+/// Serves `router` on `addr` until the server fails, over TLS if it is configured. `clients` says who the listener is for.
+async fn serve_http(clients: &str, addr: SocketAddr, router: Router, security: &Security) {
+    match &security.tls {
+        Some(tls) => {
+            info!("Listening for {} at https://{}", clients, addr);
+            if let Err(e) = serve_https(addr, router, tls).await {
+                error!("HTTPS server failed: {}", e);
+            }
+        }
+        None => {
+            info!("Listening for {} at http://{}", clients, addr);
+            let _ = gotham::init_server(addr, router).await;
+        }
+    }
+}
+
+/// Parses the command line (see `Cli`); invalid arguments print the usage and exit before anything starts. Every subcommand but `serve` is a client of a running node (see `client::run`).
+#[tokio::main]
+async fn main() {
+    match Cli::parse().command {
+        Command::Serve(options) => serve(options).await,
+        command => std::process::exit(client::run(command).await),
+    }
+}
+
+/// Runs a node. Everything (joining the ring, the maintenance and gossip tasks and the client and peer listeners) runs on this one runtime. While the node joins, the client port only serves health checks (see `joining_router`). On Ctrl-C, the listeners stop accepting requests and the maintenance tasks are cancelled.
+/// The node is configured by `Options::config`, from a configuration file, `CRUST_*` environment variables and flags; an invalid configuration exits before anything starts. Once it is read, everything is logged as its `log` table says (see `init_logging`). TLS, the cluster secret and the client and admin tokens are configured through the environment variables of `Security::from_env`.
+async fn serve(options: Options) {
+    let config = match options.config(|name| std::env::var(name).ok()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = init_logging(&config.log) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let security = match Security::from_env() {
+        Ok(security) => security,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let client_addr = SocketAddr::new(config.bind, config.port);
+    // until the node is in the ring, the client port only answers health checks. The listener is dropped once the node has joined, and the client API takes the port over.
+    let chord = tokio::select! {
+        chord = initialize_node(&config, &options.seeds, &security) => chord,
+        _ = serve_http("health checks", client_addr, joining_router(), &security) => {
+            error!("The client port stopped serving while joining the ring");
+            std::process::exit(1);
+        }
+    };
+    let gossip = match Gossip::bind(&chord, config.bind, &security, GossipConfig::default()).await {
+        Ok(gossip) => gossip,
+        Err(e) => {
+            error!("Can't bind the gossip socket: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let supervisor = Supervisor::new();
+    start_maintenance(&chord, &supervisor);
+    start_gossip(&gossip, &supervisor);
+    let peer_addr = SocketAddr::new(config.bind, PEER_PORT);
+    let clients = client_router(chord.clone(), supervisor.clone(), security.clone());
+    let peers = peer_router(chord.clone(), security.clone());
+    tokio::select! {
+        _ = serve_http("requests", client_addr, clients, &security) => {}
+        _ = serve_http("peers", peer_addr, peers, &security) => {}
+        result = serve_grpc(chord, config.bind, &security) => {
+            if let Err(e) = result {
+                error!("gRPC server failed: {}", e);
+            }
+        }
+        result = gossip.serve() => {
+            if let Err(e) = result {
+                error!("Gossip listener failed: {}", e);
+            }
+        }
+        _ = tokio::signal::ctrl_c() => info!("Received Ctrl-C, shutting down..."),
+    }
+    supervisor.shutdown().await;
 }
@@ -0,0 +1,284 @@
+use crate::FailureDetector;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const RETRANSMIT_MULTIPLIER: u32 = 3; // an update is piggybacked on this many times log2(members) messages before it's dropped, which is enough for it to reach every member with high probability.
+
+/// What the gossip protocol thinks of a member.
+/// Alive - it answered a probe, or nothing says otherwise.
+/// Suspect - it didn't answer a probe, not even through other members. It still counts as alive, and has until the suspicion times out to refute it.
+/// Dead - it was suspected for too long. It's back only once it gossips again with a higher incarnation, and forgotten if it doesn't for a while (see `Membership::prune_dead`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemberStatus {
+    Alive,
+    Suspect,
+    Dead,
+}
+
+/// A piece of gossip: `node` was `status` at `incarnation`. Updates are piggybacked on the messages of the gossip protocol (see `Gossip`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Update {
+    pub node: IpAddr,
+    pub status: MemberStatus,
+    pub incarnation: u64,
+}
+
+struct Member {
+    status: MemberStatus,
+    incarnation: u64,
+    since: Instant,
+}
+
+/// A member of the view, as shown in `/info`.
+#[derive(Serialize)]
+pub struct MemberReport {
+    node: IpAddr,
+    status: MemberStatus,
+    incarnation: u64,
+    secs_in_status: u64,
+}
+
+/// The members of the view, and the updates that are still to be piggybacked, with the number of messages each one still goes on.
+struct View {
+    incarnation: u64,
+    members: HashMap<IpAddr, Member>,
+    pending: Vec<(Update, u32)>,
+}
+
+/// This node's view of the ring, kept up to date by the gossip protocol. Every member has a `MemberStatus` and an incarnation, and an update only replaces what is known about a member if it is newer (see `overrides`).
+/// Members that are declared dead are reported to the `FailureDetector`, and cleared from it when they come back or are forgotten, so Chord maintenance repairs its pointers without waiting for a request to fail.
+/// This node's own incarnation starts with the Chord incarnation in its high bits, so that a restarted node outranks anything said about its previous run. Refuting a suspicion bumps the low bits.
+/// Like `ChordNode`, this is cloned a lot, so the view is wrapped in an `Arc`.
+#[derive(Clone)]
+pub struct Membership {
+    self_ip: IpAddr,
+    view: Arc<Mutex<View>>,
+    failures: FailureDetector,
+}
+
+impl Membership {
+    pub fn new(self_ip: IpAddr, incarnation: u64, failures: FailureDetector) -> Self {
+        Membership {
+            self_ip,
+            view: Arc::new(Mutex::new(View {
+                incarnation: incarnation << 32,
+                members: HashMap::new(),
+                pending: Vec::new(),
+            })),
+            failures,
+        }
+    }
+
+    /// the incarnation this node gossips about itself.
+    pub fn incarnation(&self) -> u64 {
+        self.view.lock().unwrap().incarnation
+    }
+
+    /// returns what the view says about `node`, `None` if it doesn't know it.
+    pub fn status(&self, node: IpAddr) -> Option<MemberStatus> {
+        let view = self.view.lock().unwrap();
+        view.members.get(&node).map(|member| member.status)
+    }
+
+    /// Adds `node` to the view as alive, if it isn't known yet.
+    pub fn learn(&self, node: IpAddr) {
+        if node != self.self_ip && self.status(node).is_none() {
+            self.apply(Update {
+                node,
+                status: MemberStatus::Alive,
+                incarnation: 0,
+            });
+        }
+    }
+
+    /// Marks `node` as suspect at its current incarnation, after it failed a probe. returns true if it wasn't suspected already.
+    pub fn suspect(&self, node: IpAddr) -> bool {
+        let incarnation = match self.view.lock().unwrap().members.get(&node) {
+            Some(member) if member.status == MemberStatus::Alive => member.incarnation,
+            _ => return false,
+        };
+        self.apply(Update {
+            node,
+            status: MemberStatus::Suspect,
+            incarnation,
+        })
+    }
+
+    /// Merges `update` into the view and queues it to be gossiped further, if it's news. An update saying that this node is suspect or dead is refuted instead, by gossiping that it's alive with a higher incarnation.
+    /// returns true if the view changed.
+    pub fn apply(&self, update: Update) -> bool {
+        let mut view = self.view.lock().unwrap();
+        if update.node == self.self_ip {
+            if update.status != MemberStatus::Alive && update.incarnation >= view.incarnation {
+                view.incarnation = update.incarnation + 1;
+                let refutation = Update {
+                    node: self.self_ip,
+                    status: MemberStatus::Alive,
+                    incarnation: view.incarnation,
+                };
+                enqueue(&mut view, refutation);
+            }
+            return false;
+        }
+        let previous = view.members.get(&update.node).map(|member| member.status);
+        if !overrides(&update, view.members.get(&update.node)) {
+            return false;
+        }
+        view.members.insert(
+            update.node,
+            Member {
+                status: update.status,
+                incarnation: update.incarnation,
+                since: Instant::now(),
+            },
+        );
+        enqueue(&mut view, update);
+        drop(view);
+        match (previous, update.status) {
+            (Some(MemberStatus::Dead), MemberStatus::Dead) => {}
+            (_, MemberStatus::Dead) => {
+                warn!(peer = %update.node, "Gossip: the peer is dead");
+                self.failures.report(update.node);
+            }
+            (Some(MemberStatus::Dead), MemberStatus::Alive) => {
+                info!(peer = %update.node, "Gossip: the peer is back");
+                self.failures.clear(update.node);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Declares dead the members that have been suspect for longer than `timeout`. returns them.
+    pub fn expire_suspects(&self, timeout: Duration) -> Vec<IpAddr> {
+        let expired: Vec<Update> = {
+            let view = self.view.lock().unwrap();
+            view.members
+                .iter()
+                .filter(|(_, m)| m.status == MemberStatus::Suspect && m.since.elapsed() >= timeout)
+                .map(|(node, m)| Update {
+                    node: *node,
+                    status: MemberStatus::Dead,
+                    incarnation: m.incarnation,
+                })
+                .collect()
+        };
+        expired
+            .into_iter()
+            .filter(|update| self.apply(*update))
+            .map(|update| update.node)
+            .collect()
+    }
+
+    /// Forgets the members that have been dead for longer than `timeout`, and clears them from the failure detector: a node that left the ring for good would otherwise stay in the view and be suspected forever. returns them.
+    pub fn prune_dead(&self, timeout: Duration) -> Vec<IpAddr> {
+        let pruned = {
+            let mut view = self.view.lock().unwrap();
+            let pruned: Vec<IpAddr> = view
+                .members
+                .iter()
+                .filter(|(_, m)| m.status == MemberStatus::Dead && m.since.elapsed() >= timeout)
+                .map(|(node, _)| *node)
+                .collect();
+            for node in &pruned {
+                view.members.remove(node);
+            }
+            view.pending
+                .retain(|(update, _)| !pruned.contains(&update.node));
+            pruned
+        };
+        for node in &pruned {
+            info!(peer = %node, "Gossip: forgetting the dead peer");
+            self.failures.clear(*node);
+        }
+        pruned
+    }
+
+    /// returns the members that aren't dead, the ones worth probing.
+    pub fn live_members(&self) -> Vec<IpAddr> {
+        let view = self.view.lock().unwrap();
+        view.members
+            .iter()
+            .filter(|(_, member)| member.status != MemberStatus::Dead)
+            .map(|(node, _)| *node)
+            .collect()
+    }
+
+    /// What the view holds against `node`, if it is suspect or dead, so that it can be told and refute it.
+    pub fn rumor_about(&self, node: IpAddr) -> Option<Update> {
+        let view = self.view.lock().unwrap();
+        let member = view.members.get(&node)?;
+        if member.status == MemberStatus::Alive {
+            return None;
+        }
+        Some(Update {
+            node,
+            status: member.status,
+            incarnation: member.incarnation,
+        })
+    }
+
+    /// Takes up to `max` updates to piggyback on an outgoing message, the least gossiped first. Updates that were gossiped enough are dropped.
+    pub fn piggyback(&self, max: usize) -> Vec<Update> {
+        let mut view = self.view.lock().unwrap();
+        view.pending
+            .sort_by_key(|(_, remaining)| std::cmp::Reverse(*remaining));
+        let updates = view
+            .pending
+            .iter_mut()
+            .take(max)
+            .map(|(update, remaining)| {
+                *remaining -= 1;
+                *update
+            })
+            .collect();
+        view.pending.retain(|(_, remaining)| *remaining > 0);
+        updates
+    }
+
+    /// returns every member of the view, sorted by address.
+    pub fn snapshot(&self) -> Vec<MemberReport> {
+        let view = self.view.lock().unwrap();
+        let mut members: Vec<MemberReport> = view
+            .members
+            .iter()
+            .map(|(node, member)| MemberReport {
+                node: *node,
+                status: member.status,
+                incarnation: member.incarnation,
+                secs_in_status: member.since.elapsed().as_secs(),
+            })
+            .collect();
+        members.sort_by_key(|member| member.node);
+        members
+    }
+}
+
+/// returns true if `update` is newer than what is known about its node: any update beats an unknown node, `Alive` needs a higher incarnation, `Suspect` beats `Alive` at the same incarnation, and `Dead` beats everything but an `Alive` or `Suspect` with a higher incarnation.
+fn overrides(update: &Update, current: Option<&Member>) -> bool {
+    let current = match current {
+        Some(current) => current,
+        None => return true,
+    };
+    match (update.status, current.status) {
+        (MemberStatus::Alive, _) => update.incarnation > current.incarnation,
+        (MemberStatus::Suspect, MemberStatus::Alive) => update.incarnation >= current.incarnation,
+        (MemberStatus::Suspect, _) => update.incarnation > current.incarnation,
+        (MemberStatus::Dead, MemberStatus::Dead) => false,
+        (MemberStatus::Dead, _) => update.incarnation >= current.incarnation,
+    }
+}
+
+/// Queues `update` to be piggybacked, replacing older news about the same node.
+fn enqueue(view: &mut View, update: Update) {
+    let members = view.members.len() as f64 + 1.0;
+    let retransmits = RETRANSMIT_MULTIPLIER * (members.log2().ceil() as u32).max(1);
+    view.pending
+        .retain(|(pending, _)| pending.node != update.node);
+    view.pending.push((update, retransmits));
+}
@@ -0,0 +1,206 @@
+use crate::peer::{unreachable, Transport};
+use crate::{ChordError, ChordNode, Hello, Load, NodeView};
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// An in-memory `Transport` that delivers requests by calling the `ChordNode` methods of the receiving node directly, so that a whole ring can run inside a single test process.
+/// Delivery is controllable: a node can be crashed (it stops answering but keeps its state, so it can be brought back), the link between two nodes can be cut, and every request can be delayed. Undeliverable requests fail exactly like a timed out HTTP request: the receiver is reported to the sender's failure detector and a `ChordError::Unreachable` is returned.
+/// Every node on the network holds a clone of it, and the network holds every node, so a `MemoryNetwork` is never freed. That's fine for tests, which is all it is meant for.
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    inner: Arc<Mutex<Network>>,
+}
+
+#[derive(Default)]
+struct Network {
+    nodes: HashMap<IpAddr, ChordNode>,
+    crashed: HashSet<IpAddr>,
+    cut: HashSet<(IpAddr, IpAddr)>,
+    hellos: HashMap<IpAddr, Hello>,
+    delay: Duration,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connect `node` to the network so that other nodes can reach it at its IP address.
+    pub fn add(&self, node: &ChordNode) {
+        let mut network = self.inner.lock().unwrap();
+        network.nodes.insert(node.self_ip(), node.clone());
+        network.crashed.remove(&node.self_ip());
+    }
+
+    /// returns the node at `ip`, if one was added.
+    pub fn node(&self, ip: IpAddr) -> Option<ChordNode> {
+        self.inner.lock().unwrap().nodes.get(&ip).cloned()
+    }
+
+    /// Stop delivering requests to `ip` until `recover(ip)` is called.
+    pub fn crash(&self, ip: IpAddr) {
+        self.inner.lock().unwrap().crashed.insert(ip);
+    }
+
+    pub fn recover(&self, ip: IpAddr) {
+        self.inner.lock().unwrap().crashed.remove(&ip);
+    }
+
+    /// Drop every request between `a` and `b`, in both directions, until `heal(a, b)` is called.
+    pub fn cut(&self, a: IpAddr, b: IpAddr) {
+        let mut network = self.inner.lock().unwrap();
+        network.cut.insert((a, b));
+        network.cut.insert((b, a));
+    }
+
+    pub fn heal(&self, a: IpAddr, b: IpAddr) {
+        let mut network = self.inner.lock().unwrap();
+        network.cut.remove(&(a, b));
+        network.cut.remove(&(b, a));
+    }
+
+    /// Make the node at `ip` introduce itself with `hello` instead of its own `ChordNode::hello()`, as if it had been built with other ring parameters or another version.
+    pub fn set_hello(&self, ip: IpAddr, hello: Hello) {
+        self.inner.lock().unwrap().hellos.insert(ip, hello);
+    }
+
+    /// Delay every request by `delay` before delivering it.
+    pub fn set_delay(&self, delay: Duration) {
+        self.inner.lock().unwrap().delay = delay;
+    }
+
+    /// returns the node at `to` if a request from `from` would currently be delivered to it.
+    fn reachable(&self, from: IpAddr, to: IpAddr) -> Option<ChordNode> {
+        let network = self.inner.lock().unwrap();
+        if network.crashed.contains(&to) || network.cut.contains(&(from, to)) {
+            return None;
+        }
+        network.nodes.get(&to).cloned()
+    }
+
+    /// Waits for the configured delay and returns the node `ip` that `node` is sending a request to. If the request can't be delivered, `ip` is reported to `node`'s failure detector.
+    async fn deliver(&self, node: &ChordNode, ip: IpAddr) -> Result<ChordNode, ChordError> {
+        let delay = self.inner.lock().unwrap().delay;
+        if delay > Duration::from_secs(0) {
+            tokio::time::sleep(delay).await;
+        }
+        match self.reachable(node.self_ip(), ip) {
+            Some(other) => {
+                node.failures.clear(ip);
+                Ok(other)
+            }
+            None => Err(unreachable(ip, "dropped by the network".to_string(), node)),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryNetwork {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn hello(&self, node: &ChordNode, ip: IpAddr) -> Result<Option<Hello>, ChordError> {
+        let other = self.deliver(node, ip).await?;
+        let hellos = &self.inner.lock().unwrap().hellos;
+        Ok(Some(
+            hellos.get(&ip).cloned().unwrap_or_else(|| other.hello()),
+        ))
+    }
+
+    async fn get_successor(&self, node: &ChordNode, ip: IpAddr) -> Result<IpAddr, ChordError> {
+        Ok(self.deliver(node, ip).await?.get_successor())
+    }
+
+    async fn get_predecessor(&self, node: &ChordNode, ip: IpAddr) -> Result<IpAddr, ChordError> {
+        Ok(self.deliver(node, ip).await?.get_predecessor())
+    }
+
+    async fn closest_preceding_finger(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        id: u64,
+    ) -> Result<IpAddr, ChordError> {
+        let other = self.deliver(node, ip).await?;
+        Ok(other.closest_preceding_finger(id))
+    }
+
+    async fn update_finger_table(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        s: IpAddr,
+        i: u64,
+    ) -> Result<(), ChordError> {
+        let mut other = self.deliver(node, ip).await?;
+        other.update_finger_table(s, i).await
+    }
+
+    async fn notify(&self, node: &ChordNode, ip: IpAddr, n: IpAddr) -> Result<(), ChordError> {
+        self.deliver(node, ip).await?.notify(n).await;
+        Ok(())
+    }
+
+    async fn insert_replica(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        keys: Vec<String>,
+    ) -> Result<(), ChordError> {
+        self.deliver(node, ip).await?.insert_replica(keys);
+        Ok(())
+    }
+
+    async fn rejoin(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        n: IpAddr,
+        incarnation: u64,
+    ) -> Result<Vec<String>, ChordError> {
+        self.deliver(node, ip).await?.handle_rejoin(n, incarnation)
+    }
+
+    async fn insert(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        key: String,
+    ) -> Result<String, ChordError> {
+        self.deliver(node, ip).await?.insert(key).await
+    }
+
+    async fn contains(&self, node: &ChordNode, ip: IpAddr, key: &str) -> Result<bool, ChordError> {
+        self.deliver(node, ip).await?.contains(key).await
+    }
+
+    async fn delete(&self, node: &ChordNode, ip: IpAddr, key: &str) -> Result<bool, ChordError> {
+        self.deliver(node, ip).await?.delete(key).await
+    }
+
+    async fn delete_replica(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        keys: Vec<String>,
+    ) -> Result<(), ChordError> {
+        self.deliver(node, ip).await?.delete_replica(keys);
+        Ok(())
+    }
+
+    async fn load(&self, node: &ChordNode, ip: IpAddr) -> Result<Load, ChordError> {
+        Ok(self.deliver(node, ip).await?.load())
+    }
+
+    async fn view(&self, node: &ChordNode, ip: IpAddr) -> Result<NodeView, ChordError> {
+        Ok(self.deliver(node, ip).await?.view())
+    }
+
+    async fn is_alive(&self, node: &ChordNode, ip: IpAddr) -> bool {
+        self.reachable(node.self_ip(), ip).is_some()
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// upper bounds of the histogram buckets.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+]; // in seconds.
+const HOP_BUCKETS: &[f64] = &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0, 12.0, 16.0];
+const RATE_WINDOW: usize = 60; // request rates are averaged over this many seconds.
+
+// segments of the HTTP routes that are followed by a parameter, and the name the parameter gets in the `route` label (see `route_label`).
+const PARAMETERS: &[(&str, &str)] = &[
+    ("key", ":key"),
+    ("lookup", ":id"),
+    ("cpf", ":id"),
+    ("fingers", ":index"),
+];
+
+/// What a node counts about itself, served in the Prometheus text format on `GET /metrics` (see `ChordNode::render_metrics`).
+/// Like `FailureDetector`, this is cloned into every copy of the node, so the counters are shared behind an `Arc`.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Mutex<Counters>>,
+}
+
+/// lookups, lookup_hops - lookups of the node responsible for an ID started on this node, and how many nodes each successful one was forwarded through.
+/// requests - the latency of the requests served over HTTP, by listener, method and route.
+/// handle_failures - rounds of `handle_failure`.
+/// task_durations, task_errors - the duration of the rounds of each maintenance task, and how many of them failed.
+/// peer_timeouts - requests to peers that timed out.
+/// key_operations - inserts, lookups and deletes of keys served by this node as their owner, whoever asked for them.
+/// client_rate, key_rate - the recent rate of the requests served on the client API, and of `key_operations`.
+#[derive(Default)]
+struct Counters {
+    lookups: u64,
+    lookup_hops: Histogram,
+    requests: BTreeMap<(&'static str, String, String), Histogram>,
+    handle_failures: u64,
+    task_durations: BTreeMap<&'static str, Histogram>,
+    task_errors: BTreeMap<&'static str, u64>,
+    peer_timeouts: u64,
+    key_operations: u64,
+    client_rate: Rate,
+    key_rate: Rate,
+}
+
+/// A Prometheus histogram: how many observations fell in each bucket (not cumulative, unlike the text format), their sum and their count.
+#[derive(Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, bounds: &[f64], value: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; bounds.len()];
+        }
+        if let Some(i) = bounds.iter().position(|bound| value <= *bound) {
+            self.buckets[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// writes the `_bucket`, `_sum` and `_count` series of the histogram `name`, with `labels` (like `task="stabilize"`) on each.
+    fn render(&self, out: &mut String, name: &str, bounds: &[f64], labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (i, bound) in bounds.iter().enumerate() {
+            cumulative += self.buckets.get(i).copied().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, self.count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+/// Counts events over the last `RATE_WINDOW` seconds, one bucket per second.
+/// counts[i] - the events of the second `seconds[i]` (since `started`), where i is that second modulo `RATE_WINDOW`.
+struct Rate {
+    started: Instant,
+    counts: [u64; RATE_WINDOW],
+    seconds: [u64; RATE_WINDOW],
+}
+
+impl Default for Rate {
+    fn default() -> Self {
+        Rate {
+            started: Instant::now(),
+            counts: [0; RATE_WINDOW],
+            seconds: [0; RATE_WINDOW],
+        }
+    }
+}
+
+impl Rate {
+    fn record(&mut self) {
+        let second = self.started.elapsed().as_secs();
+        let i = second as usize % RATE_WINDOW;
+        if self.seconds[i] != second {
+            self.seconds[i] = second;
+            self.counts[i] = 0;
+        }
+        self.counts[i] += 1;
+    }
+
+    /// returns the events a second over the last `RATE_WINDOW` seconds, or since `started` if that's more recent.
+    fn per_sec(&self) -> f64 {
+        let now = self.started.elapsed().as_secs();
+        let window = RATE_WINDOW as u64;
+        let events: u64 = (0..RATE_WINDOW)
+            .filter(|&i| now - self.seconds[i] < window)
+            .map(|i| self.counts[i])
+            .sum();
+        events as f64 / (now + 1).min(window) as f64
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn counters(&self) -> MutexGuard<'_, Counters> {
+        self.inner.lock().unwrap()
+    }
+
+    pub fn lookup_started(&self) {
+        self.counters().lookups += 1;
+    }
+
+    /// records a lookup that found the node it was looking for after forwarding through `hops` nodes.
+    pub fn lookup_done(&self, hops: u32) {
+        self.counters()
+            .lookup_hops
+            .observe(HOP_BUCKETS, f64::from(hops));
+    }
+
+    /// records a request served by `listener`. The path is reduced to its route (see `route_label`), so that keys don't end up in labels. Requests that match no route never get here: the router answers them before any middleware runs.
+    pub fn request_served(
+        &self,
+        listener: &'static str,
+        method: &str,
+        path: &str,
+        elapsed: Duration,
+    ) {
+        let route = route_label(path);
+        let mut counters = self.counters();
+        if listener == "client" {
+            counters.client_rate.record();
+        }
+        let histogram = counters
+            .requests
+            .entry((listener, method.to_string(), route))
+            .or_default();
+        histogram.observe(LATENCY_BUCKETS, elapsed.as_secs_f64());
+    }
+
+    pub fn handle_failure_started(&self) {
+        self.counters().handle_failures += 1;
+    }
+
+    /// records a round of the maintenance task `task` that took `elapsed`, and whether it failed.
+    pub fn task_round(&self, task: &'static str, elapsed: Duration, failed: bool) {
+        let mut counters = self.counters();
+        counters
+            .task_durations
+            .entry(task)
+            .or_default()
+            .observe(LATENCY_BUCKETS, elapsed.as_secs_f64());
+        let errors = counters.task_errors.entry(task).or_default();
+        if failed {
+            *errors += 1;
+        }
+    }
+
+    pub fn peer_timed_out(&self) {
+        self.counters().peer_timeouts += 1;
+    }
+
+    /// records a key operation served by this node as the owner of the key.
+    pub fn key_operation_served(&self) {
+        let mut counters = self.counters();
+        counters.key_operations += 1;
+        counters.key_rate.record();
+    }
+
+    /// returns the recent rate (a second, see `RATE_WINDOW`) of the requests served on the client API and of the key operations served as owner.
+    pub fn rates(&self) -> (f64, f64) {
+        let counters = self.counters();
+        (counters.client_rate.per_sec(), counters.key_rate.per_sec())
+    }
+
+    /// Renders the counters in the Prometheus text format, followed by `gauges`: (name, help, value) of what the node measures at the time of the scrape.
+    pub fn render(&self, gauges: &[(&str, &str, f64)]) -> String {
+        let counters = self.counters();
+        let mut out = String::new();
+        header(
+            &mut out,
+            "crust_lookups_total",
+            "Lookups of the node responsible for an ID started on this node.",
+            "counter",
+        );
+        let _ = writeln!(out, "crust_lookups_total {}", counters.lookups);
+        header(
+            &mut out,
+            "crust_lookup_hops",
+            "Nodes a successful lookup was forwarded through.",
+            "histogram",
+        );
+        counters
+            .lookup_hops
+            .render(&mut out, "crust_lookup_hops", HOP_BUCKETS, "");
+
+        header(
+            &mut out,
+            "crust_http_request_duration_seconds",
+            "Time taken to serve HTTP requests, by listener, method and route.",
+            "histogram",
+        );
+        for ((listener, method, route), histogram) in counters.requests.iter() {
+            let labels = format!(
+                "listener=\"{}\",method=\"{}\",route=\"{}\"",
+                escape(listener),
+                escape(method),
+                escape(route)
+            );
+            histogram.render(
+                &mut out,
+                "crust_http_request_duration_seconds",
+                LATENCY_BUCKETS,
+                &labels,
+            );
+        }
+
+        header(
+            &mut out,
+            "crust_handle_failure_total",
+            "Times this node repaired its pointers after a failure (handle_failure).",
+            "counter",
+        );
+        let _ = writeln!(
+            out,
+            "crust_handle_failure_total {}",
+            counters.handle_failures
+        );
+        header(
+            &mut out,
+            "crust_task_duration_seconds",
+            "Time taken by the rounds of each maintenance task.",
+            "histogram",
+        );
+        for (task, histogram) in counters.task_durations.iter() {
+            histogram.render(
+                &mut out,
+                "crust_task_duration_seconds",
+                LATENCY_BUCKETS,
+                &format!("task=\"{}\"", task),
+            );
+        }
+        header(
+            &mut out,
+            "crust_task_errors_total",
+            "Rounds of each maintenance task that failed.",
+            "counter",
+        );
+        for (task, errors) in counters.task_errors.iter() {
+            let _ = writeln!(
+                out,
+                "crust_task_errors_total{{task=\"{}\"}} {}",
+                task, errors
+            );
+        }
+        header(
+            &mut out,
+            "crust_peer_timeouts_total",
+            "Requests to peers that timed out.",
+            "counter",
+        );
+        let _ = writeln!(out, "crust_peer_timeouts_total {}", counters.peer_timeouts);
+        header(
+            &mut out,
+            "crust_key_operations_total",
+            "Inserts, lookups and deletes of keys served by this node as their owner.",
+            "counter",
+        );
+        let _ = writeln!(
+            out,
+            "crust_key_operations_total {}",
+            counters.key_operations
+        );
+
+        for (name, help, value) in gauges {
+            header(&mut out, name, help, "gauge");
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// escapes a label value of the text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// returns the route `path` was served by, with its parameters replaced by their names, e.g. `/v1/key/:key` for `/v1/key/apple/`.
+pub fn route_label(path: &str) -> String {
+    let mut route = String::new();
+    let mut previous = "";
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let parameter = PARAMETERS.iter().find(|(before, _)| *before == previous);
+        route.push('/');
+        route.push_str(parameter.map_or(segment, |(_, name)| name));
+        previous = segment;
+    }
+    if route.is_empty() {
+        route.push('/');
+    }
+    route
+}
@@ -0,0 +1,231 @@
+use crate::error_response;
+use crust::TlsClient;
+use crust::{Audience, ChordError, ChordNode, ClusterSecret, Metrics, Security, Signature};
+use gotham::handler::HandlerFuture;
+use gotham::hyper::header::{self, HeaderMap, HeaderValue};
+use gotham::hyper::{body, Body, Method, StatusCode, Uri};
+use gotham::middleware::Middleware;
+use gotham::state::{client_addr, request_id, FromState, State};
+use gotham_derive::NewMiddleware;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{debug, field, info, info_span, Instrument};
+
+const MAX_TRACKED_CLIENTS: usize = 10_000; // past this many clients, `RateLimit` forgets the ones that have been quiet long enough to have a full bucket again.
+
+/// Lets requests through to the peer API only if they come from a peer: over TLS, the client has to present a certificate signed by the cluster CA (`ChordError::Forbidden` otherwise), and with a cluster secret, the request has to be signed with it (`ChordError::Unauthorized` otherwise).
+#[derive(Clone, NewMiddleware)]
+pub struct PeerAuth;
+
+impl Middleware for PeerAuth {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        Box::pin(async move {
+            match authorize_peer(&mut state).await {
+                Ok(()) => chain(state).await,
+                Err(e) => {
+                    let resp = error_response(&state, e);
+                    Ok((state, resp))
+                }
+            }
+        })
+    }
+}
+
+async fn authorize_peer(state: &mut State) -> Result<(), ChordError> {
+    if let Some(client) = state.try_borrow::<TlsClient>() {
+        if !client.authenticated {
+            return Err(ChordError::Forbidden(
+                "Only peers with a certificate signed by the cluster CA can make this request"
+                    .to_string(),
+            ));
+        }
+    }
+    if let Some(secret) = Security::borrow_from(state).secret.clone() {
+        let body = peek_body(state).await?;
+        verify_signature(state, &secret, &body)?;
+    }
+    Ok(())
+}
+
+/// Checks the signature headers of the request against its method, path and `body`.
+fn verify_signature(state: &State, secret: &ClusterSecret, body: &[u8]) -> Result<(), ChordError> {
+    let headers = HeaderMap::borrow_from(state);
+    let signature =
+        Signature::from_headers(|name| headers.get(name).and_then(|value| value.to_str().ok()));
+    let method = Method::borrow_from(state).as_str();
+    secret.verify(method, Uri::borrow_from(state).path(), body, signature)
+}
+
+/// returns the body of the request, leaving a copy of it for the handler to read.
+async fn peek_body(state: &mut State) -> Result<body::Bytes, ChordError> {
+    let bytes = body::to_bytes(Body::take_from(state))
+        .await
+        .map_err(ChordError::bad_request)?;
+    state.put(Body::from(bytes.clone()));
+    Ok(bytes)
+}
+
+/// Lets requests through to an API only if they carry the token of its `Audience`: the `/key` API takes the client token, if there is one, and the `/admin` API the admin token, and nothing without one (see `Security::authorize`).
+#[derive(Clone, NewMiddleware)]
+pub struct TokenAuth(pub Audience);
+
+impl Middleware for TokenAuth {
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        let authorization = HeaderMap::borrow_from(&state)
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        match Security::borrow_from(&state).authorize(self.0, authorization) {
+            Ok(()) => chain(state),
+            Err(e) => {
+                let resp = error_response(&state, e);
+                Box::pin(async move { Ok((state, resp)) })
+            }
+        }
+    }
+}
+
+/// Allows every client IP address `rate` requests a second on average, in bursts of up to `burst` requests, and answers the requests over that with a `ChordError::RateLimited` and a `Retry-After` header.
+#[derive(Clone, NewMiddleware)]
+pub struct RateLimit {
+    rate: f64,
+    burst: f64,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+/// The requests a client has left (a token bucket), as of `updated`.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimit {
+    pub fn new(rate: u32, burst: u32) -> Self {
+        RateLimit {
+            rate: f64::from(rate),
+            burst: f64::from(burst),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// returns true if `ip` can make one more request now.
+    fn take(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            let refill = self.burst / self.rate;
+            buckets.retain(|_, bucket| (now - bucket.updated).as_secs_f64() < refill);
+        }
+        let burst = self.burst;
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = (now - bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+}
+
+impl Middleware for RateLimit {
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        match client_addr(&state) {
+            Some(addr) if !self.take(addr.ip()) => {
+                let error =
+                    ChordError::RateLimited(format!("Too many requests from {}", addr.ip()));
+                let mut resp = error_response(&state, error);
+                resp.headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(1));
+                Box::pin(async move { Ok((state, resp)) })
+            }
+            _ => chain(state),
+        }
+    }
+}
+
+/// Runs every request served by the `listener` it is named after in a `request` span, with the ID of the node and of the request (and the ID of the key, for the key operations), and logs one line per request once it is served, at the info level. With `failures_only`, the requests that succeed are only logged at the debug level. Every request is timed in the node's `Metrics`, logged or not.
+#[derive(Clone, NewMiddleware)]
+pub struct RequestLog {
+    listener: &'static str,
+    failures_only: bool,
+    node_id: u64,
+    metrics: Metrics,
+}
+
+impl RequestLog {
+    pub fn every_request(listener: &'static str, node: &ChordNode) -> Self {
+        RequestLog {
+            listener,
+            failures_only: false,
+            node_id: node.self_id(),
+            metrics: node.metrics().clone(),
+        }
+    }
+
+    pub fn failures(listener: &'static str, node: &ChordNode) -> Self {
+        RequestLog {
+            listener,
+            failures_only: true,
+            node_id: node.self_id(),
+            metrics: node.metrics().clone(),
+        }
+    }
+}
+
+impl Middleware for RequestLog {
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        let started = Instant::now();
+        let method = Method::borrow_from(&state).clone();
+        let path = Uri::borrow_from(&state).path().to_string();
+        let client =
+            client_addr(&state).map_or_else(|| "?".to_string(), |addr| addr.ip().to_string());
+        let span = info_span!(
+            "request",
+            listener = self.listener,
+            node_id = self.node_id,
+            request_id = request_id(&state),
+            %method,
+            %path,
+            %client,
+            key_id = field::Empty,
+        );
+        let request = async move {
+            let result = chain(state).await;
+            let status = match &result {
+                Ok((_, resp)) => resp.status(),
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            let elapsed = started.elapsed();
+            self.metrics
+                .request_served(self.listener, method.as_str(), &path, elapsed);
+            let (status, failed) = (status.as_u16(), !status.is_success());
+            let elapsed_ms = elapsed.as_millis() as u64;
+            if self.failures_only && !failed {
+                debug!(status, elapsed_ms, "Served");
+            } else {
+                info!(status, elapsed_ms, "Served");
+            }
+            result
+        };
+        Box::pin(request.instrument(span))
+    }
+}
@@ -0,0 +1,295 @@
+use crate::auth::CLIENT_TOKEN_ENV;
+use crate::config::MAX_RING_BITS;
+use crate::tls::TLS_CA_ENV;
+use crate::{ChordError, Config, LogFormat, PORT};
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The environment variable naming the configuration file, when `--config` isn't given.
+pub const CONFIG_ENV: &str = "CRUST_CONFIG";
+
+const CLIENT_HELP: &str = "Exit status: 0 on success, 1 if the key wasn't found (get, delete), 2 for invalid arguments, \
+3 if the node or the ring is unavailable (retrying later may work), 4 if the node refused the request.";
+
+const SERVE_HELP: &str = "Settings are read from the configuration file first, then from the environment, then from the flags. \
+Every setting flag has an environment variable named after it, like CRUST_PORT for --port or CRUST_STABILIZE_INTERVAL for --stabilize-interval.";
+
+/// The command line of the node binary. Run `crust --help` for the details.
+#[derive(Debug, Parser)]
+#[command(name = "crust", version, about = "A Chord distributed hash table")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// The subcommands of the binary: `serve` runs a node, the others are clients of the client API of a node (see `ClientOptions`).
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Runs a node, creating a new ring (--create) or joining an existing one (--join).
+    #[command(after_help = SERVE_HELP)]
+    Serve(Options),
+    /// Inserts a key into the ring.
+    #[command(after_help = CLIENT_HELP)]
+    Put(KeyCommand),
+    /// Checks whether a key is in the ring.
+    #[command(after_help = CLIENT_HELP)]
+    Get(KeyCommand),
+    /// Deletes a key from the ring.
+    #[command(after_help = CLIENT_HELP)]
+    Delete(KeyCommand),
+    /// Shows the ID of a key and the node responsible for it.
+    #[command(after_help = CLIENT_HELP)]
+    Lookup(KeyCommand),
+    /// Lists the nodes of the ring, in the order of their IDs.
+    #[command(after_help = CLIENT_HELP)]
+    Ring(ClientOptions),
+    /// Shows the state of a node.
+    #[command(after_help = CLIENT_HELP)]
+    Info(InfoCommand),
+}
+
+/// How a client subcommand reaches the ring: through the client API of any of its nodes.
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct ClientOptions {
+    /// Node to send the request to, with the port of its client API if it isn't the default one.
+    #[arg(long, value_name = "ADDR", env = "CRUST_NODE", default_value = "127.0.0.1", value_parser = parse_seed)]
+    pub node: SocketAddr,
+
+    /// Print JSON instead of a table.
+    #[arg(long)]
+    pub json: bool,
+
+    /// Client token of the ring, if it requires one for the key operations.
+    #[arg(long, env = CLIENT_TOKEN_ENV, hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Talk HTTPS to the node, trusting the certificates signed by this CA (the cluster CA).
+    #[arg(long, value_name = "FILE", env = TLS_CA_ENV)]
+    pub ca: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct KeyCommand {
+    pub key: String,
+
+    #[command(flatten)]
+    pub client: ClientOptions,
+}
+
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct InfoCommand {
+    /// Node to show, --node by default.
+    #[arg(value_name = "NODE", value_parser = parse_seed)]
+    pub target: Option<SocketAddr>,
+
+    #[command(flatten)]
+    pub client: ClientOptions,
+}
+
+/// How a node runs: `crust serve (--create | --join <seed>...) [flags]`.
+/// Every flag but `--create`, `--join` and `--config` overrides a setting of the `Config`, which can also come from a configuration file or from `CRUST_*` environment variables (see `config`).
+/// Addresses are IPv4 or IPv6 literals; IPv6 ones can be written in brackets, like in URLs. Invalid values are rejected by `Cli::parse`, with a usage message, before the node starts.
+#[derive(Debug, Clone, PartialEq, Args)]
+#[command(group(ArgGroup::new("ring").required(true).args(["create", "seeds"])))]
+pub struct Options {
+    /// Create a new ring, with this node as its first member.
+    #[arg(long)]
+    pub create: bool,
+
+    /// Join the ring of these nodes, trying them in order. Give the port of a seed's client API if it isn't the default one, e.g. 10.0.0.1:9000 or [2001:db8::1]:9000.
+    #[arg(long = "join", value_name = "SEED", num_args = 1.., value_parser = parse_seed)]
+    pub seeds: Vec<SocketAddr>,
+
+    /// TOML file to read the configuration from, before the environment and the flags [env: CRUST_CONFIG]
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Address the listeners bind to: 0.0.0.0 is every IPv4 interface, :: every interface [default: 0.0.0.0]
+    #[arg(long, value_name = "IP", value_parser = parse_ip)]
+    pub bind: Option<IpAddr>,
+
+    /// Address the other nodes reach this node at, which its ID is derived from. Defaults to --bind if it is a specific address, or else to the address of the interface with the default route.
+    #[arg(long, value_name = "IP", value_parser = parse_ip)]
+    pub advertise: Option<IpAddr>,
+
+    /// Port of the client API. The peer ports (8001 to 8003) can't be changed, since peers only know each other's IP addresses [default: 8000]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub port: Option<u16>,
+
+    /// Directory the keys and the incarnation number of the node are persisted in [default: data]
+    #[arg(long, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Number of bits of the IDs on the ring. Every node of a ring has to use the same [default: 6]
+    #[arg(long, value_name = "BITS", value_parser = clap::value_parser!(u32).range(1..=MAX_RING_BITS as i64))]
+    pub ring_bits: Option<u32>,
+
+    /// Number of successors every key is replicated to. Every node of a ring has to use the same [default: 6]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub replication_factor: Option<u32>,
+
+    /// Seconds a peer has to answer a liveness probe in before it's considered dead [default: 1]
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub liveness_timeout: Option<u64>,
+
+    /// Seconds any other request to a peer can take before it fails [default: 3]
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub request_timeout: Option<u64>,
+
+    /// Seconds between two rounds of stabilize [default: 2]
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub stabilize_interval: Option<u64>,
+
+    /// Seconds between two fixes of a random finger [default: 2]
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub fix_fingers_interval: Option<u64>,
+
+    /// Seconds between two rebuilds of the successor list [default: 2]
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub successor_list_interval: Option<u64>,
+
+    /// Seconds between two pushes of this node's keys to its replicas [default: 10]
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub replica_sync_interval: Option<u64>,
+
+    /// Events to log, like info or crust=debug,gotham=warn (see tracing_subscriber's EnvFilter) [default: info]
+    #[arg(long, value_name = "FILTER")]
+    pub log: Option<String>,
+
+    /// Format of the log lines: text, or json for log pipelines [default: text]
+    #[arg(long, value_name = "FORMAT")]
+    pub log_format: Option<LogFormat>,
+}
+
+impl Options {
+    /// Builds the `Config` this node runs with: the defaults, overridden by the configuration file (`--config`, or else `CRUST_CONFIG`), then by the `CRUST_*` variables that `env` returns, then by these flags. The result is validated.
+    /// `env` looks up an environment variable; it's `std::env::var` outside of tests.
+    pub fn config(&self, env: impl Fn(&str) -> Option<String>) -> Result<Config, ChordError> {
+        let file = self
+            .config
+            .clone()
+            .or_else(|| env(CONFIG_ENV).map(PathBuf::from));
+        let mut config = match file {
+            Some(path) => Config::from_file(&path)?,
+            None => Config::default(),
+        };
+
+        let var = |name: &str| env(name).filter(|value| !value.is_empty());
+        if let Some(value) = var("CRUST_BIND") {
+            config.bind = parse_ip(&value).map_err(|_| invalid_env("CRUST_BIND", &value))?;
+        }
+        if let Some(value) = var("CRUST_ADVERTISE") {
+            let ip = parse_ip(&value).map_err(|_| invalid_env("CRUST_ADVERTISE", &value))?;
+            config.advertise = Some(ip);
+        }
+        if let Some(value) = var("CRUST_DATA_DIR") {
+            config.data_dir = PathBuf::from(value);
+        }
+        if let Some(value) = var("CRUST_LOG") {
+            config.log.filter = value;
+        }
+        override_from_env(var, "CRUST_LOG_FORMAT", &mut config.log.format)?;
+        override_from_env(var, "CRUST_PORT", &mut config.port)?;
+        override_from_env(var, "CRUST_RING_BITS", &mut config.ring_bits)?;
+        override_from_env(
+            var,
+            "CRUST_REPLICATION_FACTOR",
+            &mut config.replication_factor,
+        )?;
+        override_from_env(var, "CRUST_LIVENESS_TIMEOUT", &mut config.liveness_timeout)?;
+        override_from_env(var, "CRUST_REQUEST_TIMEOUT", &mut config.request_timeout)?;
+        let intervals = &mut config.intervals;
+        override_from_env(var, "CRUST_STABILIZE_INTERVAL", &mut intervals.stabilize)?;
+        override_from_env(
+            var,
+            "CRUST_FIX_FINGERS_INTERVAL",
+            &mut intervals.fix_fingers,
+        )?;
+        override_from_env(
+            var,
+            "CRUST_SUCCESSOR_LIST_INTERVAL",
+            &mut intervals.successor_list,
+        )?;
+        override_from_env(
+            var,
+            "CRUST_REPLICA_SYNC_INTERVAL",
+            &mut intervals.replica_sync,
+        )?;
+
+        if let Some(bind) = self.bind {
+            config.bind = bind;
+        }
+        if self.advertise.is_some() {
+            config.advertise = self.advertise;
+        }
+        if let Some(data_dir) = &self.data_dir {
+            config.data_dir = data_dir.clone();
+        }
+        if let Some(filter) = &self.log {
+            config.log.filter = filter.clone();
+        }
+        config.log.format = self.log_format.unwrap_or(config.log.format);
+        let flags = [
+            (&mut config.liveness_timeout, self.liveness_timeout),
+            (&mut config.request_timeout, self.request_timeout),
+            (&mut config.intervals.stabilize, self.stabilize_interval),
+            (&mut config.intervals.fix_fingers, self.fix_fingers_interval),
+            (
+                &mut config.intervals.successor_list,
+                self.successor_list_interval,
+            ),
+            (
+                &mut config.intervals.replica_sync,
+                self.replica_sync_interval,
+            ),
+        ];
+        for (setting, flag) in flags {
+            if let Some(value) = flag {
+                *setting = value;
+            }
+        }
+        config.port = self.port.unwrap_or(config.port);
+        config.ring_bits = self.ring_bits.unwrap_or(config.ring_bits);
+        config.replication_factor = self.replication_factor.unwrap_or(config.replication_factor);
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Replaces `setting` with the value of the environment variable `name`, if it is set.
+fn override_from_env<T: FromStr>(
+    var: impl Fn(&str) -> Option<String>,
+    name: &str,
+    setting: &mut T,
+) -> Result<(), ChordError> {
+    if let Some(value) = var(name) {
+        *setting = value.parse().map_err(|_| invalid_env(name, &value))?;
+    }
+    Ok(())
+}
+
+fn invalid_env(name: &str, value: &str) -> ChordError {
+    ChordError::BadRequest(format!("Invalid value {:?} for {}", value, name))
+}
+
+/// Parses an IPv4 or IPv6 address, with or without brackets around IPv6 ones. Fails with `ChordError::BadRequest`, like the other invalid settings.
+pub fn parse_ip(value: &str) -> Result<IpAddr, ChordError> {
+    let unbracketed = value
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or(value);
+    unbracketed
+        .parse()
+        .map_err(|_| ChordError::BadRequest(format!("Invalid IP address {}", value)))
+}
+
+/// Parses the address of a seed, with or without the port of its client API, which is `PORT` by default.
+pub fn parse_seed(value: &str) -> Result<SocketAddr, ChordError> {
+    match value.parse() {
+        Ok(addr) => Ok(addr),
+        Err(_) => parse_ip(value).map(|ip| SocketAddr::new(ip, PORT)),
+    }
+}
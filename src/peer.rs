@@ -0,0 +1,546 @@
+use crate::grpc::GrpcTransport;
+use crate::{ChordError, ChordNode, Hello, Load, NodeView, Security, TlsConfig, SUSPECT_HEADER};
+use crate::{
+    HTTP_FINGER_TABLE, HTTP_HELLO, HTTP_KEY, HTTP_LOAD, HTTP_NOTIFY, HTTP_PREDECESSOR, HTTP_REJOIN,
+    HTTP_REPLICA, HTTP_VIEW,
+};
+use crate::{HTTP_PEER, HTTP_SUCCESSOR, HTTP_SUCCESSOR_CPF, HTTP_TRANSPORTS, PEER_PORT};
+use async_trait::async_trait;
+use gotham::hyper::StatusCode;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, RequestBuilder, Response, Url};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::panic::{AssertUnwindSafe, RefUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::warn;
+use url::form_urlencoded;
+
+const POOL_MAX_IDLE_PER_HOST: usize = 8; // idle connections kept open to each peer.
+const POOL_IDLE_TIMEOUT: u64 = 90; // seconds before an idle connection to a peer is closed.
+pub(crate) const TCP_KEEPALIVE: u64 = 30; // seconds between TCP keepalive probes on connections to peers.
+pub(crate) const MAX_REQUESTS_PER_PEER: usize = 16; // requests in flight to a single peer; further requests wait for one of them to finish.
+
+// what has to be percent-encoded in a path segment, the same as `url` encodes in `crust-client`.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'?')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/')
+    .add(b'%');
+
+/// Every request a node sends to another node of the ring. `ChordNode` only talks to its peers through this trait, so the same Chord logic runs over HTTP, gRPC (see `grpc::GrpcTransport`) or, in tests, an in-memory network (see `MemoryNetwork`).
+/// `node` is the node sending the request. Implementations report a peer that can't be reached to `node`'s failure detector and return `ChordError::Timeout` or `ChordError::Unreachable`, and clear the suspicion once it answers again.
+/// A transport lives inside `ChordNode`, which gotham keeps in its `State`, so it has to be unwind safe as well.
+#[async_trait]
+pub trait Transport: Send + Sync + RefUnwindSafe {
+    /// the name of this transport, as served on `GET /transports/` and shown in `/info`.
+    fn name(&self) -> &'static str;
+
+    /// asks `ip` to introduce itself (see `Hello`). Returns `None` if `ip` predates the handshake.
+    async fn hello(&self, node: &ChordNode, ip: IpAddr) -> Result<Option<Hello>, ChordError>;
+
+    /// returns the immediate successor of `ip`.
+    async fn get_successor(&self, node: &ChordNode, ip: IpAddr) -> Result<IpAddr, ChordError>;
+
+    /// returns the predecessor pointer of `ip`.
+    async fn get_predecessor(&self, node: &ChordNode, ip: IpAddr) -> Result<IpAddr, ChordError>;
+
+    /// asks `ip` for its closest preceding finger of `id`.
+    async fn closest_preceding_finger(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        id: u64,
+    ) -> Result<IpAddr, ChordError>;
+
+    /// asks `ip` to consider `s` as the `i`th entry of its finger table.
+    async fn update_finger_table(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        s: IpAddr,
+        i: u64,
+    ) -> Result<(), ChordError>;
+
+    /// tells `ip` that `n` might be its predecessor.
+    async fn notify(&self, node: &ChordNode, ip: IpAddr, n: IpAddr) -> Result<(), ChordError>;
+
+    /// stores `keys` as replicas on `ip`.
+    async fn insert_replica(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        keys: Vec<String>,
+    ) -> Result<(), ChordError>;
+
+    /// tells `ip` (the successor of `n`) that `n` is back as `incarnation`. Returns the keys `ip` hands back to `n`.
+    async fn rejoin(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        n: IpAddr,
+        incarnation: u64,
+    ) -> Result<Vec<String>, ChordError>;
+
+    /// inserts `key` on `ip`, which is expected to be the successor of the key. Returns the ID of the node the key was inserted at.
+    async fn insert(&self, node: &ChordNode, ip: IpAddr, key: String)
+        -> Result<String, ChordError>;
+
+    /// asks `ip`, which is expected to be the successor of `key`, whether it has `key`.
+    async fn contains(&self, node: &ChordNode, ip: IpAddr, key: &str) -> Result<bool, ChordError>;
+
+    /// deletes `key` from `ip`, which is expected to be the successor of the key. Returns true if the key was there.
+    async fn delete(&self, node: &ChordNode, ip: IpAddr, key: &str) -> Result<bool, ChordError>;
+
+    /// removes `keys` from the replicas on `ip`.
+    async fn delete_replica(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        keys: Vec<String>,
+    ) -> Result<(), ChordError>;
+
+    /// asks `ip` for its `Load`. Peers that predate it fail with `ChordError::Internal`.
+    async fn load(&self, node: &ChordNode, ip: IpAddr) -> Result<Load, ChordError>;
+
+    /// asks `ip` for its `NodeView`. Peers that predate it fail with `ChordError::Internal`.
+    async fn view(&self, node: &ChordNode, ip: IpAddr) -> Result<NodeView, ChordError>;
+
+    /// Mark a node as dead if it doesn't respond within the `liveness_timeout` of `node` (see `Config`). Unlike the other calls, a failed probe isn't reported to the failure detector; callers decide what a dead node means to them.
+    async fn is_alive(&self, node: &ChordNode, ip: IpAddr) -> bool;
+}
+
+/// Names of the transports this node supports, in order of preference.
+pub const SUPPORTED_TRANSPORTS: [&str; 2] = [GrpcTransport::NAME, HttpTransport::NAME];
+
+/// returns the transport called `name`, if this node supports it, authenticating this node to its peers as configured in `security`.
+pub fn transport_named(name: &str, security: &Security) -> Option<Arc<dyn Transport>> {
+    match name {
+        GrpcTransport::NAME => Some(Arc::new(GrpcTransport::with_security(security))),
+        HttpTransport::NAME => Some(Arc::new(HttpTransport::with_security(security))),
+        _ => None,
+    }
+}
+
+/// returns `SUPPORTED_TRANSPORTS`, as served on `GET /transports/`.
+pub fn supported_transport_names() -> Vec<&'static str> {
+    SUPPORTED_TRANSPORTS.to_vec()
+}
+
+/// Asks `seed` which peer transports it supports (in its `Hello`, or on `GET /transports/` for seeds that predate the handshake) and returns the first of `SUPPORTED_TRANSPORTS` that it also supports. Seeds that don't know about transports (or don't answer) only speak HTTP.
+/// `seed` is the address of the client API of the seed, which a joining node can reach before it is a peer. It has `timeout` to answer each question.
+pub async fn negotiate(
+    seed: SocketAddr,
+    security: &Security,
+    timeout: Duration,
+) -> Arc<dyn Transport> {
+    let tls = security.tls.as_ref();
+    let client = http_client(tls);
+    let names = match fetch::<Hello>(&client, seed, HTTP_HELLO, tls, timeout).await {
+        Some(hello) => hello.transports,
+        None => fetch::<Vec<String>>(&client, seed, HTTP_TRANSPORTS, tls, timeout)
+            .await
+            .unwrap_or_default(),
+    };
+    SUPPORTED_TRANSPORTS
+        .iter()
+        .find(|name| names.iter().any(|n| n == *name))
+        .and_then(|name| transport_named(name, security))
+        .unwrap_or_else(|| Arc::new(HttpTransport::with_security(security)))
+}
+
+/// GETs `path` from `seed` and parses the JSON reply, if there is one.
+async fn fetch<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    seed: SocketAddr,
+    path: &str,
+    tls: Option<&TlsConfig>,
+    timeout: Duration,
+) -> Option<T> {
+    let resp = client
+        .get(url(tls, seed, path))
+        .timeout(timeout)
+        .send()
+        .await
+        .ok()?;
+    if resp.status() != StatusCode::OK {
+        return None;
+    }
+    resp.json().await.ok()
+}
+
+/// The original peer protocol: form-urlencoded requests to the HTTP peer API of the other node, under `HTTP_PEER` on `PEER_PORT`.
+/// All requests go through one long-lived `reqwest::Client` (see `http_client`), so connections to a peer are kept alive and reused from one hop to the next instead of paying for a new TCP handshake every time.
+/// The client isn't unwind safe on its own because of the boxed callbacks in its configuration. It's never mutated after it's built, so a panicking handler can't leave it in a broken state.
+pub struct HttpTransport {
+    client: AssertUnwindSafe<reqwest::Client>,
+    security: Security,
+    limits: PeerLimits,
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpTransport {
+    pub const NAME: &'static str = "http";
+
+    pub fn new() -> Self {
+        Self::with_security(&Security::default())
+    }
+
+    /// With TLS, requests go to `https://` and present this node's certificate (see `TlsConfig::configure_client`). With a cluster secret, requests are signed (see `ClusterSecret`).
+    pub fn with_security(security: &Security) -> Self {
+        HttpTransport {
+            client: AssertUnwindSafe(http_client(security.tls.as_ref())),
+            security: security.clone(),
+            limits: PeerLimits::default(),
+        }
+    }
+
+    fn url(&self, ip: IpAddr, path: &str) -> String {
+        let path = format!("{}{}", HTTP_PEER, path);
+        url(
+            self.security.tls.as_ref(),
+            SocketAddr::new(ip, PEER_PORT),
+            &path,
+        )
+    }
+
+    /// builds a request from `node` for `path` on `ip`, signed with the cluster secret if there is one. It times out after the `request_timeout` of `node`.
+    fn request(
+        &self,
+        node: &ChordNode,
+        method: Method,
+        ip: IpAddr,
+        path: &str,
+        body: String,
+    ) -> RequestBuilder {
+        let url = Url::parse(&self.url(ip, path)).expect("Invalid peer URL");
+        let mut request = self
+            .client
+            .request(method.clone(), url.clone())
+            .timeout(Duration::from_secs(node.config().request_timeout));
+        if let Some(secret) = &self.security.secret {
+            let signature = secret.sign(method.as_str(), url.path(), body.as_bytes());
+            for (name, value) in signature.headers().iter() {
+                request = request.header(*name, value);
+            }
+        }
+        request.body(body)
+    }
+
+    /// Send a GET request. On request timeout/error, `ip` is reported to the failure detector and a `Timeout` or `Unreachable` error is returned; repairing pointers is left to maintenance.
+    async fn get_req(
+        &self,
+        ip: IpAddr,
+        path: &str,
+        chord_node: &ChordNode,
+    ) -> Result<String, ChordError> {
+        let _slot = self.limits.acquire(ip).await;
+        let resp = self
+            .request(chord_node, Method::GET, ip, path, String::new())
+            .send()
+            .await;
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e) => return Err(request_failed(ip, e, chord_node)),
+        };
+        chord_node.failures.clear(ip);
+        request_unsuccessful(resp, ip, &Method::GET).await
+    }
+
+    /// create a request with a payload (POST, PATCH or DELETE) and send it to `ip`. On request failure/timeout, `ip` is reported to the failure detector and a `Timeout` or `Unreachable` error is returned.
+    async fn data_req(
+        &self,
+        ip: IpAddr,
+        path: &str,
+        data: Vec<(&str, String)>,
+        chord_node: &ChordNode,
+        method: Method,
+    ) -> Result<String, ChordError> {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&data)
+            .finish();
+        let _slot = self.limits.acquire(ip).await;
+        let response = self
+            .request(chord_node, method.clone(), ip, path, body)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .send()
+            .await;
+        let response = match response {
+            Ok(resp) => resp,
+            Err(e) => return Err(request_failed(ip, e, chord_node)),
+        };
+        chord_node.failures.clear(ip);
+
+        let text = request_unsuccessful(response, ip, &method).await?;
+        Ok(text)
+    }
+}
+
+/// returns a `reqwest::Client` tuned for talking to the other nodes of the ring: up to `POOL_MAX_IDLE_PER_HOST` idle connections are kept open to each peer for `POOL_IDLE_TIMEOUT` seconds, with TCP keepalive and without Nagle's algorithm (requests between nodes are small and latency bound). With `tls`, the client authenticates itself to peers and only trusts the cluster CA.
+pub fn http_client(tls: Option<&TlsConfig>) -> reqwest::Client {
+    let builder = reqwest::Client::builder()
+        .pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST)
+        .pool_idle_timeout(Duration::from_secs(POOL_IDLE_TIMEOUT))
+        .tcp_keepalive(Duration::from_secs(TCP_KEEPALIVE))
+        .tcp_nodelay(true);
+    let builder = match tls {
+        Some(tls) => tls.configure_client(builder),
+        None => builder,
+    };
+    builder.build().expect("Can't build the HTTP client")
+}
+
+/// returns the path of `key` on the HTTP peer API. The key is percent-encoded as a single path segment, like `crust-client` does, so that keys can contain anything.
+fn key_path(key: &str) -> String {
+    format!("{}{}", HTTP_KEY, utf8_percent_encode(key, PATH_SEGMENT))
+}
+
+/// returns the URL of `path` on the HTTP API at `addr`.
+fn url(tls: Option<&TlsConfig>, addr: SocketAddr, path: &str) -> String {
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    // a `SocketAddr` puts IPv6 addresses in brackets, as URLs need them.
+    format!("{}://{}/{}", scheme, addr, path)
+}
+
+/// Caps the number of requests in flight to each peer at `MAX_REQUESTS_PER_PEER`. Further requests to that peer wait for a slot, so a burst of lookups can't open an unbounded number of connections to one node.
+#[derive(Default)]
+pub(crate) struct PeerLimits {
+    slots: Mutex<HashMap<IpAddr, Arc<Semaphore>>>,
+}
+
+impl PeerLimits {
+    pub(crate) async fn acquire(&self, ip: IpAddr) -> OwnedSemaphorePermit {
+        let slots = self
+            .slots
+            .lock()
+            .unwrap()
+            .entry(ip)
+            .or_insert_with(|| Arc::new(Semaphore::new(MAX_REQUESTS_PER_PEER)))
+            .clone();
+        slots
+            .acquire_owned()
+            .await
+            .expect("peer slots are never closed")
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    /// Asks the peer API, which serves the same `Hello` as the client API. Peers that predate the handshake answer `404 Not Found`.
+    async fn hello(&self, node: &ChordNode, ip: IpAddr) -> Result<Option<Hello>, ChordError> {
+        let _slot = self.limits.acquire(ip).await;
+        let resp = self
+            .request(node, Method::GET, ip, HTTP_HELLO, String::new())
+            .send()
+            .await
+            .map_err(|e| request_failed(ip, e, node))?;
+        node.failures.clear(ip);
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let hello = request_unsuccessful(resp, ip, &Method::GET).await?;
+        Ok(Some(serde_json::from_str(&hello)?))
+    }
+
+    async fn get_successor(&self, node: &ChordNode, ip: IpAddr) -> Result<IpAddr, ChordError> {
+        Ok(self.get_req(ip, HTTP_SUCCESSOR, node).await?.parse()?)
+    }
+
+    async fn get_predecessor(&self, node: &ChordNode, ip: IpAddr) -> Result<IpAddr, ChordError> {
+        Ok(self.get_req(ip, HTTP_PREDECESSOR, node).await?.parse()?)
+    }
+
+    async fn closest_preceding_finger(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        id: u64,
+    ) -> Result<IpAddr, ChordError> {
+        let path = format!("{}{}/", HTTP_SUCCESSOR_CPF, id);
+        Ok(self.get_req(ip, &path, node).await?.parse()?)
+    }
+
+    async fn update_finger_table(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        s: IpAddr,
+        i: u64,
+    ) -> Result<(), ChordError> {
+        let data = vec![("n", s.to_string()), ("i", i.to_string())];
+        self.data_req(ip, HTTP_FINGER_TABLE, data, node, Method::PATCH)
+            .await?;
+        Ok(())
+    }
+
+    async fn notify(&self, node: &ChordNode, ip: IpAddr, n: IpAddr) -> Result<(), ChordError> {
+        self.data_req(
+            ip,
+            HTTP_NOTIFY,
+            vec![("n", n.to_string())],
+            node,
+            Method::PATCH,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_replica(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        keys: Vec<String>,
+    ) -> Result<(), ChordError> {
+        let data: Vec<(&str, String)> = keys.into_iter().map(|key| ("key", key)).collect();
+        self.data_req(ip, HTTP_REPLICA, data, node, Method::POST)
+            .await?;
+        Ok(())
+    }
+
+    async fn rejoin(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        n: IpAddr,
+        incarnation: u64,
+    ) -> Result<Vec<String>, ChordError> {
+        let data = vec![
+            ("n", n.to_string()),
+            ("incarnation", incarnation.to_string()),
+        ];
+        let keys = self
+            .data_req(ip, HTTP_REJOIN, data, node, Method::POST)
+            .await?;
+        Ok(serde_json::from_str(&keys)?)
+    }
+
+    async fn insert(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        key: String,
+    ) -> Result<String, ChordError> {
+        self.data_req(ip, HTTP_KEY, vec![("key", key)], node, Method::POST)
+            .await
+    }
+
+    async fn contains(&self, node: &ChordNode, ip: IpAddr, key: &str) -> Result<bool, ChordError> {
+        Ok(self.get_req(ip, &key_path(key), node).await?.parse()?)
+    }
+
+    async fn delete(&self, node: &ChordNode, ip: IpAddr, key: &str) -> Result<bool, ChordError> {
+        Ok(self
+            .data_req(ip, &key_path(key), Vec::new(), node, Method::DELETE)
+            .await?
+            .parse()?)
+    }
+
+    async fn delete_replica(
+        &self,
+        node: &ChordNode,
+        ip: IpAddr,
+        keys: Vec<String>,
+    ) -> Result<(), ChordError> {
+        let data: Vec<(&str, String)> = keys.into_iter().map(|key| ("key", key)).collect();
+        self.data_req(ip, HTTP_REPLICA, data, node, Method::DELETE)
+            .await?;
+        Ok(())
+    }
+
+    async fn load(&self, node: &ChordNode, ip: IpAddr) -> Result<Load, ChordError> {
+        Ok(serde_json::from_str(
+            &self.get_req(ip, HTTP_LOAD, node).await?,
+        )?)
+    }
+
+    async fn view(&self, node: &ChordNode, ip: IpAddr) -> Result<NodeView, ChordError> {
+        Ok(serde_json::from_str(
+            &self.get_req(ip, HTTP_VIEW, node).await?,
+        )?)
+    }
+
+    async fn is_alive(&self, node: &ChordNode, ip: IpAddr) -> bool {
+        let _slot = self.limits.acquire(ip).await;
+        self.request(node, Method::GET, ip, HTTP_SUCCESSOR, String::new())
+            .timeout(Duration::from_secs(node.config().liveness_timeout))
+            .send()
+            .await
+            .is_ok()
+    }
+}
+
+/// Report `ip` to the failure detector and build the `ChordError::Unreachable` returned to the caller.
+pub(crate) fn unreachable(ip: IpAddr, reason: String, chord_node: &ChordNode) -> ChordError {
+    warn!(
+        peer = %ip,
+        "Request to a peer failed, reporting it as a suspect: {}",
+        reason
+    );
+    chord_node.failures.report(ip);
+    ChordError::Unreachable { node: ip, reason }
+}
+
+/// Report `ip` to the failure detector, count the timeout (see `Metrics`) and build the `ChordError::Timeout` returned to the caller.
+pub(crate) fn timed_out(ip: IpAddr, chord_node: &ChordNode) -> ChordError {
+    warn!(peer = %ip, "Request to a peer timed out, reporting it as a suspect");
+    chord_node.failures.report(ip);
+    chord_node.metrics.peer_timed_out();
+    ChordError::Timeout { node: ip }
+}
+
+/// Turns a failed request to `ip` into a `Timeout` or `Unreachable` error.
+fn request_failed(ip: IpAddr, error: reqwest::Error, chord_node: &ChordNode) -> ChordError {
+    if error.is_timeout() {
+        timed_out(ip, chord_node)
+    } else {
+        unreachable(ip, error.to_string(), chord_node)
+    }
+}
+
+/// Mark a request as failed if the server response is not 200, mapping the `ErrorBody` sent by `ip` back to a `ChordError`.
+async fn request_unsuccessful(
+    response: Response,
+    ip: IpAddr,
+    method: &Method,
+) -> Result<String, ChordError> {
+    let status = response.status();
+    // nodes that predate `ErrorBody` only name the node they couldn't reach in `SUSPECT_HEADER`.
+    let suspect = response
+        .headers()
+        .get(SUSPECT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let text = response.text().await.map_err(|e| {
+        ChordError::Internal(format!(
+            "Couldn't read the reply of {} to a {}: {}",
+            ip, method, e
+        ))
+    })?;
+    if status == StatusCode::OK {
+        return Ok(text);
+    }
+    match (ChordError::from_response(status, &text), suspect) {
+        (ChordError::Internal(reason), Some(node)) => Err(ChordError::Unreachable { node, reason }),
+        (error, _) => Err(error),
+    }
+}
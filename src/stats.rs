@@ -0,0 +1,116 @@
+use serde_derive::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// What a node reports about its own load (GET /peer/load/, or the `Load` RPC), see `ChordNode::load`.
+/// keys, replicas - how many keys the node owns, and how many it keeps as replicas for other nodes.
+/// requests_per_sec - the requests served on its client API a second, over the last minute.
+/// key_ops_per_sec - the inserts, lookups and deletes it served as the owner of the key a second, over the last minute, whichever node they came through.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Load {
+    pub keys: u64,
+    pub replicas: u64,
+    pub requests_per_sec: f64,
+    pub key_ops_per_sec: f64,
+}
+
+/// One node of the ring in `RingStats`.
+/// range - the IDs the node owns, from the ID of the node before it on the ring (excluded) to its own ID (included), as an `Interval`. `range_width` is how many IDs that is, and `share` which fraction of the ring.
+/// load - what the node reported about itself, or `None` if it couldn't be asked (see `error`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeStats {
+    pub node: IpAddr,
+    pub id: u64,
+    pub range: String,
+    pub range_width: u64,
+    pub share: f64,
+    pub load: Option<Load>,
+    pub error: Option<String>,
+}
+
+/// How evenly something is spread over the nodes that reported it. `max_to_mean` is 1 when every node has the same, and grows as one node gets more than its share. `cv` (the coefficient of variation, `stddev / mean`) is 0 when every node has the same. Both are 0 when the mean is.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Spread {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub max_to_mean: f64,
+    pub cv: f64,
+}
+
+impl Spread {
+    pub fn of(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Spread {
+                min: 0.0,
+                max: 0.0,
+                mean: 0.0,
+                stddev: 0.0,
+                max_to_mean: 0.0,
+                cv: 0.0,
+            };
+        }
+        let n = values.len() as f64;
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        let stddev = variance.sqrt();
+        let ratio = |value: f64| if mean > 0.0 { value / mean } else { 0.0 };
+        Spread {
+            min,
+            max,
+            mean,
+            stddev,
+            max_to_mean: ratio(max),
+            cv: ratio(stddev),
+        }
+    }
+}
+
+/// The imbalance of the ring in `RingStats`. The loads only count the nodes that reported theirs (`reporting`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub nodes: usize,
+    pub reporting: usize,
+    pub keys: u64,
+    pub range_width: Spread,
+    pub keys_per_node: Spread,
+    pub replicas_per_node: Spread,
+    pub requests_per_sec: Spread,
+    pub key_ops_per_sec: Spread,
+}
+
+/// How the ring and its load are spread over its nodes (GET /v1/ring/stats), see `ChordNode::ring_stats`. `nodes` are in ring order, starting with the node that was asked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RingStats {
+    pub ring_size: u64,
+    pub nodes: Vec<NodeStats>,
+    pub summary: Summary,
+}
+
+impl RingStats {
+    pub fn new(ring_size: u64, nodes: Vec<NodeStats>) -> Self {
+        let widths: Vec<f64> = nodes.iter().map(|node| node.range_width as f64).collect();
+        let loads: Vec<&Load> = nodes.iter().filter_map(|node| node.load.as_ref()).collect();
+        let spread = |value: fn(&Load) -> f64| {
+            let values: Vec<f64> = loads.iter().map(|load| value(load)).collect();
+            Spread::of(&values)
+        };
+        let summary = Summary {
+            nodes: nodes.len(),
+            reporting: loads.len(),
+            keys: loads.iter().map(|load| load.keys).sum(),
+            range_width: Spread::of(&widths),
+            keys_per_node: spread(|load| load.keys as f64),
+            replicas_per_node: spread(|load| load.replicas as f64),
+            requests_per_sec: spread(|load| load.requests_per_sec),
+            key_ops_per_sec: spread(|load| load.key_ops_per_sec),
+        };
+        RingStats {
+            ring_size,
+            nodes,
+            summary,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const STATE_FILE: &str = "state.json";
+
+/// The part of a `ChordNode` that survives a restart.
+/// incarnation - incremented every time the node starts with an existing state file. Neighbours use this to tell a recovering node apart from a stale message sent by its previous incarnation.
+/// hash_set and replica_set - the keys this node held when it last persisted its state.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct PersistedState {
+    pub incarnation: u64,
+    pub hash_set: HashSet<String>,
+    pub replica_set: HashSet<String>,
+}
+
+/// Reads and writes `PersistedState` as JSON in a data directory.
+/// Writes go to a temporary file that is then renamed over the old one, so a crash in the middle of a write never leaves a half-written state behind. The `Mutex` makes sure two handlers don't write the temporary file at the same time.
+#[derive(Clone)]
+pub struct Storage {
+    dir: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl Storage {
+    pub fn new(dir: &str) -> Self {
+        Storage {
+            dir: PathBuf::from(dir),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// returns the state persisted by a previous run, or `None` if this node never ran with this data directory.
+    pub fn load(&self) -> Option<PersistedState> {
+        let path = self.dir.join(STATE_FILE);
+        let contents = fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                warn!("Ignoring corrupt state file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, state: &PersistedState) -> io::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        fs::create_dir_all(&self.dir)?;
+        let tmp = self.dir.join(format!("{}.tmp", STATE_FILE));
+        fs::write(&tmp, serde_json::to_vec(state)?)?;
+        fs::rename(&tmp, self.dir.join(STATE_FILE))
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+}
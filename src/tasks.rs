@@ -0,0 +1,227 @@
+use crate::ChordError;
+use gotham_derive::StateData;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+const BACKOFF_MAX: u64 = 30; // a failing task never waits longer than this (in seconds) between two rounds.
+const SHUTDOWN_GRACE: u64 = 5; // seconds to wait for running rounds to finish on shutdown.
+
+/// What a supervised task is doing right now.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TaskState {
+    Sleeping,
+    Running,
+    BackingOff,
+    Paused,
+    Stopped,
+}
+
+/// Bookkeeping for one supervised task.
+struct TaskStatus {
+    state: TaskState,
+    interval: Duration,
+    rounds: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    last_success: Option<Instant>,
+    last_duration: Option<Duration>,
+}
+
+/// A snapshot of `TaskStatus`, as returned by `GET /tasks/`.
+#[derive(Serialize)]
+pub struct TaskReport {
+    name: &'static str,
+    state: TaskState,
+    interval_secs: u64,
+    rounds: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    secs_since_last_success: Option<u64>,
+    last_duration_ms: Option<u128>,
+}
+
+/// Runs the periodic maintenance tasks of a node (stabilize, fix fingers, ...) on the current tokio runtime.
+/// Each task runs one round every `interval`. A round that fails is retried with exponential backoff (capped at `BACKOFF_MAX` seconds) instead of right away, and every task stops at its next await point once `shutdown()` is called.
+/// While the supervisor is paused (see `pause()`), tasks skip their rounds until it is resumed. A round that is already running when it is paused still finishes.
+/// Like `ChordNode`, this is cloned into every request's `State`, so all shared fields are wrapped in `Arc`. The shutdown and pause channels are behind a `Mutex` too, because gotham requires `State` data to be unwind safe.
+#[derive(Clone, StateData)]
+pub struct Supervisor {
+    tasks: Arc<Mutex<BTreeMap<&'static str, TaskStatus>>>,
+    handles: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    shutdown_tx: Arc<Mutex<watch::Sender<bool>>>,
+    shutdown_rx: Arc<Mutex<watch::Receiver<bool>>>,
+    pause_tx: Arc<Mutex<watch::Sender<bool>>>,
+    pause_rx: Arc<Mutex<watch::Receiver<bool>>>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (pause_tx, pause_rx) = watch::channel(false);
+        Supervisor {
+            tasks: Arc::new(Mutex::new(BTreeMap::new())),
+            handles: Arc::new(Mutex::new(Vec::new())),
+            shutdown_tx: Arc::new(Mutex::new(shutdown_tx)),
+            shutdown_rx: Arc::new(Mutex::new(shutdown_rx)),
+            pause_tx: Arc::new(Mutex::new(pause_tx)),
+            pause_rx: Arc::new(Mutex::new(pause_rx)),
+        }
+    }
+
+    /// Spawns `round` as a supervised task called `name`, running once every `interval`.
+    pub fn spawn<F, Fut>(&self, name: &'static str, interval: Duration, round: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), ChordError>> + Send,
+    {
+        self.tasks.lock().unwrap().insert(
+            name,
+            TaskStatus {
+                state: TaskState::Sleeping,
+                interval,
+                rounds: 0,
+                failures: 0,
+                consecutive_failures: 0,
+                last_error: None,
+                last_success: None,
+                last_duration: None,
+            },
+        );
+        let supervisor = self.clone();
+        let mut shutdown = self.shutdown_rx.lock().unwrap().clone();
+        let mut paused = self.pause_rx.lock().unwrap().clone();
+        let handle = tokio::spawn(async move {
+            'rounds: loop {
+                let wait = supervisor.next_wait(name);
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = shutdown.changed() => break,
+                }
+                while *paused.borrow_and_update() {
+                    supervisor.set_state(name, TaskState::Paused);
+                    tokio::select! {
+                        _ = paused.changed() => {}
+                        _ = shutdown.changed() => break 'rounds,
+                    }
+                }
+                supervisor.set_state(name, TaskState::Running);
+                let started = Instant::now();
+                let result = tokio::select! {
+                    result = round() => result,
+                    _ = shutdown.changed() => break,
+                };
+                supervisor.record(name, started.elapsed(), result);
+            }
+            supervisor.set_state(name, TaskState::Stopped);
+            debug!(task = name, "Task stopped");
+        });
+        self.handles.lock().unwrap().push(handle);
+    }
+
+    /// Asks every task to stop and waits (up to `SHUTDOWN_GRACE` seconds) for them to do so.
+    pub async fn shutdown(&self) {
+        info!("Stopping maintenance tasks...");
+        let _ = self.shutdown_tx.lock().unwrap().send(true);
+        let handles: Vec<JoinHandle<()>> = self.handles.lock().unwrap().drain(..).collect();
+        for handle in handles {
+            let _ = tokio::time::timeout(Duration::from_secs(SHUTDOWN_GRACE), handle).await;
+        }
+    }
+
+    /// Stops every task from starting new rounds until `resume()` is called.
+    pub fn pause(&self) {
+        info!("Pausing maintenance tasks");
+        let _ = self.pause_tx.lock().unwrap().send(true);
+    }
+
+    /// Lets every task run its rounds again after `pause()`. A task that missed rounds while paused runs one right away.
+    pub fn resume(&self) {
+        info!("Resuming maintenance tasks");
+        let _ = self.pause_tx.lock().unwrap().send(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.pause_rx.lock().unwrap().borrow()
+    }
+
+    /// returns a serialized list of every task and its status.
+    pub fn report(&self) -> String {
+        let tasks = self.tasks.lock().unwrap();
+        let reports: Vec<TaskReport> = tasks
+            .iter()
+            .map(|(name, status)| TaskReport {
+                name,
+                state: status.state,
+                interval_secs: status.interval.as_secs(),
+                rounds: status.rounds,
+                failures: status.failures,
+                consecutive_failures: status.consecutive_failures,
+                last_error: status.last_error.clone(),
+                secs_since_last_success: status.last_success.map(|t| t.elapsed().as_secs()),
+                last_duration_ms: status.last_duration.map(|d| d.as_millis()),
+            })
+            .collect();
+        serde_json::to_string_pretty(&reports).expect("Can't serialize tasks")
+    }
+
+    /// returns how long task `name` should sleep before its next round: its interval, doubled for every consecutive failure.
+    fn next_wait(&self, name: &'static str) -> Duration {
+        let mut tasks = self.tasks.lock().unwrap();
+        let status = tasks.get_mut(name).unwrap();
+        if status.consecutive_failures == 0 {
+            status.state = TaskState::Sleeping;
+            return status.interval;
+        }
+        status.state = TaskState::BackingOff;
+        let factor = 2u32.saturating_pow(status.consecutive_failures.min(16));
+        std::cmp::min(
+            status.interval.saturating_mul(factor),
+            Duration::from_secs(BACKOFF_MAX),
+        )
+    }
+
+    fn set_state(&self, name: &'static str, state: TaskState) {
+        if let Some(status) = self.tasks.lock().unwrap().get_mut(name) {
+            status.state = state;
+        }
+    }
+
+    fn record(&self, name: &'static str, duration: Duration, result: Result<(), ChordError>) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let status = tasks.get_mut(name).unwrap();
+        status.rounds += 1;
+        status.last_duration = Some(duration);
+        match result {
+            Ok(()) => {
+                status.consecutive_failures = 0;
+                status.last_success = Some(Instant::now());
+            }
+            Err(e) => {
+                status.failures += 1;
+                status.consecutive_failures += 1;
+                status.last_error = Some(e.to_string());
+                warn!(
+                    task = name,
+                    "Task failed {} time(s) in a row, backing off: {}",
+                    status.consecutive_failures,
+                    e
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use crate::tls::{TlsConfig, TLS_SERVER_NAME};
+use crate::ChordError;
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::x509::extension::{
+    AuthorityKeyIdentifier, BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAlternativeName,
+    SubjectKeyIdentifier,
+};
+use openssl::x509::{X509Name, X509};
+use std::fs;
+use std::path::Path;
+
+const VALIDITY_DAYS: u32 = 365;
+
+// file names `write_to` uses.
+pub const CA_FILE: &str = "ca.pem";
+pub const CA_KEY_FILE: &str = "ca-key.pem";
+pub const CERT_FILE: &str = "node.pem";
+pub const KEY_FILE: &str = "node-key.pem";
+
+/// A throwaway cluster CA and a node certificate signed by it, to try TLS out locally and for tests. Every node of a test cluster can use the same certificate: it's valid for `TLS_SERVER_NAME`, `localhost`, `127.0.0.1` and `::1`.
+/// Keys are P-256 and stored as PKCS#8 PEM. Keep `ca_key` away from the nodes: only the CA needs it, to sign more certificates.
+pub struct TestCa {
+    pub ca: Vec<u8>,
+    pub ca_key: Vec<u8>,
+    pub cert: Vec<u8>,
+    pub key: Vec<u8>,
+}
+
+impl TestCa {
+    pub fn generate() -> Result<Self, ChordError> {
+        Self::build().map_err(|e| ChordError::Internal(format!("Can't generate a test CA: {}", e)))
+    }
+
+    fn build() -> Result<Self, ErrorStack> {
+        let ca_key = new_key()?;
+        let ca = {
+            let mut builder = certificate_builder("crust test CA", &ca_key)?;
+            builder.set_issuer_name(&*name("crust test CA")?)?;
+            builder.append_extension(BasicConstraints::new().critical().ca().build()?)?;
+            builder.append_extension(
+                KeyUsage::new()
+                    .critical()
+                    .key_cert_sign()
+                    .crl_sign()
+                    .build()?,
+            )?;
+            let key_id = SubjectKeyIdentifier::new().build(&builder.x509v3_context(None, None))?;
+            builder.append_extension(key_id)?;
+            builder.sign(&ca_key, MessageDigest::sha256())?;
+            builder.build()
+        };
+
+        let key = new_key()?;
+        let mut builder = certificate_builder(TLS_SERVER_NAME, &key)?;
+        builder.set_issuer_name(ca.subject_name())?;
+        builder.append_extension(BasicConstraints::new().critical().build()?)?;
+        builder.append_extension(
+            KeyUsage::new()
+                .critical()
+                .digital_signature()
+                .key_agreement()
+                .build()?,
+        )?;
+        builder.append_extension(
+            ExtendedKeyUsage::new()
+                .server_auth()
+                .client_auth()
+                .build()?,
+        )?;
+        let context = builder.x509v3_context(Some(&ca), None);
+        let names = SubjectAlternativeName::new()
+            .dns(TLS_SERVER_NAME)
+            .dns("localhost")
+            .ip("127.0.0.1")
+            .ip("::1")
+            .build(&context)?;
+        let authority = AuthorityKeyIdentifier::new().keyid(true).build(&context)?;
+        builder.append_extension(names)?;
+        builder.append_extension(authority)?;
+        builder.sign(&ca_key, MessageDigest::sha256())?;
+        let cert = builder.build();
+
+        Ok(TestCa {
+            ca: ca.to_pem()?,
+            ca_key: ca_key.private_key_to_pem_pkcs8()?,
+            cert: cert.to_pem()?,
+            key: key.private_key_to_pem_pkcs8()?,
+        })
+    }
+
+    /// Writes the CA, its key, the node certificate and its key to `CA_FILE`, `CA_KEY_FILE`, `CERT_FILE` and `KEY_FILE` in `dir`.
+    pub fn write_to(&self, dir: &Path) -> Result<(), ChordError> {
+        fs::create_dir_all(dir).map_err(|e| write_failed(dir, e))?;
+        for (file, pem) in &[
+            (CA_FILE, &self.ca),
+            (CA_KEY_FILE, &self.ca_key),
+            (CERT_FILE, &self.cert),
+            (KEY_FILE, &self.key),
+        ] {
+            fs::write(dir.join(file), pem).map_err(|e| write_failed(dir, e))?;
+        }
+        Ok(())
+    }
+
+    /// returns the TLS configuration of a node using the certificate of this CA.
+    pub fn tls_config(&self) -> TlsConfig {
+        TlsConfig::from_pem(self.cert.clone(), self.key.clone(), self.ca.clone())
+            .expect("A generated test CA is always valid")
+    }
+}
+
+fn write_failed(dir: &Path, error: std::io::Error) -> ChordError {
+    ChordError::Internal(format!("Can't write to {}: {}", dir.display(), error))
+}
+
+fn new_key() -> Result<PKey<Private>, ErrorStack> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    PKey::from_ec_key(EcKey::generate(&group)?)
+}
+
+fn name(common_name: &str) -> Result<X509Name, ErrorStack> {
+    let mut name = X509Name::builder()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, common_name)?;
+    Ok(name.build())
+}
+
+/// A v3 certificate for `key`, valid from now on for `VALIDITY_DAYS`, with a random serial number.
+fn certificate_builder(
+    common_name: &str,
+    key: &PKey<Private>,
+) -> Result<openssl::x509::X509Builder, ErrorStack> {
+    let mut builder = X509::builder()?;
+    builder.set_version(2)?;
+    let mut serial = BigNum::new()?;
+    serial.rand(128, MsbOption::MAYBE_ZERO, false)?;
+    builder.set_serial_number(&*serial.to_asn1_integer()?)?;
+    builder.set_subject_name(&*name(common_name)?)?;
+    builder.set_pubkey(key)?;
+    builder.set_not_before(&*Asn1Time::days_from_now(0)?)?;
+    builder.set_not_after(&*Asn1Time::days_from_now(VALIDITY_DAYS)?)?;
+    Ok(builder)
+}
@@ -0,0 +1,214 @@
+use crate::ChordError;
+use gotham::handler::NewHandler;
+use gotham::hyper::server::conn::Http;
+use gotham::hyper::service::service_fn;
+use gotham::rustls::internal::pemfile;
+use gotham::rustls::{AllowAnyAnonymousOrAuthenticatedClient, Certificate, PrivateKey};
+use gotham::rustls::{RootCertStore, ServerConfig, Session};
+use gotham::service::call_handler;
+use gotham::state::State;
+use gotham_derive::StateData;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::PKey;
+use openssl::stack::Stack;
+use openssl::x509::X509;
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::{env, fs, io};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, warn};
+
+/// Environment variables with the paths of the PEM files TLS is configured with. TLS is enabled when all three are set.
+pub const TLS_CERT_ENV: &str = "CRUST_TLS_CERT"; // this node's certificate, followed by any intermediate certificates.
+pub const TLS_KEY_ENV: &str = "CRUST_TLS_KEY"; // the private key of the certificate, PKCS#8 or RSA.
+pub const TLS_CA_ENV: &str = "CRUST_TLS_CA"; // the cluster CA, which signs the certificate of every node.
+
+/// Peers are dialed by IP address, which changes from one run to the next, so node certificates aren't tied to one. Instead, every node certificate carries this DNS name, and it is the name a node expects when it connects to a peer over gRPC.
+pub const TLS_SERVER_NAME: &str = "crust";
+
+/// The certificates and key of this node, and the CA of its cluster.
+/// With TLS, both listeners only speak TLS: the gRPC peer port requires a client certificate signed by the cluster CA (mutual TLS), while the HTTP API accepts clients without one (browsers), but only serves its peer endpoints to clients that present one (see `TlsClient`). Outgoing requests to peers present this node's certificate and only trust the cluster CA.
+#[derive(Clone)]
+pub struct TlsConfig {
+    cert: Vec<u8>,
+    key: Vec<u8>,
+    ca: Vec<u8>,
+    /// `cert` and `key` bundled as PKCS#12, which is how `reqwest` takes a client identity.
+    identity: Vec<u8>,
+}
+
+impl TlsConfig {
+    /// Loads the files named by `TLS_CERT_ENV`, `TLS_KEY_ENV` and `TLS_CA_ENV`. Returns `None` if none of them are set, and an error if only some of them are.
+    pub fn from_env() -> Result<Option<Self>, ChordError> {
+        let paths: Vec<Option<String>> = [TLS_CERT_ENV, TLS_KEY_ENV, TLS_CA_ENV]
+            .iter()
+            .map(|name| env::var(name).ok())
+            .collect();
+        match paths.as_slice() {
+            [Some(cert), Some(key), Some(ca)] => Self::from_files(cert, key, ca).map(Some),
+            [None, None, None] => Ok(None),
+            _ => Err(ChordError::Internal(format!(
+                "TLS needs all of {}, {} and {} to be set",
+                TLS_CERT_ENV, TLS_KEY_ENV, TLS_CA_ENV
+            ))),
+        }
+    }
+
+    /// Loads a certificate chain, its private key and the cluster CA from PEM files, checking that each of them can be used.
+    pub fn from_files(cert: &str, key: &str, ca: &str) -> Result<Self, ChordError> {
+        Self::from_pem(read(cert)?, read(key)?, read(ca)?)
+    }
+
+    pub fn from_pem(cert: Vec<u8>, key: Vec<u8>, ca: Vec<u8>) -> Result<Self, ChordError> {
+        if certificates(&cert).is_empty() {
+            return Err(invalid("No certificate found in the certificate file"));
+        }
+        if private_key(&key).is_none() {
+            return Err(invalid(
+                "No PKCS#8 or RSA private key found in the key file",
+            ));
+        }
+        if roots(&ca).is_empty() {
+            return Err(invalid("No certificate found in the CA file"));
+        }
+        let identity = pkcs12(&cert, &key).map_err(invalid)?;
+        reqwest::Identity::from_pkcs12_der(&identity, "").map_err(invalid)?;
+        reqwest::Certificate::from_pem(&ca).map_err(invalid)?;
+        Ok(TlsConfig {
+            cert,
+            key,
+            ca,
+            identity,
+        })
+    }
+
+    /// The configuration of the HTTP listener: client certificates are optional, but if one is presented it has to be signed by the cluster CA.
+    pub fn server_config(&self) -> ServerConfig {
+        let mut config =
+            ServerConfig::new(AllowAnyAnonymousOrAuthenticatedClient::new(roots(&self.ca)));
+        config
+            .set_single_cert(certificates(&self.cert), private_key(&self.key).unwrap())
+            .expect("Checked when the TLS configuration was loaded");
+        config
+    }
+
+    /// The configuration of the gRPC listener, which only accepts peers with a certificate signed by the cluster CA.
+    pub fn grpc_server_config(&self) -> tonic::transport::ServerTlsConfig {
+        tonic::transport::ServerTlsConfig::new()
+            .identity(tonic::transport::Identity::from_pem(&self.cert, &self.key))
+            .client_ca_root(tonic::transport::Certificate::from_pem(&self.ca))
+    }
+
+    /// The configuration of gRPC channels to peers: present this node's certificate, and expect one for `TLS_SERVER_NAME` signed by the cluster CA.
+    pub fn grpc_client_config(&self) -> tonic::transport::ClientTlsConfig {
+        tonic::transport::ClientTlsConfig::new()
+            .domain_name(TLS_SERVER_NAME)
+            .ca_certificate(tonic::transport::Certificate::from_pem(&self.ca))
+            .identity(tonic::transport::Identity::from_pem(&self.cert, &self.key))
+    }
+
+    /// Configures an HTTP client to present this node's certificate and to trust nothing but the cluster CA. The address a peer is dialed at isn't checked against its certificate (see `TLS_SERVER_NAME`): being signed by the cluster CA is what makes it a peer.
+    pub fn configure_client(&self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        builder
+            .use_native_tls()
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(
+                reqwest::Certificate::from_pem(&self.ca)
+                    .expect("Checked when the TLS configuration was loaded"),
+            )
+            .identity(
+                reqwest::Identity::from_pkcs12_der(&self.identity, "")
+                    .expect("Checked when the TLS configuration was loaded"),
+            )
+            .danger_accept_invalid_hostnames(true)
+    }
+}
+
+/// Put in the `State` of every request served over TLS. `authenticated` is true if the client presented a certificate signed by the cluster CA, which is what makes it a peer.
+#[derive(StateData, Clone, Copy)]
+pub struct TlsClient {
+    pub authenticated: bool,
+}
+
+/// Serves `new_handler` over TLS on `addr`, like `gotham::tls::init_server`, but also tells the handlers whether the client is a peer (see `TlsClient`).
+pub async fn serve_https<NH>(addr: SocketAddr, new_handler: NH, tls: &TlsConfig) -> io::Result<()>
+where
+    NH: NewHandler + 'static,
+{
+    let listener = TcpListener::bind(addr).await?;
+    let acceptor = TlsAcceptor::from(Arc::new(tls.server_config()));
+    let new_handler = Arc::new(new_handler);
+    loop {
+        let (socket, client_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Couldn't accept a connection: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let new_handler = new_handler.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(socket).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!(client = %client_addr.ip(), "TLS handshake failed: {}", e);
+                    return;
+                }
+            };
+            let client = TlsClient {
+                authenticated: stream.get_ref().1.get_peer_certificates().is_some(),
+            };
+            let service = service_fn(move |req| {
+                let mut state = State::from_request(req, client_addr);
+                state.put(client);
+                call_handler(new_handler.clone(), AssertUnwindSafe(state))
+            });
+            // like gotham, ignore connections that break off.
+            let _ = Http::new().serve_connection(stream, service).await;
+        });
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, ChordError> {
+    fs::read(path).map_err(|e| ChordError::Internal(format!("Can't read {}: {}", path, e)))
+}
+
+fn invalid(error: impl std::fmt::Display) -> ChordError {
+    ChordError::Internal(format!("Invalid TLS configuration: {}", error))
+}
+
+fn certificates(pem: &[u8]) -> Vec<Certificate> {
+    pemfile::certs(&mut &pem[..]).unwrap_or_default()
+}
+
+fn private_key(pem: &[u8]) -> Option<PrivateKey> {
+    let pkcs8 = pemfile::pkcs8_private_keys(&mut &pem[..]).unwrap_or_default();
+    let rsa = pemfile::rsa_private_keys(&mut &pem[..]).unwrap_or_default();
+    pkcs8.into_iter().chain(rsa).next()
+}
+
+fn roots(pem: &[u8]) -> RootCertStore {
+    let mut store = RootCertStore::empty();
+    let _ = store.add_pem_file(&mut &pem[..]);
+    store
+}
+
+/// Bundles a PEM certificate chain and its key as PKCS#12, with an empty password.
+fn pkcs12(cert: &[u8], key: &[u8]) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let mut chain = X509::stack_from_pem(cert)?.into_iter();
+    let leaf = chain.next().expect("Checked by the caller");
+    let mut intermediates = Stack::new()?;
+    for cert in chain {
+        intermediates.push(cert)?;
+    }
+    Pkcs12::builder()
+        .name(TLS_SERVER_NAME)
+        .pkey(&*PKey::private_key_from_pem(key)?)
+        .cert(&leaf)
+        .ca(intermediates)
+        .build2("")?
+        .to_der()
+}
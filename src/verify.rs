@@ -0,0 +1,262 @@
+use crate::{get_identifier, ring_size, ChordError};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+
+/// What a node tells the node verifying the ring about itself (GET /peer/view/, or the `View` RPC), see `ChordNode::view`: its pointers, its finger table (the `start` of each finger and the node it points to), and the keys it owns and keeps as replicas.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeView {
+    pub successor: IpAddr,
+    pub predecessor: IpAddr,
+    pub successor_list: Vec<IpAddr>,
+    pub fingers: Vec<Finger>,
+    pub keys: Vec<String>,
+    pub replicas: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finger {
+    pub start: u64,
+    pub node: IpAddr,
+}
+
+impl NodeView {
+    /// returns every node this node points to.
+    pub(crate) fn neighbours(&self) -> Vec<IpAddr> {
+        let mut nodes = vec![self.successor, self.predecessor];
+        nodes.extend(&self.successor_list);
+        nodes.extend(self.fingers.iter().map(|finger| finger.node));
+        nodes
+    }
+}
+
+/// Something wrong with the ring, as found by `check`. Every violation has a `kind` (its name in snake_case, in JSON) and names the node it was found on.
+/// Unreachable - a node that another node points to didn't answer. It's left out of the ring the others are checked against.
+/// PointersDisagree - the predecessor of the successor of `node` isn't `node`.
+/// WrongSuccessor, WrongPredecessor - a pointer of `node` isn't the next (or previous) node of the ring.
+/// WrongFinger - finger `index` of `node` doesn't point to the successor of its `start`.
+/// KeyNotOnOwner - the owner of `key` (the successor of its ID) doesn't have it among its keys. `found_on` are the nodes that have it, as keys or replicas.
+/// MisplacedKey - `node` has `key` among its keys, but doesn't own it.
+/// MissingReplicas - the owner of `key` has it, but not every node of its successor list has it as a replica. `missing` are the ones that don't.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Violation {
+    Unreachable {
+        node: IpAddr,
+        error: String,
+    },
+    PointersDisagree {
+        node: IpAddr,
+        successor: IpAddr,
+        successors_predecessor: IpAddr,
+    },
+    WrongSuccessor {
+        node: IpAddr,
+        found: IpAddr,
+        expected: IpAddr,
+    },
+    WrongPredecessor {
+        node: IpAddr,
+        found: IpAddr,
+        expected: IpAddr,
+    },
+    WrongFinger {
+        node: IpAddr,
+        index: usize,
+        start: u64,
+        found: IpAddr,
+        expected: IpAddr,
+    },
+    KeyNotOnOwner {
+        node: IpAddr,
+        key: String,
+        key_id: u64,
+        found_on: Vec<IpAddr>,
+    },
+    MisplacedKey {
+        node: IpAddr,
+        key: String,
+        key_id: u64,
+        owner: IpAddr,
+    },
+    MissingReplicas {
+        node: IpAddr,
+        key: String,
+        expected: usize,
+        found: usize,
+        missing: Vec<IpAddr>,
+    },
+}
+
+/// The result of verifying the ring (GET /v1/ring/verify/), see `ChordNode::verify_ring`.
+/// nodes - the nodes that answered, in the order of their IDs, which is the ring everything is checked against.
+/// keys - how many different keys were found on them.
+/// healthy - whether there are no `violations`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RingCheck {
+    pub nodes: Vec<IpAddr>,
+    pub keys: usize,
+    pub healthy: bool,
+    pub violations: Vec<Violation>,
+}
+
+/// The nodes that answered, sorted by ID.
+struct Ring {
+    nodes: Vec<(u64, IpAddr)>,
+}
+
+impl Ring {
+    /// returns the node responsible for `id`: the first one at or after it.
+    fn successor_of(&self, id: u64) -> IpAddr {
+        self.nodes
+            .iter()
+            .find(|(node_id, _)| *node_id >= id)
+            .unwrap_or(&self.nodes[0])
+            .1
+    }
+
+    /// returns the nodes that should keep the replicas of the keys `owner` owns: its successor list (see `build_successor_list`), which starts at the successor of its successor and is `count` nodes long, without `owner` and without going around the ring more than once.
+    fn replica_holders(&self, owner: IpAddr, count: usize) -> Vec<IpAddr> {
+        let i = self.position(owner);
+        let mut holders = Vec::new();
+        for step in 2..count + 2 {
+            let node = self.nodes[(i + step) % self.nodes.len()].1;
+            if node != owner && !holders.contains(&node) {
+                holders.push(node);
+            }
+        }
+        holders
+    }
+
+    fn before(&self, ip: IpAddr) -> IpAddr {
+        let i = self.position(ip);
+        self.nodes[(i + self.nodes.len() - 1) % self.nodes.len()].1
+    }
+
+    fn position(&self, ip: IpAddr) -> usize {
+        self.nodes.iter().position(|(_, node)| *node == ip).unwrap()
+    }
+}
+
+/// Checks the `views` of every node of a ring (and the errors of the ones that didn't answer) against each other: the pointers and fingers of every node, where every key is and how many replicas it has, with the replicas of a key on the successor list of its owner.
+pub(crate) fn check(
+    views: &BTreeMap<IpAddr, Result<NodeView, ChordError>>,
+    replication_factor: u32,
+) -> RingCheck {
+    let mut violations = Vec::new();
+    let mut answered = BTreeMap::new();
+    for (ip, view) in views {
+        match view {
+            Ok(view) => {
+                answered.insert(*ip, view);
+            }
+            Err(e) => violations.push(Violation::Unreachable {
+                node: *ip,
+                error: e.to_string(),
+            }),
+        }
+    }
+    let mut nodes: Vec<(u64, IpAddr)> = answered
+        .keys()
+        .map(|ip| (get_identifier(&ip.to_string()), *ip))
+        .collect();
+    nodes.sort_unstable();
+    let ring = Ring { nodes };
+
+    for (id, ip) in &ring.nodes {
+        let view = answered[ip];
+        let successor = ring.successor_of((id + 1) % ring_size());
+        if view.successor != successor {
+            violations.push(Violation::WrongSuccessor {
+                node: *ip,
+                found: view.successor,
+                expected: successor,
+            });
+        }
+        let predecessor = ring.before(*ip);
+        if view.predecessor != predecessor {
+            violations.push(Violation::WrongPredecessor {
+                node: *ip,
+                found: view.predecessor,
+                expected: predecessor,
+            });
+        }
+        if let Some(successor_view) = answered.get(&view.successor) {
+            if successor_view.predecessor != *ip {
+                violations.push(Violation::PointersDisagree {
+                    node: *ip,
+                    successor: view.successor,
+                    successors_predecessor: successor_view.predecessor,
+                });
+            }
+        }
+        for (index, finger) in view.fingers.iter().enumerate() {
+            let expected = ring.successor_of(finger.start);
+            if finger.node != expected {
+                violations.push(Violation::WrongFinger {
+                    node: *ip,
+                    index,
+                    start: finger.start,
+                    found: finger.node,
+                    expected,
+                });
+            }
+        }
+    }
+
+    // every key, with the nodes that have it as a key and as a replica.
+    let mut keys: BTreeMap<&str, (Vec<IpAddr>, BTreeSet<IpAddr>)> = BTreeMap::new();
+    for (ip, view) in &answered {
+        for key in &view.keys {
+            keys.entry(key).or_default().0.push(*ip);
+        }
+        for key in &view.replicas {
+            keys.entry(key).or_default().1.insert(*ip);
+        }
+    }
+    let replicas = replication_factor as usize;
+    for (key, (owners, holders)) in &keys {
+        let key_id = get_identifier(key);
+        let owner = ring.successor_of(key_id);
+        for node in owners.iter().filter(|node| **node != owner) {
+            violations.push(Violation::MisplacedKey {
+                node: *node,
+                key: key.to_string(),
+                key_id,
+                owner,
+            });
+        }
+        if !owners.contains(&owner) {
+            let found_on: BTreeSet<IpAddr> = owners.iter().chain(holders).cloned().collect();
+            violations.push(Violation::KeyNotOnOwner {
+                node: owner,
+                key: key.to_string(),
+                key_id,
+                found_on: found_on.into_iter().collect(),
+            });
+            continue;
+        }
+        let expected = ring.replica_holders(owner, replicas);
+        let missing: Vec<IpAddr> = expected
+            .iter()
+            .filter(|node| !holders.contains(node))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            violations.push(Violation::MissingReplicas {
+                node: owner,
+                key: key.to_string(),
+                expected: expected.len(),
+                found: expected.len() - missing.len(),
+                missing,
+            });
+        }
+    }
+
+    RingCheck {
+        nodes: ring.nodes.iter().map(|(_, ip)| *ip).collect(),
+        keys: keys.len(),
+        healthy: violations.is_empty(),
+        violations,
+    }
+}
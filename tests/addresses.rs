@@ -0,0 +1,100 @@
+//! Parses the bind and advertise addresses of a node, and talks to a peer at an IPv6 address.
+
+use clap::Parser;
+use crust::{create_ring, serve_grpc, Cli, Command, GrpcTransport, MemoryNetwork, Options};
+use crust::{
+    parse_ip, parse_seed, ChordError, Config, PersistedState, Security, Storage, Transport,
+};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Parses `crust serve <line>`.
+fn serve(line: &str) -> Result<Options, clap::Error> {
+    let args = ["crust", "serve"]
+        .iter()
+        .copied()
+        .chain(line.split_whitespace());
+    Cli::try_parse_from(args).map(|cli| match cli.command {
+        Command::Serve(options) => options,
+        command => panic!("not serve: {:?}", command),
+    })
+}
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+fn seed(value: &str) -> SocketAddr {
+    value.parse().unwrap()
+}
+
+#[test]
+fn seeds_and_addresses_can_be_ipv4_or_ipv6() {
+    let options = serve(
+        "--bind :: --advertise [2001:db8::1] --join 10.0.0.1 [2001:db8::2] fe80::3 [2001:db8::4]:9000",
+    )
+    .unwrap();
+    assert_eq!(options.bind, Some(ip("::")));
+    assert_eq!(options.advertise, Some(ip("2001:db8::1")));
+    assert_eq!(
+        options.seeds,
+        vec![
+            seed("10.0.0.1:8000"),
+            seed("[2001:db8::2]:8000"),
+            seed("[fe80::3]:8000"),
+            seed("[2001:db8::4]:9000"),
+        ]
+    );
+    let config = options.config(|_| None).unwrap();
+    assert_eq!(config.advertised_ip().unwrap(), ip("2001:db8::1"));
+}
+
+#[test]
+fn a_specific_bind_address_is_advertised() {
+    let options = serve("--create --bind 10.0.0.7").unwrap();
+    let config = options.config(|_| None).unwrap();
+    assert_eq!(config.advertised_ip().unwrap(), ip("10.0.0.7"));
+    assert!(options.seeds.is_empty());
+}
+
+#[test]
+fn invalid_addresses_are_rejected() {
+    assert!(serve("--create --bind").is_err());
+    assert!(serve("--create --advertise 10.0.0").is_err());
+    assert!(serve("--join 10.0.0.1 not-an-address").is_err());
+    assert!(serve("--join 10.0.0.1:http").is_err());
+    for value in &["10.0.0", "[::1", "not-an-address"] {
+        let error = parse_ip(value).unwrap_err();
+        assert!(matches!(error, ChordError::BadRequest(_)), "{:?}", error);
+        let error = parse_seed(value).unwrap_err();
+        assert!(matches!(error, ChordError::BadRequest(_)), "{:?}", error);
+    }
+}
+
+#[tokio::test]
+async fn peers_can_be_reached_over_ipv6() {
+    let server = IpAddr::V6(Ipv6Addr::LOCALHOST);
+    let dir = std::env::temp_dir().join(format!("crust-addresses-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let node = create_ring(
+        server,
+        PersistedState::default(),
+        Config::default(),
+        Storage::new(dir.to_str().unwrap()),
+        Arc::new(MemoryNetwork::new()),
+    );
+    let caller = node.clone();
+    tokio::spawn(async move { serve_grpc(node, server, &Security::default()).await });
+
+    let peer = GrpcTransport::new();
+    let mut successor = None;
+    for _ in 0..50 {
+        if let Ok(ip) = peer.get_successor(&caller, server).await {
+            successor = Some(ip);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(successor, Some(server));
+}
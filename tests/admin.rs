@@ -0,0 +1,154 @@
+//! Runs maintenance on demand, over a `MemoryNetwork` and through the admin API of a `crust` process.
+
+mod common;
+
+use common::{addresses, create_node, join_node};
+use crust::ADMIN_TOKEN_ENV;
+use crust::{ChordError, ChordNode, Config, Maintenance, MemoryNetwork, Supervisor};
+use serde_json::Value;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn two_nodes(network: &MemoryNetwork) -> (ChordNode, ChordNode) {
+    let ips = addresses(48, 2);
+    let first = create_node(network, ips[0], Config::default());
+    let second = join_node(network, ips[1], ips[0], Config::default()).await;
+    (first, second)
+}
+
+#[tokio::test]
+async fn maintenance_run_on_demand_returns_what_it_changed() {
+    let network = MemoryNetwork::new();
+    let (first, second) = two_nodes(&network).await;
+
+    let diff = second.run_now(Maintenance::SuccessorList).await;
+    assert_eq!(diff.action, "successor_list");
+    assert_eq!(diff.error, None);
+    let list = &diff.changes["successor_list"];
+    assert_eq!(list.before, serde_json::json!([]));
+    assert!(!list.after.as_array().unwrap().is_empty());
+    assert_eq!(diff.changes.len(), 1, "{:?}", diff.changes);
+
+    let diff = second.run_now(Maintenance::Stabilize).await;
+    assert_eq!(diff.error, None);
+    assert_eq!(first.get_predecessor(), second.self_ip());
+
+    second.run_now(Maintenance::Fingers).await;
+    let diff = second.run_now(Maintenance::Fingers).await;
+    assert_eq!(diff.action, "fix_every_finger");
+    assert!(diff.changes.is_empty(), "{:?}", diff.changes);
+    let diff = second.run_now(Maintenance::finger(0).unwrap()).await;
+    assert_eq!(diff.action, "fix_finger 0");
+    assert!(diff.changes.is_empty(), "{:?}", diff.changes);
+    assert!(matches!(
+        Maintenance::finger(64),
+        Err(ChordError::BadRequest(_))
+    ));
+
+    first.run_now(Maintenance::Stabilize).await;
+    assert_eq!(first.get_successor(), second.self_ip());
+    network.crash(second.self_ip());
+    let diff = first.run_now(Maintenance::Stabilize).await;
+    assert!(diff.error.is_some());
+    // the failed round repaired the pointers of the first node, which is alone again.
+    let alone = first.self_ip().to_string();
+    assert_eq!(diff.changes["successor"].after, alone);
+    assert_eq!(diff.changes["predecessor"].after, alone);
+}
+
+#[tokio::test]
+async fn paused_tasks_skip_their_rounds() {
+    let supervisor = Supervisor::new();
+    let rounds = Arc::new(AtomicU32::new(0));
+    let counted = rounds.clone();
+    supervisor.spawn("count", Duration::from_millis(10), move || {
+        counted.fetch_add(1, Ordering::SeqCst);
+        async { Ok(()) }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(rounds.load(Ordering::SeqCst) > 0);
+
+    supervisor.pause();
+    assert!(supervisor.is_paused());
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let paused_at = rounds.load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(rounds.load(Ordering::SeqCst), paused_at);
+    assert!(supervisor.report().contains("\"paused\""));
+
+    supervisor.resume();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(rounds.load(Ordering::SeqCst) > paused_at);
+    supervisor.shutdown().await;
+}
+
+/// A node, killed when it's dropped.
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// returns the status and body of `POST path` on the client port of 127.0.52.1 with `token`, once it answers.
+async fn post(path: &str, token: Option<&str>) -> (u16, Value) {
+    let url = format!("http://127.0.52.1:8000{}", path);
+    let client = reqwest::Client::new();
+    for _ in 0..100 {
+        let mut request = client.post(&url);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        if let Ok(resp) = request.send().await {
+            let status = resp.status().as_u16();
+            return (status, resp.json().await.unwrap_or(Value::Null));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("{} doesn't answer", url);
+}
+
+#[tokio::test]
+async fn the_admin_api_takes_the_admin_token() {
+    let dir = std::env::temp_dir().join(format!("crust-admin-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let child = Command::new(env!("CARGO_BIN_EXE_crust"))
+        .args(["serve", "--create", "--bind", "127.0.52.1", "--data-dir"])
+        .arg(&dir)
+        .env(ADMIN_TOKEN_ENV, "sudo")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let _node = Process(child);
+
+    let (status, error) = post("/v1/admin/pause", None).await;
+    assert_eq!(status, 401);
+    assert_eq!(error["code"], "unauthorized");
+    assert_eq!(post("/v1/admin/pause", Some("guess")).await.0, 401);
+
+    let (status, diff) = post("/v1/admin/pause", Some("sudo")).await;
+    assert_eq!(status, 200);
+    assert_eq!(diff["action"], "pause");
+    assert_eq!(diff["changes"]["maintenance_paused"]["before"], false);
+    assert_eq!(diff["changes"]["maintenance_paused"]["after"], true);
+
+    let (status, diff) = post("/v1/admin/stabilize", Some("sudo")).await;
+    assert_eq!(status, 200);
+    assert_eq!(diff["action"], "stabilize");
+    assert_eq!(diff["error"], Value::Null);
+    let (status, diff) = post("/v1/admin/fingers/2", Some("sudo")).await;
+    assert_eq!(status, 200);
+    assert_eq!(diff["action"], "fix_finger 2");
+    let (status, error) = post("/v1/admin/fingers/99", Some("sudo")).await;
+    assert_eq!(status, 400);
+    assert_eq!(error["code"], "bad_request");
+
+    let (status, diff) = post("/v1/admin/resume", Some("sudo")).await;
+    assert_eq!(status, 200);
+    assert_eq!(diff["changes"]["maintenance_paused"]["after"], false);
+}
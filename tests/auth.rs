@@ -0,0 +1,143 @@
+//! Signs and verifies peer requests with a cluster secret, directly and over gRPC.
+
+use crust::{create_ring, serve_grpc, ChordError, ClusterSecret, GrpcTransport, MemoryNetwork};
+use crust::{Audience, Config, PersistedState, Security, Storage, Transport};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn node(ip: IpAddr) -> crust::ChordNode {
+    let dir = std::env::temp_dir().join(format!("crust-auth-{}-{}", std::process::id(), ip));
+    let _ = std::fs::remove_dir_all(&dir);
+    create_ring(
+        ip,
+        PersistedState::default(),
+        Config::default(),
+        Storage::new(dir.to_str().unwrap()),
+        Arc::new(MemoryNetwork::new()),
+    )
+}
+
+fn unauthorized(result: Result<(), ChordError>) -> bool {
+    matches!(result, Err(ChordError::Unauthorized(_)))
+}
+
+#[test]
+fn signed_requests_are_accepted_once() {
+    let secret = ClusterSecret::new(b"hunter2");
+    let signature = secret.sign("PATCH", "/notify/", b"n=10.0.0.1");
+    assert!(secret
+        .verify("PATCH", "/notify/", b"n=10.0.0.1", Some(signature.clone()))
+        .is_ok());
+    assert!(unauthorized(secret.verify(
+        "PATCH",
+        "/notify/",
+        b"n=10.0.0.1",
+        Some(signature)
+    )));
+}
+
+#[test]
+fn tampered_or_foreign_requests_are_rejected() {
+    let secret = ClusterSecret::new(b"hunter2");
+    let signature = secret.sign("PATCH", "/notify/", b"n=10.0.0.1");
+    for (method, path, body) in &[
+        ("PATCH", "/notify/", &b"n=10.0.0.2"[..]),
+        ("PATCH", "/predecessor/", &b"n=10.0.0.1"[..]),
+        ("POST", "/notify/", &b"n=10.0.0.1"[..]),
+    ] {
+        let result = secret.verify(method, path, body, Some(signature.clone()));
+        assert!(unauthorized(result), "{} {} was accepted", method, path);
+    }
+
+    let stranger = ClusterSecret::new(b"hunter3");
+    let forged = stranger.sign("PATCH", "/notify/", b"n=10.0.0.1");
+    assert!(unauthorized(secret.verify(
+        "PATCH",
+        "/notify/",
+        b"n=10.0.0.1",
+        Some(forged)
+    )));
+    assert!(unauthorized(secret.verify(
+        "PATCH",
+        "/notify/",
+        b"n=10.0.0.1",
+        None
+    )));
+}
+
+#[test]
+fn the_key_api_takes_a_client_token() {
+    let security = Security {
+        client_token: Some("letmein".to_string()),
+        ..Security::default()
+    };
+    assert!(security
+        .authorize(Audience::Client, Some("Bearer letmein"))
+        .is_ok());
+    assert!(unauthorized(
+        security.authorize(Audience::Client, Some("Bearer guess"))
+    ));
+    assert!(unauthorized(
+        security.authorize(Audience::Client, Some("letmein"))
+    ));
+    assert!(unauthorized(security.authorize(Audience::Client, None)));
+    assert!(Security::default()
+        .authorize(Audience::Client, None)
+        .is_ok());
+}
+
+#[test]
+fn the_admin_api_takes_an_admin_token_and_is_off_without_one() {
+    let security = Security {
+        admin_token: Some("sudo".to_string()),
+        client_token: Some("letmein".to_string()),
+        ..Security::default()
+    };
+    assert!(security
+        .authorize(Audience::Admin, Some("Bearer sudo"))
+        .is_ok());
+    assert!(unauthorized(
+        security.authorize(Audience::Admin, Some("Bearer letmein"))
+    ));
+    assert!(unauthorized(security.authorize(Audience::Admin, None)));
+    assert!(matches!(
+        Security::default().authorize(Audience::Admin, Some("Bearer sudo")),
+        Err(ChordError::Forbidden(_))
+    ));
+}
+
+#[tokio::test]
+async fn grpc_peers_have_to_sign_their_requests() {
+    let security = Security {
+        secret: Some(ClusterSecret::new(b"hunter2")),
+        ..Security::default()
+    };
+    let server = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let serving = security.clone();
+    tokio::spawn(async move { serve_grpc(node(server), server, &serving).await });
+    let caller = node(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+
+    let peer = GrpcTransport::with_security(&security);
+    let mut successor = None;
+    for _ in 0..50 {
+        if let Ok(ip) = peer.get_successor(&caller, server).await {
+            successor = Some(ip);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(successor, Some(server));
+
+    let unsigned = GrpcTransport::new();
+    let result = unsigned.get_successor(&caller, server).await;
+    assert!(matches!(result, Err(ChordError::Unauthorized(_))));
+    assert!(unsigned.hello(&caller, server).await.is_ok());
+
+    let stranger = GrpcTransport::with_security(&Security {
+        secret: Some(ClusterSecret::new(b"hunter3")),
+        ..Security::default()
+    });
+    let result = stranger.notify(&caller, server, caller.self_ip()).await;
+    assert!(matches!(result, Err(ChordError::Unauthorized(_))));
+}
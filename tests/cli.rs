@@ -0,0 +1,137 @@
+//! Parses the command line of the node binary.
+
+use clap::{CommandFactory, Parser};
+use crust::{Cli, ClientOptions, Command, Config, InfoCommand, Intervals, KeyCommand, Options};
+use std::path::PathBuf;
+
+/// Looks up nothing, as if no `CRUST_*` variable was set.
+fn no_env(_: &str) -> Option<String> {
+    None
+}
+
+/// Parses `crust <line>`.
+fn parse(line: &str) -> Result<Options, clap::Error> {
+    let args = std::iter::once("crust").chain(line.split_whitespace());
+    Cli::try_parse_from(args).map(|cli| match cli.command {
+        Command::Serve(options) => options,
+        command => panic!("not serve: {:?}", command),
+    })
+}
+
+#[test]
+fn the_command_line_is_well_formed() {
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn flags_have_defaults() {
+    let options = parse("serve --create").unwrap();
+    assert!(options.create);
+    assert!(options.seeds.is_empty());
+    assert_eq!(options.config(no_env).unwrap(), Config::default());
+}
+
+#[test]
+fn flags_override_the_defaults() {
+    let options = parse(
+        "serve --join 10.0.0.1 --port 9000 --data-dir /var/lib/crust --replication-factor 3 \
+         --stabilize-interval 5 --fix-fingers-interval 6 --successor-list-interval 7 \
+         --replica-sync-interval 30",
+    )
+    .unwrap();
+    assert!(!options.create);
+    assert_eq!(options.seeds, vec!["10.0.0.1:8000".parse().unwrap()]);
+    let config = options.config(no_env).unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.data_dir, PathBuf::from("/var/lib/crust"));
+    assert_eq!(config.replication_factor, 3);
+    assert_eq!(
+        config.intervals,
+        Intervals {
+            stabilize: 5,
+            fix_fingers: 6,
+            successor_list: 7,
+            replica_sync: 30,
+        }
+    );
+}
+
+#[test]
+fn a_node_either_creates_or_joins_a_ring() {
+    assert!(parse("serve").is_err());
+    assert!(parse("serve --create --join 10.0.0.1").is_err());
+    assert!(parse("serve --join").is_err());
+    assert!(parse("").is_err());
+}
+
+#[test]
+fn invalid_values_are_rejected() {
+    assert!(parse("serve --create --port 0").is_err());
+    assert!(parse("serve --create --port 70000").is_err());
+    assert!(parse("serve --create --replication-factor 0").is_err());
+    assert!(parse("serve --create --ring-bits 64").is_err());
+    assert!(parse("serve --create --stabilize-interval 0").is_err());
+    assert!(parse("serve --create --replica-sync-interval soon").is_err());
+    assert!(parse("serve --create --unknown").is_err());
+
+    // valid on their own, but not together.
+    let options = parse("serve --create --replication-factor 64").unwrap();
+    assert!(options.config(no_env).is_err());
+    let options = parse("serve --create --liveness-timeout 5 --request-timeout 2").unwrap();
+    assert!(options.config(no_env).is_err());
+}
+
+/// Parses `crust <line>`, for a client subcommand.
+fn client(line: &str) -> Result<Command, clap::Error> {
+    let args = std::iter::once("crust").chain(line.split_whitespace());
+    Cli::try_parse_from(args).map(|cli| cli.command)
+}
+
+#[test]
+fn client_subcommands_take_a_key() {
+    let options = ClientOptions {
+        node: "10.0.0.1:9000".parse().unwrap(),
+        json: true,
+        token: Some("secret".to_string()),
+        ca: Some(PathBuf::from("ca.pem")),
+    };
+    let expected = KeyCommand {
+        key: "apple".to_string(),
+        client: options.clone(),
+    };
+    let flags = "--node 10.0.0.1:9000 --json --token secret --ca ca.pem";
+    for (name, wrap) in [
+        ("put", Command::Put as fn(KeyCommand) -> Command),
+        ("get", Command::Get),
+        ("delete", Command::Delete),
+        ("lookup", Command::Lookup),
+    ] {
+        let command = client(&format!("{} apple {}", name, flags)).unwrap();
+        assert_eq!(
+            format!("{:?}", command),
+            format!("{:?}", wrap(expected.clone()))
+        );
+        assert!(client(name).is_err(), "{} without a key", name);
+    }
+    match client(&format!("ring {}", flags)).unwrap() {
+        Command::Ring(ring) => assert_eq!(ring, options),
+        command => panic!("not ring: {:?}", command),
+    }
+}
+
+#[test]
+fn info_shows_the_node_it_is_given() {
+    match client("info [2001:db8::1]").unwrap() {
+        Command::Info(InfoCommand { target, client }) => {
+            assert_eq!(target, Some("[2001:db8::1]:8000".parse().unwrap()));
+            assert!(!client.json);
+        }
+        command => panic!("not info: {:?}", command),
+    }
+    match client("info").unwrap() {
+        Command::Info(info) => assert_eq!(info.target, None),
+        command => panic!("not info: {:?}", command),
+    }
+    assert!(client("info localhost").is_err());
+    assert!(client("get apple --node 10.0.0").is_err());
+}
@@ -0,0 +1,237 @@
+//! Runs rings of `crust` processes on loopback addresses, and talks to them through `crust_client`, and as a peer over HTTP.
+
+use crust::{
+    create_ring, Config, HttpTransport, MemoryNetwork, PersistedState, Storage, Transport,
+    PEER_PORT,
+};
+use crust_client::{ChordError, Client, ClientConfig, Location};
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The nodes of a ring, killed when it's dropped.
+struct Ring {
+    nodes: Vec<(IpAddr, Child)>,
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        for (_, child) in self.nodes.iter_mut() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Ring {
+    /// starts a node at each of `ips`: the first one creates the ring, the others join it. Returns once every node is in the ring.
+    async fn start(ips: &[&str]) -> Ring {
+        let mut ring = Ring { nodes: Vec::new() };
+        for ip in ips {
+            let ip: IpAddr = ip.parse().unwrap();
+            let dir =
+                std::env::temp_dir().join(format!("crust-client-{}-{}", std::process::id(), ip));
+            let _ = std::fs::remove_dir_all(&dir);
+            let mut command = Command::new(env!("CARGO_BIN_EXE_crust"));
+            command.args(["serve", "--bind", &ip.to_string(), "--data-dir"]);
+            command.arg(&dir);
+            command.args([
+                "--stabilize-interval",
+                "1",
+                "--successor-list-interval",
+                "1",
+            ]);
+            match ring.nodes.first() {
+                Some((seed, _)) => command.args(["--join", &seed.to_string()]),
+                None => command.arg("--create"),
+            };
+            let child = command
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap();
+            ring.nodes.push((ip, child));
+            connect(&[addr(ip)]).await;
+        }
+        let client = connect(&[addr(ring.nodes[0].0)]).await;
+        for _ in 0..100 {
+            if matches!(client.ring().await, Ok(nodes) if nodes.len() == ips.len()) {
+                return ring;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        panic!("The nodes didn't form a ring");
+    }
+
+    fn kill(&mut self, ip: IpAddr) {
+        let (_, child) = self.nodes.iter_mut().find(|(node, _)| *node == ip).unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
+
+fn addr(ip: IpAddr) -> SocketAddr {
+    SocketAddr::new(ip, 8000)
+}
+
+/// connects to `seeds`, waiting for one of them to be up. Requests are retried for up to a minute: a node whose predecessor crashed doesn't answer for the keys it took over until it notices, which takes a few stabilize rounds.
+async fn connect(seeds: &[SocketAddr]) -> Client {
+    let config = ClientConfig {
+        retries: 30,
+        backoff: Duration::from_millis(200),
+        ..ClientConfig::default()
+    };
+    for _ in 0..100 {
+        if let Ok(client) = Client::connect(seeds, config.clone()).await {
+            return client;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Can't connect to {:?}", seeds);
+}
+
+#[tokio::test]
+async fn keys_go_to_the_nodes_that_own_them() {
+    let ring = Ring::start(&["127.0.44.1", "127.0.44.2", "127.0.44.3"]).await;
+    // the first seed isn't up, so the client has to move on to the second one.
+    let client = connect(&[addr(ip("127.0.44.9")), addr(ring.nodes[1].0)]).await;
+    assert_eq!(client.hello().ring_bits, 6);
+
+    let keys: Vec<String> = (0..10).map(|i| format!("key {}/{}", i, i)).collect();
+    for key in &keys {
+        let node_id = client.put(key).await.unwrap();
+        assert!(client.contains(key).await.unwrap());
+        let location = client.get(key).await.unwrap().unwrap();
+        assert_eq!(location, client.lookup(key).await.unwrap());
+        assert_eq!(location.node_id, node_id);
+        assert_eq!(location.id, client.key_id(key));
+    }
+    for key in &keys {
+        assert!(client.delete(key).await.unwrap());
+        assert!(!client.delete(key).await.unwrap());
+        assert!(!client.contains(key).await.unwrap());
+        assert_eq!(client.get(key).await.unwrap(), None);
+    }
+    let info = client.info(addr(ring.nodes[2].0)).await.unwrap();
+    assert_eq!(info["self_ip"], "127.0.44.3");
+}
+
+#[tokio::test]
+async fn requests_are_retried_while_the_ring_routes_around_a_crash() {
+    let mut ring = Ring::start(&["127.0.45.1", "127.0.45.2", "127.0.45.3"]).await;
+    let client = connect(&[addr(ring.nodes[0].0)]).await;
+    let keys: Vec<String> = (0..10).map(|i| format!("crash-{}", i)).collect();
+    let mut locations: Vec<Location> = Vec::new();
+    for key in &keys {
+        client.put(key).await.unwrap();
+        locations.push(client.lookup(key).await.unwrap());
+    }
+    // let the replicas catch up, then crash a node that owns keys, which the client has cached as their owner.
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let crashed = locations
+        .iter()
+        .map(|location| location.node)
+        .find(|node| *node != ring.nodes[0].0)
+        .expect("The seed owns every key");
+    ring.kill(crashed);
+
+    for key in &keys {
+        assert!(client.contains(key).await.unwrap(), "{} is lost", key);
+        assert_ne!(client.lookup(key).await.unwrap().node, crashed);
+    }
+}
+
+#[tokio::test]
+async fn peers_look_up_keys_with_any_character_over_http() {
+    let ring = Ring::start(&["127.0.43.1"]).await;
+    let server = ring.nodes[0].0;
+    let client = connect(&[addr(server)]).await;
+    let keys = [
+        "a/b",
+        "what?",
+        "c#d",
+        "100%",
+        "%2F",
+        "with spaces",
+        "a/../b",
+    ];
+    for key in &keys {
+        client.put(key).await.unwrap();
+    }
+
+    // a node asking the only node of the ring, over the HTTP peer API.
+    let caller_ip = ip("10.0.43.1");
+    let dir =
+        std::env::temp_dir().join(format!("crust-client-{}-{}", std::process::id(), caller_ip));
+    let _ = std::fs::remove_dir_all(&dir);
+    let caller = create_ring(
+        caller_ip,
+        PersistedState::default(),
+        Config::default(),
+        Storage::new(dir.to_str().unwrap()),
+        Arc::new(MemoryNetwork::new()),
+    );
+    let http = HttpTransport::new();
+    for key in &keys {
+        assert!(
+            http.contains(&caller, server, key).await.unwrap(),
+            "{}",
+            key
+        );
+        assert!(!http
+            .contains(&caller, server, &format!("{}x", key))
+            .await
+            .unwrap());
+        assert!(http.delete(&caller, server, key).await.unwrap(), "{}", key);
+        assert!(!client.contains(key).await.unwrap(), "{}", key);
+    }
+}
+
+#[tokio::test]
+async fn peers_asking_for_ids_off_the_ring_get_a_bad_request() {
+    let ring = Ring::start(&["127.0.46.1"]).await;
+    let server = ring.nodes[0].0;
+    let cpf = |id: &str| format!("http://{}:{}/peer/successor/cpf/{}", server, PEER_PORT, id);
+    let client = reqwest::Client::new();
+    for id in &[
+        "abc",
+        "-1",
+        &(1u64 << 32).to_string(),
+        &u64::MAX.to_string(),
+    ] {
+        let resp = client.get(cpf(id)).send().await.unwrap();
+        assert_eq!(resp.status(), 400, "{}", id);
+    }
+    let resp = client.get(cpf("42")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.text().await.unwrap(), server.to_string());
+}
+
+#[tokio::test]
+async fn seeds_that_dont_say_hello_are_skipped() {
+    let ring = Ring::start(&["127.0.47.1"]).await;
+    // a seed that answers every request, but not with a hello.
+    let impostor = addr(ip("127.0.47.9"));
+    let listener = TcpListener::bind(impostor).unwrap();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let _ = stream.read(&mut [0; 1024]);
+            let response = "HTTP/1.1 200 OK\r\ncontent-length: 5\r\nconnection: close\r\n\r\nhello";
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    let client = connect(&[impostor, addr(ring.nodes[0].0)]).await;
+    assert_eq!(client.hello().ring_bits, 6);
+    let error = Client::connect(&[impostor], ClientConfig::default())
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(error, ChordError::Internal(_)), "{:?}", error);
+}
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
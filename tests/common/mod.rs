@@ -0,0 +1,80 @@
+//! Fixtures shared by the tests that run nodes over a `MemoryNetwork`. Every test file keeps to a subnet of its own (10.0.X.0/24, and 127.0.X.0/24 for nodes on real sockets), so that the nodes and storage of different files never meet.
+// every test binary compiles this module, but none of them uses all of it.
+#![allow(dead_code)]
+
+use crust::{create_ring, get_identifier, join, ChordNode, Config, MemoryNetwork};
+use crust::{PersistedState, Storage};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+/// returns a `Storage` for the node at `ip`, in a temporary directory that starts out empty.
+pub fn storage(ip: IpAddr) -> Storage {
+    let dir = std::env::temp_dir().join(format!("crust-test-{}-{}", std::process::id(), ip));
+    let _ = std::fs::remove_dir_all(&dir);
+    Storage::new(dir.to_str().unwrap())
+}
+
+/// returns `n` addresses in 10.0.`subnet`.0/24 whose Chord IDs are all different.
+pub fn addresses(subnet: u8, n: usize) -> Vec<IpAddr> {
+    let mut ids = HashSet::new();
+    (1..=255)
+        .map(|i| IpAddr::V4(Ipv4Addr::new(10, 0, subnet, i)))
+        .filter(|ip| ids.insert(get_identifier(&ip.to_string())))
+        .take(n)
+        .collect()
+}
+
+/// creates a ring at `ip`, and adds its node to `network`.
+pub fn create_node(network: &MemoryNetwork, ip: IpAddr, config: Config) -> ChordNode {
+    let node = create_ring(
+        ip,
+        PersistedState::default(),
+        config,
+        storage(ip),
+        Arc::new(network.clone()),
+    );
+    network.add(&node);
+    node
+}
+
+/// joins the node at `ip` to the ring through `seed`, and adds it to `network`. No maintenance is run, so only its successor is known.
+pub async fn join_node(
+    network: &MemoryNetwork,
+    ip: IpAddr,
+    seed: IpAddr,
+    config: Config,
+) -> ChordNode {
+    let node = join(
+        ip,
+        seed,
+        PersistedState::default(),
+        config,
+        storage(ip),
+        Arc::new(network.clone()),
+    )
+    .await
+    .unwrap();
+    network.add(&node);
+    node
+}
+
+/// runs `rounds` maintenance rounds on every node of `nodes`, one node after the other.
+pub async fn stabilize(nodes: &[ChordNode], rounds: usize) {
+    for _ in 0..rounds {
+        for node in nodes {
+            let _ = node.maintenance_round().await;
+        }
+    }
+}
+
+/// Starts a ring of the nodes at `ips` on `network`: the first one creates it, and the others join it through the first one, stabilizing the ring after every join. returns the nodes in the order of `ips`, with their successors and predecessors fixed. Their fingers are only as good as joining and maintenance made them.
+pub async fn ring_of(network: &MemoryNetwork, ips: &[IpAddr], config: Config) -> Vec<ChordNode> {
+    let mut nodes = vec![create_node(network, ips[0], config.clone())];
+    for ip in &ips[1..] {
+        nodes.push(join_node(network, *ip, ips[0], config.clone()).await);
+        stabilize(&nodes, 3).await;
+    }
+    stabilize(&nodes, 5).await;
+    nodes
+}
@@ -0,0 +1,224 @@
+//! Builds the configuration of a node from a file, the environment and the command line.
+
+use clap::Parser;
+use crust::{create_ring, Cli, Command, Config, Intervals, LogFormat, Logging, MemoryNetwork};
+use crust::{Options, LOG_FILTER};
+use crust::{PersistedState, Storage, CONFIG_ENV};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Parses `crust serve <line>`.
+fn serve(line: &str) -> Options {
+    let args = ["crust", "serve"]
+        .iter()
+        .copied()
+        .chain(line.split_whitespace());
+    match Cli::try_parse_from(args).unwrap().command {
+        Command::Serve(options) => options,
+        command => panic!("not serve: {:?}", command),
+    }
+}
+
+/// returns an environment with only `vars` set.
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    move |name| vars.get(name).cloned()
+}
+
+/// Writes `contents` to a configuration file for the test called `name`, and returns its path.
+fn config_file(name: &str, contents: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("crust-config-{}-{}.toml", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+#[test]
+fn flags_override_the_environment_which_overrides_the_file() {
+    let file = config_file(
+        "layers",
+        r#"
+        bind = "10.0.0.1"
+        port = 9000
+        data_dir = "/var/lib/crust"
+        request_timeout = 10
+
+        [intervals]
+        stabilize = 5
+        replica_sync = 60
+        "#,
+    );
+    let env = env(&[
+        (CONFIG_ENV, file.to_str().unwrap()),
+        ("CRUST_PORT", "9100"),
+        ("CRUST_REQUEST_TIMEOUT", "20"),
+        ("CRUST_STABILIZE_INTERVAL", "6"),
+        ("CRUST_ADVERTISE", "[2001:db8::1]"),
+    ]);
+    let config = serve("--create --port 9200 --stabilize-interval 7")
+        .config(env)
+        .unwrap();
+    assert_eq!(
+        config,
+        Config {
+            bind: ip("10.0.0.1"),
+            advertise: Some(ip("2001:db8::1")),
+            port: 9200,
+            data_dir: PathBuf::from("/var/lib/crust"),
+            request_timeout: 20,
+            intervals: Intervals {
+                stabilize: 7,
+                replica_sync: 60,
+                ..Intervals::default()
+            },
+            ..Config::default()
+        }
+    );
+}
+
+#[test]
+fn the_config_flag_takes_precedence_over_the_environment() {
+    let from_flag = config_file("flag", "port = 9000");
+    let from_env = config_file("env", "port = 9100");
+    let options = serve(&format!("--create --config {}", from_flag.display()));
+    let config = options
+        .config(env(&[(CONFIG_ENV, from_env.to_str().unwrap())]))
+        .unwrap();
+    assert_eq!(config.port, 9000);
+}
+
+#[test]
+fn invalid_files_are_rejected() {
+    assert!(Config::from_toml("prot = 9000").is_err());
+    assert!(Config::from_toml("port = \"http\"").is_err());
+    assert!(Config::from_toml("port = 70000").is_err());
+    assert!(Config::from_toml("[intervals]\nstabilise = 2").is_err());
+    assert!(Config::from_toml("bind = \"localhost\"").is_err());
+    assert!(Config::from_file(&PathBuf::from("/does/not/exist.toml")).is_err());
+    let options = serve("--create --config /does/not/exist.toml");
+    assert!(options.config(env(&[])).is_err());
+}
+
+#[test]
+fn invalid_environment_variables_are_rejected() {
+    let options = serve("--create");
+    for (name, value) in [
+        ("CRUST_PORT", "http"),
+        ("CRUST_BIND", "10.0.0"),
+        ("CRUST_ADVERTISE", "[2001:db8::1"),
+        ("CRUST_REPLICATION_FACTOR", "-1"),
+    ] {
+        let error = options.config(env(&[(name, value)])).unwrap_err();
+        assert!(error.to_string().contains(name), "{}", error);
+    }
+    // well-formed, but invalid.
+    let zero = env(&[("CRUST_FIX_FINGERS_INTERVAL", "0")]);
+    assert!(options.config(zero).is_err());
+    // empty variables are ignored.
+    assert_eq!(
+        options.config(env(&[("CRUST_PORT", "")])).unwrap(),
+        Config::default()
+    );
+}
+
+#[test]
+fn logging_is_configured_like_the_other_settings() {
+    let file = config_file(
+        "log",
+        r#"
+        [log]
+        filter = "crust=debug"
+        format = "json"
+        "#,
+    );
+    let options = serve(&format!("--create --config {}", file.display()));
+    let config = options.config(env(&[])).unwrap();
+    assert_eq!(
+        config.log,
+        Logging {
+            filter: "crust=debug".to_string(),
+            format: LogFormat::Json,
+        }
+    );
+    let config = options
+        .config(env(&[("CRUST_LOG", "warn"), ("CRUST_LOG_FORMAT", "text")]))
+        .unwrap();
+    assert_eq!(config.log.filter, "warn");
+    assert_eq!(config.log.format, LogFormat::Text);
+    let options = serve("--create --log crust=trace,gotham=warn --log-format json");
+    let config = options.config(env(&[("CRUST_LOG", "warn")])).unwrap();
+    assert_eq!(config.log.filter, "crust=trace,gotham=warn");
+    assert_eq!(config.log.format, LogFormat::Json);
+    assert_eq!(Config::default().log.filter, LOG_FILTER);
+
+    assert!(Config::from_toml("[log]\nformat = \"xml\"").is_err());
+    assert!(Cli::try_parse_from(["crust", "serve", "--create", "--log-format", "xml"]).is_err());
+    let error = serve("--create")
+        .config(env(&[("CRUST_LOG_FORMAT", "xml")]))
+        .unwrap_err();
+    assert!(error.to_string().contains("CRUST_LOG_FORMAT"), "{}", error);
+    let error = serve("--create --log crust=loud")
+        .config(env(&[]))
+        .unwrap_err();
+    assert!(error.to_string().contains("log.filter"), "{}", error);
+}
+
+#[test]
+fn every_invalid_setting_is_reported() {
+    let config = Config {
+        port: 0,
+        ring_bits: 4,
+        replication_factor: 16,
+        liveness_timeout: 5,
+        request_timeout: 2,
+        ..Config::default()
+    };
+    let error = config.validate().unwrap_err().to_string();
+    assert!(error.contains("port"), "{}", error);
+    assert!(error.contains("replication_factor"), "{}", error);
+    assert!(error.contains("liveness_timeout"), "{}", error);
+
+    assert!(Config {
+        ring_bits: 0,
+        ..Config::default()
+    }
+    .validate()
+    .is_err());
+    assert!(Config {
+        ring_bits: 64,
+        ..Config::default()
+    }
+    .validate()
+    .is_err());
+    assert!(Config::default().validate().is_ok());
+}
+
+#[test]
+fn info_shows_the_effective_configuration() {
+    let dir = std::env::temp_dir().join(format!("crust-config-info-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = Config {
+        replication_factor: 3,
+        ..Config::default()
+    };
+    let node = create_ring(
+        ip("10.0.42.1"),
+        PersistedState::default(),
+        config,
+        Storage::new(dir.to_str().unwrap()),
+        Arc::new(MemoryNetwork::new()),
+    );
+    let info: serde_json::Value = serde_json::from_str(&node.info()).unwrap();
+    assert_eq!(info["config"]["replication_factor"], 3);
+    assert_eq!(info["config"]["intervals"]["stabilize"], 2);
+    assert_eq!(info["config"]["advertise"], serde_json::Value::Null);
+}
@@ -0,0 +1,181 @@
+//! Runs the gossip protocol between nodes on loopback addresses, and checks the merge rules of the membership view.
+
+use crust::Config;
+use crust::{create_ring, ChordNode, FailureDetector, Gossip, GossipConfig, MemoryNetwork};
+use crust::{ClusterSecret, MemberStatus, Membership, PersistedState, Security, Storage, Update};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+fn config() -> GossipConfig {
+    GossipConfig {
+        period: Duration::from_millis(200),
+        ping_timeout: Duration::from_millis(50),
+        suspicion_timeout: Duration::from_millis(400),
+        dead_timeout: Duration::from_secs(60),
+        indirect_probes: 2,
+    }
+}
+
+fn node(ip: IpAddr) -> ChordNode {
+    let dir = std::env::temp_dir().join(format!("crust-gossip-{}-{}", std::process::id(), ip));
+    let _ = std::fs::remove_dir_all(&dir);
+    create_ring(
+        ip,
+        PersistedState::default(),
+        Config::default(),
+        Storage::new(dir.to_str().unwrap()),
+        Arc::new(MemoryNetwork::new()),
+    )
+}
+
+/// Starts gossiping for a node at `ip`, and returns it along with the task answering its packets.
+async fn member(
+    ip: IpAddr,
+    security: &Security,
+) -> (ChordNode, Gossip, tokio::task::JoinHandle<()>) {
+    let node = node(ip);
+    let gossip = Gossip::bind(&node, ip, security, config()).await.unwrap();
+    let server = gossip.clone();
+    let handle = tokio::spawn(async move {
+        let _ = server.serve().await;
+    });
+    (node, gossip, handle)
+}
+
+/// Runs probe rounds on every gossip until `done` holds, or fails after `rounds` of them.
+async fn gossip_until(gossips: &[&Gossip], rounds: usize, done: impl Fn() -> bool) {
+    for _ in 0..rounds {
+        for gossip in gossips {
+            gossip.probe_round().await.unwrap();
+        }
+        if done() {
+            return;
+        }
+    }
+    panic!("The members didn't converge in {} rounds", rounds);
+}
+
+#[tokio::test]
+async fn members_learn_about_each_other_and_agree_on_failures() {
+    let (a_ip, b_ip, c_ip) = (ip("127.0.40.1"), ip("127.0.40.2"), ip("127.0.40.3"));
+    let security = Security::default();
+    let (a, a_gossip, _a) = member(a_ip, &security).await;
+    let (b, b_gossip, _b) = member(b_ip, &security).await;
+    let (c, c_gossip, c_server) = member(c_ip, &security).await;
+    // a only knows b, and b only knows c: everyone else is learned through gossip.
+    a.membership().learn(b_ip);
+    b.membership().learn(c_ip);
+
+    let everyone_alive = || {
+        [(&a, b_ip, c_ip), (&b, a_ip, c_ip), (&c, a_ip, b_ip)]
+            .iter()
+            .all(|(node, x, y)| {
+                node.membership().status(*x) == Some(MemberStatus::Alive)
+                    && node.membership().status(*y) == Some(MemberStatus::Alive)
+            })
+    };
+    gossip_until(&[&a_gossip, &b_gossip, &c_gossip], 20, everyone_alive).await;
+
+    c_server.abort();
+    let c_is_dead = || {
+        [&a, &b]
+            .iter()
+            .all(|node| node.membership().status(c_ip) == Some(MemberStatus::Dead))
+    };
+    gossip_until(&[&a_gossip, &b_gossip], 30, c_is_dead).await;
+    assert_eq!(a.membership().status(b_ip), Some(MemberStatus::Alive));
+    let info: serde_json::Value = serde_json::from_str(&a.info()).unwrap();
+    assert_eq!(info["suspects"][0][0], c_ip.to_string());
+}
+
+#[tokio::test]
+async fn packets_signed_with_another_secret_are_ignored() {
+    let (a_ip, b_ip) = (ip("127.0.41.1"), ip("127.0.41.2"));
+    let with_secret = |secret: &[u8]| Security {
+        secret: Some(ClusterSecret::new(secret)),
+        ..Security::default()
+    };
+    let (a, a_gossip, _a) = member(a_ip, &with_secret(b"one secret")).await;
+    let (b, b_gossip, _b) = member(b_ip, &with_secret(b"another secret")).await;
+    a.membership().learn(b_ip);
+    b.membership().learn(a_ip);
+
+    gossip_until(&[&a_gossip, &b_gossip], 30, || {
+        a.membership().status(b_ip) == Some(MemberStatus::Dead)
+            && b.membership().status(a_ip) == Some(MemberStatus::Dead)
+    })
+    .await;
+}
+
+#[test]
+fn suspicions_about_this_node_are_refuted() {
+    let me = ip("10.0.0.1");
+    let membership = Membership::new(me, 1, FailureDetector::new());
+    let incarnation = membership.incarnation();
+    membership.apply(Update {
+        node: me,
+        status: MemberStatus::Suspect,
+        incarnation,
+    });
+    assert!(membership.incarnation() > incarnation);
+    assert!(membership.piggyback(8).contains(&Update {
+        node: me,
+        status: MemberStatus::Alive,
+        incarnation: membership.incarnation(),
+    }));
+    assert_eq!(membership.status(me), None);
+}
+
+#[test]
+fn only_newer_updates_change_the_view() {
+    let failures = FailureDetector::new();
+    let membership = Membership::new(ip("10.0.0.1"), 1, failures.clone());
+    let other = ip("10.0.0.2");
+    let update = |status, incarnation| Update {
+        node: other,
+        status,
+        incarnation,
+    };
+    assert!(membership.apply(update(MemberStatus::Alive, 5)));
+    assert!(!membership.apply(update(MemberStatus::Alive, 5)));
+    assert!(!membership.apply(update(MemberStatus::Suspect, 4)));
+    assert!(membership.apply(update(MemberStatus::Suspect, 5)));
+    assert!(!membership.apply(update(MemberStatus::Alive, 5)));
+
+    assert!(membership.apply(update(MemberStatus::Dead, 5)));
+    assert!(failures.is_suspected(other));
+    assert!(!membership.apply(update(MemberStatus::Alive, 5)));
+    assert!(membership.apply(update(MemberStatus::Alive, 6)));
+    assert!(!failures.is_suspected(other));
+}
+
+#[test]
+fn dead_members_are_forgotten_after_a_while() {
+    let failures = FailureDetector::new();
+    let membership = Membership::new(ip("10.0.0.1"), 1, failures.clone());
+    let (dead, alive) = (ip("10.0.0.2"), ip("10.0.0.3"));
+    membership.learn(alive);
+    membership.apply(Update {
+        node: dead,
+        status: MemberStatus::Dead,
+        incarnation: 0,
+    });
+    assert!(failures.is_suspected(dead));
+
+    assert!(membership.prune_dead(Duration::from_secs(60)).is_empty());
+    assert_eq!(membership.status(dead), Some(MemberStatus::Dead));
+
+    assert_eq!(membership.prune_dead(Duration::ZERO), vec![dead]);
+    assert_eq!(membership.status(dead), None);
+    assert!(!failures.is_suspected(dead));
+    assert!(!membership
+        .piggyback(8)
+        .iter()
+        .any(|update| update.node == dead));
+    assert_eq!(membership.status(alive), Some(MemberStatus::Alive));
+}
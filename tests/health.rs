@@ -0,0 +1,112 @@
+//! Checks when nodes report themselves ready, over a `MemoryNetwork` and as `crust` processes.
+
+mod common;
+
+use common::{addresses, create_node, join_node, stabilize};
+use crust::{Config, MemoryNetwork};
+use serde_json::Value;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+#[tokio::test]
+async fn nodes_are_ready_once_they_know_their_neighbours() {
+    let network = MemoryNetwork::new();
+    let ips = addresses(47, 2);
+    let first = create_node(&network, ips[0], Config::default());
+    // alone in its ring, the first node is its own successor and predecessor.
+    assert!(first.readiness().await.ready);
+
+    let second = join_node(&network, ips[1], ips[0], Config::default()).await;
+    let nodes = [first.clone(), second.clone()];
+    let readiness = second.readiness().await;
+    assert!(!readiness.ready);
+    assert!(readiness.joined);
+    assert!(readiness.successor_reachable);
+    assert!(!readiness.predecessor_known);
+    assert!(!readiness.keys_transferred);
+    assert_eq!(readiness.reasons.len(), 2, "{:?}", readiness.reasons);
+
+    stabilize(&nodes, 3).await;
+    for i in 0..10 {
+        first.insert(format!("key-{}", i)).await.unwrap();
+    }
+    stabilize(&nodes, 1).await;
+    for node in [&first, &second] {
+        let readiness = node.readiness().await;
+        assert!(readiness.ready, "{:?}", readiness);
+        assert!(readiness.reasons.is_empty());
+    }
+
+    network.cut(ips[0], ips[1]);
+    let readiness = first.readiness().await;
+    assert!(!readiness.ready);
+    assert!(!readiness.successor_reachable);
+    assert!(
+        readiness.reasons[0].contains(&ips[1].to_string()),
+        "{:?}",
+        readiness
+    );
+}
+
+/// A node, killed when it's dropped.
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn serve(ip: &str, ring: &[&str]) -> Process {
+    let dir = std::env::temp_dir().join(format!("crust-health-{}-{}", std::process::id(), ip));
+    let _ = std::fs::remove_dir_all(&dir);
+    let child = Command::new(env!("CARGO_BIN_EXE_crust"))
+        .args(["serve", "--bind", ip, "--data-dir"])
+        .arg(&dir)
+        .args(["--stabilize-interval", "1"])
+        .args(ring)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    Process(child)
+}
+
+/// returns the status and body of `GET path` on the client port of `ip`, once it answers.
+async fn get(ip: &str, path: &str) -> (u16, Value) {
+    let url = format!("http://{}:8000{}", ip, path);
+    for _ in 0..100 {
+        if let Ok(resp) = reqwest::get(&url).await {
+            let status = resp.status().as_u16();
+            return (status, resp.json().await.unwrap_or(Value::Null));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("{} doesn't answer", url);
+}
+
+#[tokio::test]
+async fn a_joining_node_is_alive_but_not_ready() {
+    // the seed isn't up yet, so the node keeps trying to join.
+    let _joining = serve("127.0.51.2", &["--join", "127.0.51.1"]);
+    let alive = serde_json::json!({"alive": true});
+    assert_eq!(get("127.0.51.2", "/healthz").await, (200, alive));
+    let (status, readiness) = get("127.0.51.2", "/readyz").await;
+    assert_eq!(status, 503);
+    assert_eq!(readiness["joined"], false);
+    assert_eq!(readiness["reasons"][0], "Joining the ring");
+    assert_eq!(get("127.0.51.2", "/v1/info/").await.0, 404);
+
+    let _seed = serve("127.0.51.1", &["--create"]);
+    for _ in 0..100 {
+        let (status, readiness) = get("127.0.51.2", "/readyz").await;
+        if status == 200 {
+            assert_eq!(readiness["ready"], true);
+            assert_eq!(get("127.0.51.2", "/v1/info/").await.0, 200);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(300)).await;
+    }
+    panic!("The node never got ready");
+}
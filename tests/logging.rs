@@ -0,0 +1,98 @@
+//! Runs a `crust` process that logs JSON, and reads its log.
+
+use crust::identifier;
+use serde_json::Value;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// A node, killed when it's dropped, and the lines it logged.
+struct Node {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Node {
+    /// returns the first event logged from now on that `matches`, failing after 20 seconds.
+    fn find(&self, matches: impl Fn(&Value) -> bool) -> Value {
+        loop {
+            let line = self
+                .lines
+                .recv_timeout(Duration::from_secs(20))
+                .expect("The node didn't log what it should have");
+            let event: Value = serde_json::from_str(&line)
+                .unwrap_or_else(|e| panic!("Not JSON ({}): {}", e, line));
+            if matches(&event) {
+                return event;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn events_are_logged_as_json_with_the_fields_of_their_spans() {
+    let dir = std::env::temp_dir().join(format!("crust-logging-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_crust"))
+        .args(["serve", "--create", "--bind", "127.0.48.1", "--data-dir"])
+        .arg(&dir)
+        .args(["--log-format", "json", "--log", "crust=debug,warn"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let (sender, lines) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let node = Node { child, lines };
+
+    let started = node.find(|event| event["message"] == "Starting node");
+    assert_eq!(started["level"], "INFO");
+    assert_eq!(started["ip"], "127.0.48.1");
+    let node_id = started["node_id"].as_u64().unwrap();
+    assert!(started["timestamp"].is_string());
+    node.find(|event| {
+        event["message"]
+            .as_str()
+            .is_some_and(|message| message.starts_with("Listening for requests"))
+    });
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post("http://127.0.48.1:8000/v1/key/")
+        .form(&[("key", "apple")])
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let key_id = identifier("apple", 6);
+    let inserting = node.find(|event| event["message"] == "Inserting a key");
+    assert_eq!(inserting["level"], "DEBUG");
+    assert_eq!(inserting["key_id"], key_id);
+    assert_eq!(inserting["span"]["name"], "request");
+    let served = node.find(|event| event["message"] == "Served");
+    assert_eq!(served["status"], 200);
+    let request = &served["span"];
+    assert_eq!(request["listener"], "client");
+    assert_eq!(request["method"], "POST");
+    assert_eq!(request["path"], "/v1/key/");
+    assert_eq!(request["node_id"], node_id);
+    assert_eq!(request["key_id"], key_id);
+    assert!(request["request_id"].is_string());
+    assert_eq!(served["spans"][0], *request);
+}
@@ -0,0 +1,138 @@
+//! Counts what nodes of a ring over a `MemoryNetwork` do, and renders it for Prometheus.
+
+mod common;
+
+use common::{addresses, create_node, join_node, stabilize};
+use crust::{route_label, Config, MemoryNetwork, Metrics};
+use std::time::Duration;
+
+/// returns the value of the series `series` (a name with its labels) in `text`.
+fn value(text: &str, series: &str) -> f64 {
+    text.lines()
+        .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("no {} in:\n{}", series, text))
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn nodes_count_lookups_maintenance_and_failures() {
+    let network = MemoryNetwork::new();
+    let ips = addresses(43, 3);
+    let mut nodes = vec![create_node(&network, ips[0], Config::default())];
+    for ip in &ips[1..] {
+        nodes.push(join_node(&network, *ip, ips[0], Config::default()).await);
+    }
+    stabilize(&nodes, 3).await;
+
+    let node = &nodes[0];
+    let before = value(&node.render_metrics(), "crust_lookups_total");
+    for i in 0..10 {
+        node.insert(format!("key-{}", i)).await.unwrap();
+    }
+    let text = node.render_metrics();
+    assert_eq!(value(&text, "crust_lookups_total"), before + 10.0);
+    assert!(value(&text, "crust_lookup_hops_count") >= 10.0);
+    stabilize(&nodes, 1).await;
+    let text = node.render_metrics();
+    let owned: f64 = nodes
+        .iter()
+        .map(|node| value(&node.render_metrics(), "crust_owned_keys"))
+        .sum();
+    assert_eq!(owned, 10.0);
+    assert!(value(&text, "crust_replica_keys") > 0.0);
+    assert_eq!(value(&text, "crust_successor_list_length"), 6.0);
+    assert_eq!(
+        value(
+            &text,
+            "crust_task_duration_seconds_count{task=\"stabilize\"}"
+        ),
+        4.0
+    );
+    assert_eq!(
+        value(&text, "crust_task_errors_total{task=\"fix_fingers\"}"),
+        0.0
+    );
+    assert_eq!(value(&text, "crust_handle_failure_total"), 0.0);
+
+    // the successor of the first node crashes: its next stabilize round fails and repairs its pointers.
+    let successor = nodes
+        .iter()
+        .find(|other| other.self_ip() == node.get_successor())
+        .unwrap();
+    network.crash(successor.self_ip());
+    let _ = node.maintenance_round().await;
+    let text = node.render_metrics();
+    assert!(value(&text, "crust_task_errors_total{task=\"stabilize\"}") >= 1.0);
+    assert!(value(&text, "crust_handle_failure_total") >= 1.0);
+}
+
+#[test]
+fn metrics_are_in_the_prometheus_text_format() {
+    let metrics = Metrics::new();
+    metrics.lookup_started();
+    metrics.lookup_done(2);
+    metrics.peer_timed_out();
+    let elapsed = Duration::from_millis(30);
+    metrics.request_served("client", "GET", "/v1/key/apple/", elapsed);
+    metrics.request_served("client", "GET", "/v1/key/pear", elapsed);
+    metrics.request_served("peer", "GET", "/peer/successor/cpf/12/", elapsed);
+    let text = metrics.render(&[("crust_owned_keys", "Keys.", 3.0)]);
+
+    assert_eq!(value(&text, "crust_lookup_hops_bucket{le=\"1\"}"), 0.0);
+    assert_eq!(value(&text, "crust_lookup_hops_bucket{le=\"2\"}"), 1.0);
+    assert_eq!(value(&text, "crust_lookup_hops_bucket{le=\"+Inf\"}"), 1.0);
+    assert_eq!(value(&text, "crust_lookup_hops_sum"), 2.0);
+    assert_eq!(value(&text, "crust_peer_timeouts_total"), 1.0);
+    let key = "listener=\"client\",method=\"GET\",route=\"/v1/key/:key\"";
+    assert_eq!(
+        value(
+            &text,
+            &format!("crust_http_request_duration_seconds_count{{{}}}", key)
+        ),
+        2.0
+    );
+    assert_eq!(
+        value(
+            &text,
+            &format!(
+                "crust_http_request_duration_seconds_bucket{{{},le=\"0.025\"}}",
+                key
+            )
+        ),
+        0.0
+    );
+    assert_eq!(
+        value(
+            &text,
+            &format!(
+                "crust_http_request_duration_seconds_bucket{{{},le=\"0.05\"}}",
+                key
+            )
+        ),
+        2.0
+    );
+    assert!(text.contains("route=\"/peer/successor/cpf/:id\""));
+    assert_eq!(value(&text, "crust_owned_keys"), 3.0);
+    // every metric is introduced by its help and type.
+    for line in text.lines().filter(|line| !line.starts_with('#')) {
+        let name = line.split(['{', ' ']).next().unwrap();
+        let family = ["_bucket", "_sum", "_count"]
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix))
+            .filter(|family| text.contains(&format!("# TYPE {} histogram", family)))
+            .unwrap_or(name);
+        assert!(text.contains(&format!("# HELP {} ", family)), "{}", line);
+        assert!(text.contains(&format!("# TYPE {} ", family)), "{}", line);
+    }
+
+    assert_eq!(route_label("/"), "/");
+    assert_eq!(route_label("/v1/lookup/42/"), "/v1/lookup/:id");
+    assert_eq!(route_label("/peer/key/"), "/peer/key");
+    assert_eq!(route_label("/peer/key/key"), "/peer/key/:key");
+    assert_eq!(
+        route_label("/v1/admin/fingers/3"),
+        "/v1/admin/fingers/:index"
+    );
+    assert_eq!(route_label("/v1/admin/fingers"), "/v1/admin/fingers");
+}
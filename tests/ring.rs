@@ -0,0 +1,367 @@
+//! Runs whole rings in a single process over a `MemoryNetwork`.
+
+use crust::{create_ring, get_identifier, join, ChordNode, MemoryNetwork, PersistedState};
+use crust::{local_hello, ChordError, Config, Hello, Storage, PROTOCOL_VERSION};
+use crust::{Bracket, Interval, Maintenance, Transport, REPLICATION_FACTOR, RING_BITS};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+/// returns `n` addresses whose Chord IDs are all different.
+fn addresses(n: usize) -> Vec<IpAddr> {
+    let mut ids = HashSet::new();
+    (1..=255)
+        .map(|i| IpAddr::V4(Ipv4Addr::new(10, 0, 0, i)))
+        .filter(|ip| ids.insert(get_identifier(&ip.to_string())))
+        .take(n)
+        .collect()
+}
+
+/// every node gets its own data directory, so that tests running in parallel don't share state files.
+fn storage(test: &str, ip: IpAddr) -> Storage {
+    let dir = std::env::temp_dir().join(format!("crust-{}-{}-{}", test, std::process::id(), ip));
+    let _ = std::fs::remove_dir_all(&dir);
+    Storage::new(dir.to_str().unwrap())
+}
+
+fn first_incarnation() -> PersistedState {
+    PersistedState {
+        incarnation: 1,
+        ..PersistedState::default()
+    }
+}
+
+/// Starts a ring of `n` nodes on `network`, all joining through the first one.
+async fn start_ring(test: &str, network: &MemoryNetwork, n: usize) -> Vec<ChordNode> {
+    let ips = addresses(n);
+    let first = create_ring(
+        ips[0],
+        first_incarnation(),
+        Config::default(),
+        storage(test, ips[0]),
+        Arc::new(network.clone()),
+    );
+    network.add(&first);
+    let mut nodes = vec![first];
+    for ip in &ips[1..] {
+        let node = join(
+            *ip,
+            ips[0],
+            first_incarnation(),
+            Config::default(),
+            storage(test, *ip),
+            Arc::new(network.clone()),
+        )
+        .await
+        .expect("join failed");
+        network.add(&node);
+        nodes.push(node);
+        stabilize(&nodes, 3).await;
+    }
+    nodes
+}
+
+/// Runs `rounds` rounds of maintenance on every node.
+async fn stabilize(nodes: &[ChordNode], rounds: usize) {
+    for _ in 0..rounds {
+        for node in nodes {
+            let _ = node.maintenance_round().await;
+        }
+    }
+}
+
+/// asserts that following successor pointers visits `nodes` in the order of their IDs, and that predecessors point the other way.
+fn assert_ring(nodes: &[ChordNode]) {
+    let mut sorted: Vec<&ChordNode> = nodes.iter().collect();
+    sorted.sort_by_key(|node| get_identifier(&node.self_ip().to_string()));
+    for (i, node) in sorted.iter().enumerate() {
+        let next = sorted[(i + 1) % sorted.len()];
+        assert_eq!(node.get_successor(), next.self_ip());
+        assert_eq!(next.get_predecessor(), node.self_ip());
+    }
+}
+
+#[tokio::test]
+async fn nodes_joining_one_by_one_form_a_ring() {
+    let network = MemoryNetwork::new();
+    let nodes = start_ring("join", &network, 6).await;
+    stabilize(&nodes, 5).await;
+    assert_ring(&nodes);
+}
+
+#[tokio::test]
+async fn keys_can_be_found_from_every_node() {
+    let network = MemoryNetwork::new();
+    let nodes = start_ring("keys", &network, 5).await;
+    stabilize(&nodes, 5).await;
+
+    let keys: Vec<String> = (0..20).map(|i| format!("key{}", i)).collect();
+    for (i, key) in keys.iter().enumerate() {
+        nodes[i % nodes.len()].insert(key.clone()).await.unwrap();
+    }
+    for node in &nodes {
+        for key in &keys {
+            assert!(node.contains(key).await.unwrap(), "{} not found", key);
+        }
+        assert!(!node.contains("missing").await.unwrap());
+    }
+}
+
+#[tokio::test]
+async fn deleted_keys_are_gone_from_their_replicas_too() {
+    let network = MemoryNetwork::new();
+    let nodes = start_ring("delete", &network, 5).await;
+    stabilize(&nodes, 5).await;
+
+    let keys: Vec<String> = (0..10).map(|i| format!("key{}", i)).collect();
+    for key in &keys {
+        nodes[0].insert(key.clone()).await.unwrap();
+    }
+    stabilize(&nodes, 2).await;
+    for (i, key) in keys.iter().enumerate() {
+        assert!(nodes[i % nodes.len()].delete(key).await.unwrap());
+        assert!(!nodes[0].delete(key).await.unwrap());
+    }
+
+    // the replicas don't bring the keys back once their owners are gone.
+    let owner = nodes[0]
+        .calculate_successor(&get_identifier(&keys[0]).to_string())
+        .await
+        .unwrap();
+    network.crash(owner);
+    let survivors: Vec<ChordNode> = nodes
+        .into_iter()
+        .filter(|node| node.self_ip() != owner)
+        .collect();
+    // the first request for the key reports its owner as down, and maintenance hands its range over to its successor.
+    assert!(survivors[0].contains(&keys[0]).await.is_err());
+    stabilize(&survivors, 5).await;
+    for node in &survivors {
+        let info: serde_json::Value = serde_json::from_str(&node.info()).unwrap();
+        let owned = info["hash_set"].as_array().unwrap();
+        assert!(owned.is_empty(), "{} owns {:?}", node.self_ip(), owned);
+    }
+}
+
+#[tokio::test]
+async fn ring_routes_around_a_crashed_node() {
+    let network = MemoryNetwork::new();
+    let nodes = start_ring("crash", &network, 5).await;
+    stabilize(&nodes, 5).await;
+
+    // a key owned by the node that is about to crash.
+    let victim = nodes[2].self_ip();
+    let mut key = String::new();
+    for i in 0.. {
+        key = format!("key{}", i);
+        let id = get_identifier(&key).to_string();
+        if nodes[0].calculate_successor(&id).await.unwrap() == victim {
+            break;
+        }
+    }
+    nodes[0].insert(key.clone()).await.unwrap();
+    stabilize(&nodes, 2).await;
+
+    network.crash(victim);
+    let asking = &nodes[(2 + 3) % nodes.len()];
+    let error = asking.contains(&key).await.unwrap_err();
+    assert!(matches!(error, ChordError::Unreachable { node, .. } if node == victim));
+
+    let survivors: Vec<ChordNode> = nodes
+        .into_iter()
+        .filter(|node| node.self_ip() != victim)
+        .collect();
+    stabilize(&survivors, 5).await;
+    assert_ring(&survivors);
+    for node in &survivors {
+        assert!(node.contains(&key).await.unwrap());
+    }
+
+    // once no finger points to the victim anymore, the survivors forget about it and stop repairing their pointers every round.
+    for node in &survivors {
+        node.run_now(Maintenance::Fingers).await;
+    }
+    stabilize(&survivors, 1).await;
+    let repairs = |node: &ChordNode| handle_failures(&node.render_metrics());
+    let before: Vec<f64> = survivors.iter().map(repairs).collect();
+    stabilize(&survivors, 2).await;
+    for (node, before) in survivors.iter().zip(before) {
+        let info: serde_json::Value = serde_json::from_str(&node.info()).unwrap();
+        assert_eq!(
+            info["suspects"],
+            serde_json::json!([]),
+            "{}",
+            node.self_ip()
+        );
+        assert_eq!(repairs(node), before);
+    }
+}
+
+/// returns how many times `handle_failure` ran, from the metrics of a node.
+fn handle_failures(metrics: &str) -> f64 {
+    metrics
+        .lines()
+        .find_map(|line| line.strip_prefix("crust_handle_failure_total "))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+fn bracket(open: bool) -> Bracket {
+    if open {
+        Bracket::Open
+    } else {
+        Bracket::Closed
+    }
+}
+
+#[test]
+fn intervals_contain_what_walking_the_ring_finds() {
+    let size = 1 << RING_BITS;
+    for (open_start, open_end) in [(true, true), (true, false), (false, true), (false, false)] {
+        for from in 0..size {
+            for to in 0..size {
+                // the IDs met walking clockwise from the first ID of the interval to its last.
+                let mut id = if open_start { (from + 1) % size } else { from };
+                let last = if open_end { (to + size - 1) % size } else { to };
+                let mut walked = HashSet::from([id]);
+                while id != last {
+                    id = (id + 1) % size;
+                    walked.insert(id);
+                }
+                let interval = Interval::new(bracket(open_start), from, to, bracket(open_end));
+                for id in 0..size {
+                    assert_eq!(
+                        interval.contains(id),
+                        walked.contains(&id),
+                        "{} in {}",
+                        id,
+                        interval
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn finger_updates_out_of_range_are_refused() {
+    let network = MemoryNetwork::new();
+    let nodes = start_ring("fingers", &network, 2).await;
+    let (a, b) = (&nodes[0], &nodes[1]);
+
+    let error = network
+        .update_finger_table(a, b.self_ip(), a.self_ip(), RING_BITS as u64)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ChordError::BadRequest(_)), "{:?}", error);
+    // the node is still usable: the lock of its state wasn't poisoned.
+    stabilize(&nodes, 3).await;
+    assert_ring(&nodes);
+    assert!(serde_json::from_str::<serde_json::Value>(&b.info()).is_ok());
+}
+
+#[tokio::test]
+async fn cut_links_are_reported_and_healed() {
+    let network = MemoryNetwork::new();
+    let nodes = start_ring("cut", &network, 3).await;
+    stabilize(&nodes, 5).await;
+
+    let (a, b) = (&nodes[0], nodes[0].get_successor());
+    network.cut(a.self_ip(), b);
+    assert!(a.maintenance_round().await.is_err());
+    network.heal(a.self_ip(), b);
+    stabilize(&nodes, 5).await;
+    assert_ring(&nodes);
+}
+
+#[tokio::test]
+async fn joining_through_an_incompatible_seed_is_refused() {
+    let network = MemoryNetwork::new();
+    let nodes = start_ring("incompatible", &network, 2).await;
+    let seed = nodes[0].self_ip();
+    network.set_hello(
+        seed,
+        Hello {
+            ring_bits: RING_BITS + 1,
+            hash: "sha1".to_string(),
+            ..local_hello()
+        },
+    );
+
+    let ip = addresses(3)[2];
+    let result = join(
+        ip,
+        seed,
+        first_incarnation(),
+        Config::default(),
+        storage("incompatible", ip),
+        Arc::new(network.clone()),
+    )
+    .await;
+    match result {
+        Err(ChordError::Incompatible(reason)) => {
+            assert!(reason.contains("bit IDs"), "{}", reason);
+            assert!(reason.contains("sha1"), "{}", reason);
+        }
+        other => panic!("expected an incompatible seed, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn seeds_of_another_minor_version_can_be_joined() {
+    let network = MemoryNetwork::new();
+    let nodes = start_ring("minor", &network, 2).await;
+    let seed = nodes[0].self_ip();
+    let major = PROTOCOL_VERSION.split('.').next().unwrap();
+    network.set_hello(
+        seed,
+        Hello {
+            protocol: format!("{}.999", major),
+            version: "999.0.0".to_string(),
+            ..local_hello()
+        },
+    );
+
+    let ip = addresses(3)[2];
+    let node = join(
+        ip,
+        seed,
+        first_incarnation(),
+        Config::default(),
+        storage("minor", ip),
+        Arc::new(network.clone()),
+    )
+    .await
+    .expect("join failed");
+    network.add(&node);
+    let mut nodes = nodes;
+    nodes.push(node);
+    stabilize(&nodes, 5).await;
+    assert_ring(&nodes);
+}
+
+#[tokio::test]
+async fn nodes_with_another_replication_factor_cant_join() {
+    let network = MemoryNetwork::new();
+    let nodes = start_ring("replication", &network, 2).await;
+    let ip = addresses(3)[2];
+    let result = join(
+        ip,
+        nodes[0].self_ip(),
+        first_incarnation(),
+        Config {
+            replication_factor: REPLICATION_FACTOR - 1,
+            ..Config::default()
+        },
+        storage("replication", ip),
+        Arc::new(network.clone()),
+    )
+    .await;
+    match result {
+        Err(ChordError::Incompatible(reason)) => {
+            assert!(reason.contains("replication factor"), "{}", reason)
+        }
+        other => panic!("expected an incompatible seed, got {:?}", other.err()),
+    }
+}
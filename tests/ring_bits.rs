@@ -0,0 +1,64 @@
+//! Runs a ring with the largest IDs there can be. The number of ring bits is shared by the whole process, so these tests get a binary of their own.
+
+mod common;
+
+use common::{addresses, ring_of, stabilize};
+use crust::{get_identifier, Config, Maintenance, MemoryNetwork, MAX_RING_BITS};
+use std::sync::mpsc;
+use std::time::Duration;
+
+fn config() -> Config {
+    Config {
+        ring_bits: MAX_RING_BITS,
+        replication_factor: 2,
+        ..Config::default()
+    }
+}
+
+/// Builds a ring of 4 nodes, fixes every finger, and checks that keys can be found from every node.
+async fn settle_ring() {
+    let network = MemoryNetwork::new();
+    let ips = addresses(42, 4);
+    let nodes = ring_of(&network, &ips, config()).await;
+    assert_eq!(nodes[0].hello().ring_bits, MAX_RING_BITS);
+    assert!(ips
+        .iter()
+        .any(|ip| get_identifier(&ip.to_string()) >= 1 << 32));
+    for node in &nodes {
+        node.run_now(Maintenance::Fingers).await;
+    }
+    stabilize(&nodes, 2).await;
+
+    let keys: Vec<String> = (0..10).map(|i| format!("key{}", i)).collect();
+    for key in &keys {
+        nodes[0].insert(key.clone()).await.unwrap();
+    }
+    for node in &nodes {
+        for key in &keys {
+            assert!(
+                node.contains(key).await.unwrap(),
+                "{} on {}",
+                key,
+                node.self_ip()
+            );
+        }
+    }
+    let check = nodes[1].verify_ring().await;
+    assert!(check.healthy, "{:?}", check.violations);
+    assert_eq!(check.nodes.len(), 4);
+}
+
+#[test]
+fn a_ring_with_the_largest_ids_works() {
+    // lookups used to walk intervals one ID at a time, which never finished with this many IDs. The ring runs on a thread of its own, so that such a loop fails the test instead of hanging it.
+    let (done, finished) = mpsc::channel();
+    std::thread::spawn(move || {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(settle_ring());
+        let _ = done.send(());
+    });
+    finished
+        .recv_timeout(Duration::from_secs(30))
+        .expect("the ring failed or didn't settle in time");
+}
@@ -0,0 +1,99 @@
+//! Checks the load and key distribution report of a ring, over a `MemoryNetwork` and gRPC.
+
+mod common;
+
+use common::{addresses, create_node, ring_of};
+use crust::{get_identifier, RING_BITS};
+use crust::{serve_grpc, Config, GrpcTransport, MemoryNetwork, Security, Spread, Transport};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+#[tokio::test]
+async fn the_report_covers_the_whole_ring_and_every_key() {
+    let network = MemoryNetwork::new();
+    let nodes = ring_of(&network, &addresses(49, 4), Config::default()).await;
+    let keys: Vec<String> = (0..30).map(|i| format!("key{}", i)).collect();
+    for key in &keys {
+        nodes[0].insert(key.clone()).await.unwrap();
+    }
+
+    let stats = nodes[1].ring_stats().await.unwrap();
+    assert_eq!(stats.ring_size, 1 << RING_BITS);
+    assert_eq!(stats.nodes.len(), 4);
+    assert_eq!(stats.nodes[0].node, nodes[1].self_ip());
+    let widths: u64 = stats.nodes.iter().map(|node| node.range_width).sum();
+    assert_eq!(widths, stats.ring_size);
+    let shares: f64 = stats.nodes.iter().map(|node| node.share).sum();
+    assert!((shares - 1.0).abs() < 1e-9);
+
+    for (i, node) in stats.nodes.iter().enumerate() {
+        let previous = &stats.nodes[(i + 3) % 4];
+        assert_eq!(node.range, format!("({},{}]", previous.id, node.id));
+        let load = node.load.as_ref().unwrap();
+        assert_eq!(node.error, None);
+        // the keys the node owns are the keys in its range.
+        let owned = keys
+            .iter()
+            .map(|key| get_identifier(key))
+            .filter(|id| {
+                (id + stats.ring_size - previous.id - 1) % stats.ring_size < node.range_width
+            })
+            .count();
+        assert_eq!(load.keys, owned as u64, "{:?}", node);
+    }
+    // every insert was served by the owner of its key.
+    let key_ops: f64 = stats
+        .nodes
+        .iter()
+        .map(|node| node.load.as_ref().unwrap().key_ops_per_sec)
+        .sum();
+    assert!(key_ops > 0.0);
+
+    let summary = &stats.summary;
+    assert_eq!((summary.nodes, summary.reporting), (4, 4));
+    assert_eq!(summary.keys, 30);
+    assert_eq!(summary.range_width.mean, 16.0);
+    assert!(summary.range_width.max_to_mean >= 1.0);
+    assert_eq!(summary.keys_per_node.mean, 7.5);
+}
+
+#[test]
+fn spreads_measure_the_imbalance() {
+    let even = Spread::of(&[4.0, 4.0, 4.0, 4.0]);
+    assert_eq!((even.max_to_mean, even.cv, even.stddev), (1.0, 0.0, 0.0));
+
+    let skewed = Spread::of(&[1.0, 1.0, 1.0, 5.0]);
+    assert_eq!((skewed.min, skewed.max, skewed.mean), (1.0, 5.0, 2.0));
+    assert_eq!(skewed.max_to_mean, 2.5);
+    assert!((skewed.cv - 3f64.sqrt() / 2.0).abs() < 1e-9);
+
+    assert_eq!(Spread::of(&[]).max_to_mean, 0.0);
+    assert_eq!(Spread::of(&[0.0, 0.0]).cv, 0.0);
+}
+
+#[tokio::test]
+async fn peers_report_their_load_over_grpc() {
+    let server = IpAddr::V4(Ipv4Addr::new(127, 0, 49, 1));
+    let network = MemoryNetwork::new();
+    let node = create_node(&network, server, Config::default());
+    node.insert("apple".to_string()).await.unwrap();
+    node.insert_replica(vec!["pear".to_string(), "plum".to_string()]);
+    let serving = node.clone();
+    tokio::spawn(async move { serve_grpc(serving, server, &Security::default()).await });
+
+    let caller = create_node(
+        &network,
+        IpAddr::V4(Ipv4Addr::new(10, 0, 49, 200)),
+        Config::default(),
+    );
+    let grpc = GrpcTransport::new();
+    for _ in 0..50 {
+        if let Ok(load) = grpc.load(&caller, server).await {
+            assert_eq!((load.keys, load.replicas), (1, 2));
+            assert!(load.key_ops_per_sec > 0.0);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("The gRPC server never answered");
+}
@@ -0,0 +1,54 @@
+//! Serves the gRPC peer protocol over mutual TLS with a generated test CA.
+
+use crust::{create_ring, serve_grpc, GrpcTransport, MemoryNetwork, PersistedState, Storage};
+use crust::{Config, Security, TestCa, Transport};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn node(ip: IpAddr) -> crust::ChordNode {
+    let dir = std::env::temp_dir().join(format!("crust-tls-{}-{}", std::process::id(), ip));
+    let _ = std::fs::remove_dir_all(&dir);
+    create_ring(
+        ip,
+        PersistedState::default(),
+        Config::default(),
+        Storage::new(dir.to_str().unwrap()),
+        Arc::new(MemoryNetwork::new()),
+    )
+}
+
+#[tokio::test]
+async fn peers_need_a_certificate_from_the_cluster_ca() {
+    let ca = TestCa::generate().unwrap();
+    let tls = ca.tls_config();
+    let server = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let security = Security {
+        tls: Some(tls),
+        ..Security::default()
+    };
+    let serving = security.clone();
+    tokio::spawn(async move { serve_grpc(node(server), server, &serving).await });
+    let caller = node(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+
+    let peer = GrpcTransport::with_security(&security);
+    let mut hello = None;
+    for _ in 0..50 {
+        if let Ok(reply) = peer.hello(&caller, server).await {
+            hello = reply;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(hello.is_some(), "a peer of the cluster couldn't connect");
+
+    let other_ca = Security {
+        tls: Some(TestCa::generate().unwrap().tls_config()),
+        ..Security::default()
+    };
+    let stranger = GrpcTransport::with_security(&other_ca);
+    assert!(stranger.hello(&caller, server).await.is_err());
+
+    let plain = GrpcTransport::new();
+    assert!(plain.hello(&caller, server).await.is_err());
+}
@@ -0,0 +1,126 @@
+//! Verifies healthy and broken rings over a `MemoryNetwork`, and asks a node for its view over gRPC.
+
+mod common;
+
+use common::{addresses, create_node, ring_of, stabilize};
+use crust::{serve_grpc, ChordNode, Config, GrpcTransport, Maintenance, MemoryNetwork};
+use crust::{Security, Transport, Violation};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+/// Starts a ring on `ips`, with every pointer and finger fixed, and `keys` inserted and replicated. The nodes are sorted by ID.
+async fn healthy_ring(network: &MemoryNetwork, ips: &[IpAddr], keys: &[String]) -> Vec<ChordNode> {
+    let mut nodes = ring_of(network, ips, Config::default()).await;
+    for node in &nodes {
+        node.run_now(Maintenance::Fingers).await;
+    }
+    // the successor lists are built from the successors, so they need another round once those are fixed.
+    stabilize(&nodes, 2).await;
+    for key in keys {
+        nodes[0].insert(key.clone()).await.unwrap();
+    }
+    nodes.sort_by_key(|node| node.self_id());
+    nodes
+}
+
+#[tokio::test]
+async fn a_healthy_ring_has_no_violations() {
+    let network = MemoryNetwork::new();
+    let keys: Vec<String> = (0..20).map(|i| format!("key{}", i)).collect();
+    let nodes = healthy_ring(&network, &addresses(50, 4), &keys).await;
+
+    let check = nodes[2].verify_ring().await;
+    assert!(check.healthy, "{:?}", check.violations);
+    assert!(check.violations.is_empty());
+    let ips: Vec<IpAddr> = nodes.iter().map(ChordNode::self_ip).collect();
+    assert_eq!(check.nodes, ips);
+    assert_eq!(check.keys, 20);
+}
+
+#[tokio::test]
+async fn broken_pointers_and_missing_replicas_are_reported() {
+    let network = MemoryNetwork::new();
+    let keys = vec!["apple".to_string()];
+    let nodes = healthy_ring(&network, &addresses(51, 4), &keys).await;
+    let (a, b, c, d) = (&nodes[0], &nodes[1], &nodes[2], &nodes[3]);
+
+    // c forgets its predecessor.
+    c.update_predecessor(c.self_ip());
+    // a replica of the key goes missing.
+    let owner = nodes
+        .iter()
+        .find(|node| node.view().keys.contains(&keys[0]))
+        .unwrap();
+    let holder = nodes
+        .iter()
+        .find(|node| node.self_ip() != owner.self_ip())
+        .unwrap();
+    holder.delete_replica(keys.clone());
+
+    let check = d.verify_ring().await;
+    assert!(!check.healthy);
+    let violations = &check.violations;
+    assert!(violations.contains(&Violation::WrongPredecessor {
+        node: c.self_ip(),
+        found: c.self_ip(),
+        expected: b.self_ip(),
+    }));
+    assert!(violations.contains(&Violation::PointersDisagree {
+        node: b.self_ip(),
+        successor: c.self_ip(),
+        successors_predecessor: c.self_ip(),
+    }));
+    assert!(violations.contains(&Violation::MissingReplicas {
+        node: owner.self_ip(),
+        key: "apple".to_string(),
+        expected: 3,
+        found: 2,
+        missing: vec![holder.self_ip()],
+    }));
+    assert_eq!(violations.len(), 3, "{:?}", violations);
+
+    // once b is gone, a's successor and c's predecessor are wrong, and a's fingers that pointed to b.
+    network.crash(b.self_ip());
+    let check = a.verify_ring().await;
+    let violations = &check.violations;
+    assert!(violations
+        .iter()
+        .any(|v| matches!(v, Violation::Unreachable { node, .. } if *node == b.self_ip())));
+    assert!(violations.contains(&Violation::WrongSuccessor {
+        node: a.self_ip(),
+        found: b.self_ip(),
+        expected: c.self_ip(),
+    }));
+    assert!(violations
+        .iter()
+        .any(|v| matches!(v, Violation::WrongFinger { node, found, .. } if *node == a.self_ip() && *found == b.self_ip())));
+    assert!(!check.nodes.contains(&b.self_ip()));
+}
+
+#[tokio::test]
+async fn peers_report_their_view_over_grpc() {
+    let server = IpAddr::V4(Ipv4Addr::new(127, 0, 50, 1));
+    let network = MemoryNetwork::new();
+    let node = create_node(&network, server, Config::default());
+    node.insert("apple".to_string()).await.unwrap();
+    node.insert_replica(vec!["pear".to_string()]);
+    let serving = node.clone();
+    tokio::spawn(async move { serve_grpc(serving, server, &Security::default()).await });
+
+    let caller = create_node(
+        &network,
+        IpAddr::V4(Ipv4Addr::new(10, 0, 50, 200)),
+        Config::default(),
+    );
+    let grpc = GrpcTransport::new();
+    for _ in 0..50 {
+        if let Ok(view) = grpc.view(&caller, server).await {
+            assert_eq!(view, node.view());
+            assert_eq!((view.successor, view.predecessor), (server, server));
+            assert_eq!(view.keys, vec!["apple".to_string()]);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("The gRPC server never answered");
+}